@@ -89,11 +89,13 @@ fn parse_late_skindata(
             inner: (),
         },
     )?;
+    let data_size = reader.stream_position()? - ptr.offset_absolute();
     reader.seek(std::io::SeekFrom::Start(pos_saved))?;
 
     Ok(EXRelPtr::new_with_offset(
-        ptr.offset_relative(),
+        ptr.offset_relative().unwrap_or_default(),
         ptr.offset_absolute(),
+        data_size,
         inner,
     ))
 }
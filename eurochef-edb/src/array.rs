@@ -7,7 +7,7 @@ use std::{
     slice::{Iter, SliceIndex},
 };
 
-use crate::common::EXRelPtr;
+use crate::common::{write_relptr, EXRelPtr};
 
 #[derive(Clone)]
 pub struct EXGeoHashArray<T: BinRead + 'static> {
@@ -34,9 +34,24 @@ impl<T: BinRead> EXGeoHashArray<T> {
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     pub fn data(&self) -> &Vec<T> {
         &self.data
     }
+
+    pub fn data_mut(&mut self) -> &mut Vec<T> {
+        &mut self.data
+    }
+
+    /// Replaces the array contents, which will be written to a newly allocated location
+    pub fn set_data(&mut self, data: Vec<T>) {
+        self.array_size = data.len() as i16;
+        self.data = data;
+        self.rel_offset = EXRelPtr::new(());
+    }
 }
 
 impl<T: BinRead, I: SliceIndex<[T]>> Index<I> for EXGeoHashArray<T> {
@@ -75,6 +90,8 @@ where
                     .push(T::read_options(reader, endian, args.clone())?)
             }
 
+            let data_size = reader.stream_position()? - array.rel_offset.offset_absolute();
+            array.rel_offset.set_data_size(data_size);
             reader.seek(std::io::SeekFrom::Start(pos_saved))?;
         }
 
@@ -82,15 +99,40 @@ where
     }
 }
 
-impl<T: BinRead> BinWrite for EXGeoHashArray<T> {
-    type Args<'a> = ();
+impl<T: BinRead + BinWrite> BinWrite for EXGeoHashArray<T>
+where
+    for<'a> <T as BinWrite>::Args<'a>: Clone,
+{
+    type Args<'a> = <T as BinWrite>::Args<'a>;
     fn write_options<W: std::io::Write + std::io::Seek>(
         &self,
-        _writer: &mut W,
-        _endian: binrw::Endian,
-        _args: Self::Args<'_>,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
     ) -> binrw::BinResult<()> {
-        todo!()
+        // Arrays of unknown types don't carry any data, so keep the original size
+        let array_size = if size_of::<T>() == 0 {
+            self.array_size
+        } else {
+            i16::try_from(self.data.len()).map_err(|_| binrw::Error::AssertFail {
+                pos: writer.stream_position().unwrap_or_default(),
+                message: format!("Too many elements in array ({})", self.data.len()),
+            })?
+        };
+
+        array_size.write_options(writer, endian, ())?;
+        self._hash_size.write_options(writer, endian, ())?;
+
+        write_relptr::<_, _, i32>(
+            writer,
+            endian,
+            self.rel_offset.is_null(),
+            self.rel_offset.offset_absolute(),
+            self.rel_offset.data_size(),
+            0,
+            (!self.data.is_empty()).then_some(&self.data),
+            args,
+        )
     }
 }
 
@@ -160,9 +202,24 @@ impl<T: BinRead> EXRelArray<T> {
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     pub fn data(&self) -> &Vec<T> {
         &self.data
     }
+
+    pub fn data_mut(&mut self) -> &mut Vec<T> {
+        &mut self.data
+    }
+
+    /// Replaces the array contents, which will be written to a newly allocated location
+    pub fn set_data(&mut self, data: Vec<T>) {
+        self.array_size = data.len() as i32;
+        self.data = data;
+        self.rel_offset = EXRelPtr::new(());
+    }
 }
 
 impl<T: BinRead> BinRead for EXRelArray<T>
@@ -191,6 +248,8 @@ where
                     .push(T::read_options(reader, endian, args.clone())?)
             }
 
+            let data_size = reader.stream_position()? - array.rel_offset.offset_absolute();
+            array.rel_offset.set_data_size(data_size);
             reader.seek(std::io::SeekFrom::Start(pos_saved))?;
         }
 
@@ -198,15 +257,39 @@ where
     }
 }
 
-impl<T: BinRead> BinWrite for EXRelArray<T> {
-    type Args<'a> = ();
+impl<T: BinRead + BinWrite> BinWrite for EXRelArray<T>
+where
+    for<'a> <T as BinWrite>::Args<'a>: Clone,
+{
+    type Args<'a> = <T as BinWrite>::Args<'a>;
     fn write_options<W: std::io::Write + std::io::Seek>(
         &self,
-        _writer: &mut W,
-        _endian: binrw::Endian,
-        _args: Self::Args<'_>,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
     ) -> binrw::BinResult<()> {
-        todo!()
+        // Arrays of unknown types don't carry any data, so keep the original size
+        let array_size = if size_of::<T>() == 0 {
+            self.array_size
+        } else {
+            i32::try_from(self.data.len()).map_err(|_| binrw::Error::AssertFail {
+                pos: writer.stream_position().unwrap_or_default(),
+                message: format!("Too many elements in array ({})", self.data.len()),
+            })?
+        };
+
+        array_size.write_options(writer, endian, ())?;
+
+        write_relptr::<_, _, i32>(
+            writer,
+            endian,
+            self.rel_offset.is_null(),
+            self.rel_offset.offset_absolute(),
+            self.rel_offset.data_size(),
+            0,
+            (!self.data.is_empty()).then_some(&self.data),
+            args,
+        )
     }
 }
 
@@ -245,7 +328,7 @@ impl<T: BinRead + Serialize> Serialize for EXRelArray<T> {
 }
 
 #[binrw]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct EXGeoCommonArrayElement {
     pub hashcode: u32,
     pub section: u16,
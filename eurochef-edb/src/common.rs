@@ -1,10 +1,18 @@
-use std::{any::TypeId, fmt::Debug, ops::Deref};
-
-use binrw::{binrw, BinRead, BinReaderExt, BinWrite};
+use std::{
+    any::TypeId,
+    fmt::Debug,
+    io::{Seek, SeekFrom, Write},
+    ops::Deref,
+};
+
+use binrw::{binrw, BinRead, BinReaderExt, BinResult, BinWrite, Endian};
 use num::NumCast;
 use serde::Serialize;
 
-use crate::array::EXGeoCommonArrayElement;
+use crate::{
+    array::EXGeoCommonArrayElement,
+    writer::{DatabaseWriter, RelocTarget, RelocWriter},
+};
 
 pub type EXVector3 = [f32; 3]; // TODO: Replace with structs
 pub type EXVector = [f32; 4];
@@ -13,8 +21,11 @@ pub type EXVector2 = [f32; 2];
 // TODO: RelPtr16 generic
 #[derive(Clone)]
 pub struct EXRelPtr<T: BinRead = (), OT: BinRead + NumCast + NumSize = i32, const OFFSET: i64 = 0> {
-    offset: OT,
+    /// `None` when the relative offset is only known once the pointer is written
+    offset: Option<OT>,
     offset_absolute: u64,
+    /// Amount of bytes available at `offset_absolute` for writing the data back in place
+    data_size: u64,
 
     data: T,
 }
@@ -25,15 +36,31 @@ impl<T: BinRead, OT: BinRead + NumCast + NumSize, const OFFSET: i64> EXRelPtr<T,
         self.offset_absolute
     }
 
-    /// Returns the offset to the data relative to the start of the pointer
-    pub fn offset_relative(&self) -> i32 {
-        self.offset.to_i32().unwrap()
+    /// Returns the offset to the data relative to the start of the pointer, or `None` if the pointer hasn't been written yet
+    pub fn offset_relative(&self) -> Option<i32> {
+        self.offset.as_ref().map(|o| o.to_i32().unwrap())
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.offset_relative() == Some(0)
+    }
+
+    /// Creates a pointer that was read from a file, `data_size` is the amount of bytes `data` takes up at `offset_absolute`
+    pub fn new_with_offset(offset: OT, offset_absolute: u64, data_size: u64, data: T) -> Self {
+        Self {
+            offset: Some(offset),
+            offset_absolute,
+            data_size,
+            data,
+        }
     }
 
-    pub fn new_with_offset(offset: OT, offset_absolute: u64, data: T) -> Self {
+    /// Creates a pointer to data that will be written at `offset_absolute`, where `data_size` bytes have been reserved for it
+    pub fn new_in_place(offset_absolute: u64, data_size: u64, data: T) -> Self {
         Self {
-            offset,
+            offset: None,
             offset_absolute,
+            data_size,
             data,
         }
     }
@@ -41,6 +68,27 @@ impl<T: BinRead, OT: BinRead + NumCast + NumSize, const OFFSET: i64> EXRelPtr<T,
     pub fn data(self) -> T {
         self.data
     }
+
+    /// Data is written back in place, unless it no longer fits in its original location
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+
+    /// Replaces the pointed-to data, which will be written to a newly allocated location
+    pub fn set_data(&mut self, data: T) {
+        self.data = data;
+        self.offset = None;
+        self.offset_absolute = 0;
+        self.data_size = 0;
+    }
+
+    pub(crate) fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    pub(crate) fn set_data_size(&mut self, data_size: u64) {
+        self.data_size = data_size;
+    }
 }
 
 impl<'a, T: BinRead, OT: BinRead + NumCast + NumSize, const OFFSET: i64> BinRead
@@ -61,42 +109,148 @@ where
             as u64
             - OT::size_bytes() as u64;
 
-        let data = if TypeId::of::<T>() != TypeId::of::<()>() {
+        let (data, data_size) = if TypeId::of::<T>() != TypeId::of::<()>() {
             let pos_saved = reader.stream_position()?;
             reader.seek(std::io::SeekFrom::Start(offset_absolute))?;
 
             let inner = T::read_options(reader, endian, args)?;
+            let data_size = reader.stream_position()? - offset_absolute;
             reader.seek(std::io::SeekFrom::Start(pos_saved))?;
 
-            inner
+            (inner, data_size)
         } else {
             // Hack to return () (no-op)
-            T::read_options(reader, endian, args)?
+            (T::read_options(reader, endian, args)?, 0)
         };
 
         binrw::BinResult::Ok(Self {
-            offset,
+            offset: Some(offset),
             offset_absolute,
+            data_size,
             data,
         })
     }
 }
 
-impl<T: BinRead, OT: BinRead + NumCast + NumSize, const OFFSET: i64> BinWrite
-    for EXRelPtr<T, OT, OFFSET>
+impl<T, OT, const OFFSET: i64> BinWrite for EXRelPtr<T, OT, OFFSET>
+where
+    T: BinRead + BinWrite + 'static,
+    OT: BinRead + BinWrite + NumCast + NumSize,
+    for<'a> <OT as BinWrite>::Args<'a>: Default,
+    for<'a> <T as BinWrite>::Args<'a>: Clone,
 {
-    type Args<'a> = ();
+    type Args<'a> = <T as BinWrite>::Args<'a>;
 
     fn write_options<W: std::io::Write + std::io::Seek>(
         &self,
-        _writer: &mut W,
-        _endian: binrw::Endian,
-        _args: Self::Args<'_>,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
     ) -> binrw::BinResult<()> {
-        todo!()
+        let data = if TypeId::of::<T>() != TypeId::of::<()>() {
+            Some(&self.data)
+        } else {
+            None
+        };
+
+        write_relptr::<_, _, OT>(
+            writer,
+            endian,
+            self.is_null(),
+            self.offset_absolute,
+            self.data_size,
+            OFFSET,
+            data,
+            args,
+        )
     }
 }
 
+/// Writes a relative pointer at the current position of `writer`.
+///
+/// Pointers without data (`data == None`) keep pointing to `offset_absolute`, unless they are null.
+/// Data is written back in place when `offset_absolute` is known and it still fits in the `data_size` bytes there,
+/// otherwise it needs to be allocated by a [`RelocWriter`](crate::writer::RelocWriter) (leaving the original data unused).
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_relptr<W: Write + Seek, D: BinWrite, OT: BinWrite + NumCast + NumSize>(
+    writer: &mut W,
+    endian: Endian,
+    is_null: bool,
+    offset_absolute: u64,
+    data_size: u64,
+    bias: i64,
+    data: Option<&D>,
+    args: D::Args<'_>,
+) -> BinResult<()>
+where
+    for<'a> OT::Args<'a>: Default,
+    for<'a> D::Args<'a>: Clone,
+{
+    let ptr_pos = writer.stream_position()?;
+    let fits_in_place = match data {
+        Some(d) if offset_absolute != 0 => written_size(d, endian, args.clone())? <= data_size,
+        _ => false,
+    };
+
+    if let Some(relocwriter) = writer.downcast_to_relocwriter() {
+        let target = match data {
+            Some(d) if fits_in_place => {
+                relocwriter.write_at(offset_absolute, d, endian, args)?;
+                Some(RelocTarget::Absolute(offset_absolute))
+            }
+            Some(d) => Some(RelocTarget::Chunk(relocwriter.allocate(d, endian, args)?)),
+            None if is_null => None,
+            None => Some(RelocTarget::Absolute(offset_absolute)),
+        };
+
+        if let Some(target) = target {
+            relocwriter.add_fixup(ptr_pos, OT::size_bytes(), bias, endian, target);
+        }
+
+        // Placeholder, patched when the writer is finished
+        let placeholder: OT = NumCast::from(0).unwrap();
+        return placeholder.write_options(relocwriter, endian, Default::default());
+    }
+
+    if let Some(d) = data {
+        if !fits_in_place {
+            return Err(binrw::Error::Custom {
+                pos: ptr_pos,
+                err: Box::new(
+                    "Newly allocated or grown pointer data can only be written with a RelocWriter",
+                ),
+            });
+        }
+
+        writer.seek(SeekFrom::Start(offset_absolute))?;
+        d.write_options(writer, endian, args)?;
+        writer.seek(SeekFrom::Start(ptr_pos))?;
+    }
+
+    let offset = if is_null && data.is_none() {
+        0
+    } else {
+        offset_absolute as i64 - ptr_pos as i64 - bias
+    };
+
+    let offset: OT = NumCast::from(offset).ok_or_else(|| binrw::Error::AssertFail {
+        pos: ptr_pos,
+        message: format!(
+            "Relative offset {offset} does not fit in {} bytes",
+            OT::size_bytes()
+        ),
+    })?;
+
+    offset.write_options(writer, endian, Default::default())
+}
+
+/// Returns the amount of bytes `value` takes up when written, not counting any data it points to
+fn written_size<D: BinWrite>(value: &D, endian: Endian, args: D::Args<'_>) -> BinResult<u64> {
+    let mut writer = RelocWriter::new();
+    value.write_options(&mut writer, endian, args)?;
+    writer.stream_position().map_err(binrw::Error::Io)
+}
+
 impl<T: BinRead + Debug, OT: BinRead + NumCast + NumSize, const OFFSET: i64> Debug
     for EXRelPtr<T, OT, OFFSET>
 {
@@ -105,9 +259,10 @@ impl<T: BinRead + Debug, OT: BinRead + NumCast + NumSize, const OFFSET: i64> Deb
         self.data.fmt(f)?;
         f.write_str(
             format!(
-                ", addr=0x{:x}, offset=0x{:x}",
+                ", addr=0x{:x}, offset={}",
                 self.offset_absolute,
-                self.offset.to_i64().unwrap()
+                self.offset_relative()
+                    .map_or("unknown".to_string(), |o| format!("0x{o:x}"))
             )
             .as_str(),
         )?;
@@ -115,13 +270,21 @@ impl<T: BinRead + Debug, OT: BinRead + NumCast + NumSize, const OFFSET: i64> Deb
     }
 }
 
+impl<OT: BinRead + NumCast + NumSize, const OFFSET: i64> EXRelPtr<(), OT, OFFSET> {
+    /// Creates a pointer to an absolute address in the file, the relative offset is only known once the pointer is written
+    pub fn new_absolute(offset_absolute: u64) -> Self {
+        Self::new_in_place(offset_absolute, 0, ())
+    }
+}
+
 impl<T: BinRead + Debug> EXRelPtr<T> {
     /// This method is only meant as a hack for Default implementations
     pub fn new(v: T) -> Self {
         Self {
             data: v,
-            offset: 0,
+            offset: Some(0),
             offset_absolute: 0,
+            data_size: 0,
         }
     }
 }
@@ -1,12 +1,13 @@
 use std::{
-    io::{Read, Seek},
+    io::{Read, Seek, SeekFrom, Write},
     mem::transmute,
 };
 
 use crate::{
-    binrw::{BinReaderExt, Endian},
+    binrw::{BinReaderExt, BinWrite, BinWriterExt, Endian},
     header::EXGeoHeader,
     versions::Platform,
+    writer::RelocWriter,
     Hashcode, HashcodeUtils,
 };
 use tracing::{info, warn};
@@ -134,3 +135,335 @@ impl Read for EdbFile {
 pub trait EdbReaderMethods {
     fn add_reference(&mut self, file: Hashcode, reference: Hashcode);
 }
+
+/// Patches an existing EDB file, it does not lay out a new file from scratch.
+///
+/// Object payloads keep their original location, new and replaced data is appended to the end of the file.
+/// Arrays in the header that have been replaced (eg. with [`EXGeoHashArray::set_data`](crate::array::EXGeoHashArray::set_data)) are reallocated as well.
+/// Refpointers to objects that were moved to a new address (same hashcode, different address in the header) are updated when finishing,
+/// new objects only get a refpointer through [`Self::add_refpointer`]
+pub struct EdbPatcher {
+    pub endian: Endian,
+    pub platform: Platform,
+    pub header: EXGeoHeader,
+
+    /// Header as it was read from the file
+    original_header: EXGeoHeader,
+
+    data: Vec<u8>,
+
+    /// Size of the file before anything was appended
+    original_size: u64,
+
+    /// Alignment of appended data
+    pub alignment: u64,
+}
+
+impl EdbPatcher {
+    pub fn new(edb: &mut EdbFile) -> Result<Self> {
+        let mut data = vec![];
+        edb.seek(SeekFrom::Start(0))?;
+        edb.read_to_end(&mut data)?;
+
+        Ok(Self {
+            endian: edb.endian,
+            platform: edb.platform,
+            header: edb.header.clone(),
+            original_header: edb.header.clone(),
+            original_size: data.len() as u64,
+            data,
+            alignment: 16,
+        })
+    }
+
+    /// Current size of the output file, excluding pending allocations
+    pub fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Raw file data, excluding the header which is written in [`Self::finish`]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Writes `value` at an absolute address, replacing the data that was there
+    pub fn write_at<T: BinWrite>(
+        &mut self,
+        address: u64,
+        value: &T,
+        args: T::Args<'_>,
+    ) -> Result<()> {
+        let endian = self.endian;
+        self.with_writer(|w| {
+            w.seek(SeekFrom::Start(address))?;
+            value.write_options(w, endian, args)
+        })
+    }
+
+    /// Appends `value` to the end of the file, returning its absolute address
+    pub fn append<T: BinWrite>(&mut self, value: &T, args: T::Args<'_>) -> Result<u32> {
+        self.align();
+        let address = self.len() as u32;
+
+        let endian = self.endian;
+        self.with_writer(|w| {
+            w.seek(SeekFrom::End(0))?;
+            value.write_options(w, endian, args)
+        })?;
+
+        Ok(address)
+    }
+
+    /// Appends raw bytes to the end of the file, returning their absolute address
+    pub fn append_bytes(&mut self, bytes: &[u8]) -> u32 {
+        self.align();
+        let address = self.len() as u32;
+        self.data.extend_from_slice(bytes);
+
+        address
+    }
+
+    /// Adds a refpointer to an object in this file, returning its index
+    pub fn add_refpointer(&mut self, hashcode: Hashcode, address: u32) -> u32 {
+        let mut refpointers = self.header.refpointer_list.data().clone();
+        let mut refpointer = refpointers.first().cloned().unwrap_or_default();
+        refpointer.hashcode = hashcode;
+        refpointer.address = address;
+        refpointers.push(refpointer);
+        self.header.refpointer_list.set_data(refpointers);
+
+        self.header.refpointer_list.len() as u32 - 1
+    }
+
+    /// Points refpointers to objects that were moved to their new address
+    fn relocate_refpointers(&mut self) {
+        let original = object_addresses(&self.original_header);
+        let moved: Vec<(u32, u32)> = object_addresses(&self.header)
+            .into_iter()
+            .filter_map(|(hashcode, address)| {
+                original
+                    .iter()
+                    .find(|(h, a)| *h == hashcode && *a != address)
+                    .map(|&(_, old_address)| (old_address, address))
+            })
+            .collect();
+
+        for r in self.header.refpointer_list.data_mut() {
+            if let Some(&(_, new_address)) = moved.iter().find(|(old, _)| *old == r.address) {
+                r.address = new_address;
+            }
+        }
+    }
+
+    /// Writes the header and section tables, and returns the finished file
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        self.relocate_refpointers();
+
+        let header = self.header.clone();
+        let endian = self.endian;
+        self.with_writer(|w| {
+            w.seek(SeekFrom::Start(0))?;
+            header.write_options(w, endian, ())
+        })?;
+
+        // Appended data (including relocated header arrays and refpointers) has to stay loaded,
+        // so the base file covers the entire file as soon as anything was appended
+        let file_size = self.len() as u32;
+        let base_file_size = if self.len() == self.original_size {
+            self.header.base_file_size
+        } else {
+            file_size
+        };
+
+        let mut cur = std::io::Cursor::new(self.data);
+        cur.seek(SeekFrom::Start(0x14))?;
+        cur.write_type(&file_size, endian)?;
+        cur.write_type(&base_file_size, endian)?;
+
+        Ok(cur.into_inner())
+    }
+
+    pub fn write<W: Write>(self, writer: &mut W) -> Result<()> {
+        let data = self.finish()?;
+        writer.write_all(&data)?;
+
+        Ok(())
+    }
+
+    fn align(&mut self) {
+        let aligned_len = (self.len() + self.alignment - 1) / self.alignment * self.alignment;
+        self.data.resize(aligned_len as usize, 0);
+    }
+
+    fn with_writer(
+        &mut self,
+        f: impl FnOnce(&mut RelocWriter) -> binrw::BinResult<()>,
+    ) -> Result<()> {
+        let mut writer = RelocWriter::with_data(std::mem::take(&mut self.data));
+        writer.alignment = self.alignment;
+        let res = f(&mut writer);
+
+        // Put the data back, even if writing failed
+        self.data = writer.finish()?;
+        res?;
+
+        Ok(())
+    }
+}
+
+/// Hashcode and address of every object in the header arrays
+fn object_addresses(header: &EXGeoHeader) -> Vec<(Hashcode, u32)> {
+    let mut objects = vec![];
+    objects.extend(header.entity_list.iter().map(|e| &e.common));
+    objects.extend(header.anim_list.iter().map(|e| &e.common));
+    objects.extend(header.animskin_list.iter().map(|e| &e.common));
+    objects.extend(header.animscript_list.iter());
+    objects.extend(header.map_list.iter());
+    objects.extend(header.animmode_list.iter().map(|e| &e.common));
+    objects.extend(header.animset_list.iter().map(|e| &e.common));
+    objects.extend(header.particle_list.iter());
+    objects.extend(header.swoosh_list.iter());
+    objects.extend(header.spreadsheet_list.iter().map(|e| &e.common));
+    objects.extend(header.font_list.iter());
+    objects.extend(header.material_list.iter());
+    objects.extend(header.texture_list.iter().map(|e| &e.common));
+
+    objects.iter().map(|c| (c.hashcode, c.address)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Builds a minimal big endian (v259) EDB with a single texture and a refpointer to it
+    fn build_edb() -> Vec<u8> {
+        let mut cur = Cursor::new(vec![0u8; 0x130]);
+        let mut w = |offset: u64, values: &[u32]| {
+            cur.seek(SeekFrom::Start(offset)).unwrap();
+            for v in values {
+                cur.write_be(v).unwrap();
+            }
+        };
+
+        // Magic, hashcode, version, flags, time, file size, base file size
+        w(0x0, &[0x47454F4D, 0x80000001, 259, 0, 0, 0x130, 0x130]);
+        // Refpointer list (1 entry)
+        w(0x48, &[0x00010001, 0x120 - 0x4c]);
+        // Texture list (1 entry, hash size 1)
+        w(0xb8, &[0x00010001, 0xd0 - 0xbc]);
+        // Texture header
+        w(0xd0, &[0x06000000, 0, 0x100, 0, (16 << 16) | 16, 0, 0]);
+        // Texture payload
+        w(0x100, &[0xC0FFEE; 8]);
+        // Refpointer to the texture
+        w(0x120, &[0x06000000, 0, 0x100, 0]);
+
+        cur.into_inner()
+    }
+
+    #[test]
+    fn roundtrip_unmodified() {
+        let data = build_edb();
+        let mut edb = EdbFile::new(Box::new(Cursor::new(data.clone())), Platform::Pc).unwrap();
+
+        let writer = EdbPatcher::new(&mut edb).unwrap();
+        assert_eq!(writer.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn grow_section_table() {
+        let mut edb = EdbFile::new(Box::new(Cursor::new(build_edb())), Platform::Pc).unwrap();
+        let mut writer = EdbPatcher::new(&mut edb).unwrap();
+
+        let mut texture = writer.header.texture_list[0].clone();
+        texture.common.hashcode += 1;
+        texture.common.address = writer.append_bytes(&[0xAA; 0x20]);
+
+        let mut textures = writer.header.texture_list.data().clone();
+        textures.push(texture);
+        writer.header.texture_list.set_data(textures);
+
+        let data = writer.finish().unwrap();
+        let edb = EdbFile::new(Box::new(Cursor::new(data.clone())), Platform::Pc).unwrap();
+        assert_eq!(edb.header.file_size as usize, data.len());
+        assert_eq!(edb.header.base_file_size as usize, data.len());
+        assert_eq!(edb.header.texture_list.len(), 2);
+        assert_eq!(edb.header.texture_list[1].common.hashcode, 0x06000001);
+        assert_eq!(edb.header.texture_list[1].common.address, 0x130);
+        assert_eq!(edb.header.texture_list[0].width, 16);
+    }
+
+    #[test]
+    fn grow_base_file() {
+        // Base file that doesn't span the entire file
+        let mut data = build_edb();
+        data[0x18..0x1c].copy_from_slice(&0x100u32.to_be_bytes());
+
+        let mut edb = EdbFile::new(Box::new(Cursor::new(data)), Platform::Pc).unwrap();
+        let mut writer = EdbPatcher::new(&mut edb).unwrap();
+        let address = writer.append_bytes(&[0xAA; 0x20]);
+        writer.add_refpointer(0x06000001, address);
+
+        let data = writer.finish().unwrap();
+        let edb = EdbFile::new(Box::new(Cursor::new(data.clone())), Platform::Pc).unwrap();
+        assert_eq!(edb.header.file_size as usize, data.len());
+        assert_eq!(edb.header.base_file_size as usize, data.len());
+    }
+
+    #[test]
+    fn relocate_refpointers() {
+        let mut edb = EdbFile::new(Box::new(Cursor::new(build_edb())), Platform::Pc).unwrap();
+        let mut writer = EdbPatcher::new(&mut edb).unwrap();
+
+        let address = writer.append_bytes(&[0xAA; 0x20]);
+        writer.header.texture_list.data_mut()[0].common.address = address;
+        let index = writer.add_refpointer(0x06000001, address);
+
+        let data = writer.finish().unwrap();
+        let edb = EdbFile::new(Box::new(Cursor::new(data)), Platform::Pc).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(edb.header.refpointer_list.len(), 2);
+        assert_eq!(edb.header.refpointer_list[0].address, address);
+        assert_eq!(edb.header.refpointer_list[1].hashcode, 0x06000001);
+        assert_eq!(edb.header.refpointer_list[1].address, address);
+    }
+
+    /// Round-trips real EDB files from the folder in `EUROCHEF_EDB_FIXTURES`.
+    /// Game files can't be distributed with the repository, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn roundtrip_fixtures() {
+        let folder = std::env::var("EUROCHEF_EDB_FIXTURES")
+            .expect("EUROCHEF_EDB_FIXTURES should point to a folder with EDB files");
+
+        let mut tested = 0;
+        for entry in std::fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path
+                .extension()
+                .map_or(true, |e| !e.eq_ignore_ascii_case("edb"))
+            {
+                continue;
+            }
+
+            let data = std::fs::read(&path).unwrap();
+            let platform = Platform::from_path(&path).unwrap_or(Platform::Pc);
+            let mut edb = EdbFile::new(Box::new(Cursor::new(data.clone())), platform).unwrap();
+            let writer = EdbPatcher::new(&mut edb).unwrap();
+            assert!(
+                writer.finish().unwrap() == data,
+                "{} doesn't round-trip",
+                path.display()
+            );
+            tested += 1;
+        }
+
+        assert!(tested > 0, "No EDB files found");
+    }
+}
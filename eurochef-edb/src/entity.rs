@@ -1,5 +1,5 @@
 #![allow(non_camel_case_types)]
-use binrw::{binrw, BinRead, BinReaderExt};
+use binrw::{binrw, BinRead, BinReaderExt, BinWrite};
 use serde::Serialize;
 
 use crate::{
@@ -75,12 +75,16 @@ pub struct EXGeoMeshEntityData {
     #[brw(if(platform != Platform::Ps2))]
     index_count_all: u32, // 0x80
 
+    #[bw(ignore)]
     #[br(calc = if platform == Platform::Ps2 { tristrip_count_ps2 as u32 } else { tristrip_count_all })]
     pub tristrip_count: u32,
+    #[bw(ignore)]
     #[br(calc = if platform == Platform::Ps2 { vertex_count_ps2 as u32 } else { vertex_count_all })]
     pub vertex_count: u32,
+    #[bw(ignore)]
     #[br(calc = if platform == Platform::Ps2 { 0 } else { _unk7c_all })]
     pub _unk7c: u32,
+    #[bw(ignore)]
     #[br(calc = if platform == Platform::Ps2 { index_count_ps2 as u32 } else { index_count_all })]
     pub index_count: u32,
 }
@@ -111,6 +115,7 @@ pub struct EXGeoSplitEntity {
     _unk58: u32,
 
     #[br(count = entity_count, args { inner: (version, platform) })]
+    #[bw(args(version, platform))]
     pub entities: Vec<EXRelPtr<EXGeoEntity>>, // 0x5c
 }
 
@@ -179,3 +184,24 @@ impl BinRead for EXGeoEntity {
         })
     }
 }
+
+impl BinWrite for EXGeoEntity {
+    type Args<'a> = (u32, Platform);
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.type_code().write_options(writer, endian, ())?;
+
+        match self {
+            EXGeoEntity::Mesh(e) => e.write_options(writer, endian, args),
+            EXGeoEntity::Split(e) => e.write_options(writer, endian, args),
+            EXGeoEntity::MapZone(e) => e.write_options(writer, endian, args),
+            EXGeoEntity::Instance(e) => e.write_options(writer, endian, (args.0,)),
+            EXGeoEntity::UnknownType(_) => Ok(()),
+        }
+    }
+}
@@ -211,13 +211,14 @@ impl BinRead for EXGeoMeshEntity {
 impl BinWrite for EXGeoMeshEntity {
     type Args<'a> = (u32, Platform);
 
+    /// Only writes the entity header, the geometry data it points to is kept as-is
     fn write_options<W: std::io::Write + std::io::Seek>(
         &self,
-        _writer: &mut W,
-        _endian: binrw::Endian,
-        _args: Self::Args<'_>,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
     ) -> binrw::BinResult<()> {
-        todo!()
+        self.data.write_options(writer, endian, args)
     }
 }
 
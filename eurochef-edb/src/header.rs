@@ -40,9 +40,11 @@ pub struct EXGeoHeader {
     pub section_list: EXGeoHashArray<()>, // 0x40
     pub refpointer_list: EXGeoHashArray<EXGeoRefPointerHeader>,
     #[br(args(version))]
+    #[bw(args(*version))]
     pub entity_list: EXGeoHashArray<EXGeoEntityHeader>, // 0x50
     pub anim_list: EXGeoHashArray<EXGeoAnimHeader>,
     #[br(args(version))]
+    #[bw(args(*version))]
     pub animskin_list: EXGeoHashArray<EXGeoAnimSkinHeader>, // 0x60
     pub animscript_list: EXGeoHashArray<EXGeoAnimScriptHeader>,
     pub map_list: EXGeoHashArray<EXGeoMapHeader>, // 0x70
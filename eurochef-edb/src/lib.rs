@@ -12,6 +12,7 @@ pub mod text;
 pub mod texture;
 pub mod util;
 pub mod versions;
+pub mod writer;
 
 // Re-export binrw
 pub use binrw;
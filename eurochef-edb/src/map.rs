@@ -33,7 +33,8 @@ pub struct EXGeoMap {
 
     pub bounds_box: [EXVector3; 2], // 0x6c
 
-    #[serde(skip)]
+    #[br(temp)]
+    #[bw(calc = zones.len() as u32)]
     num_zones: u32, // 0x84
 
     #[brw(if(version.eq(&205)))]
@@ -43,12 +44,13 @@ pub struct EXGeoMap {
         count: num_zones as usize,
         inner: (version,)
     })]
+    #[bw(args(version))]
     pub zones: Vec<EXGeoMapZone>, // 0x88
 }
 
 #[binrw]
 #[derive(Debug, Serialize, Clone)]
-#[br(import(version: u32))]
+#[brw(import(version: u32))]
 // TODO(cohae): Struct is not accurate below version 248 yet
 pub struct EXGeoMapZone {
     pub entity_refptr: u32,                    // 0x0
@@ -66,13 +68,13 @@ pub struct EXGeoMapZone {
     pub hash_ref: u32,                   // 0x30
     pub section: u32,                    // 0x34
     pub unk38: [u32; 10],                // 0x38
-    #[br(if(version.ne(&213) && version.ne(&221) && version.ne(&177) && version.ne(&240)))]
+    #[brw(if(version.ne(&213) && version.ne(&221) && version.ne(&177) && version.ne(&240)))]
     pub unk60: [u32; 2],
     pub bounds_box: [EXVector3; 2], // 0x60
     pub unk80: u32,                 // 0x80
 
     // Robots has 8 less bytes
-    #[br(if(!version.le(&248) || (version.eq(&213) || version.eq(&221) || version.eq(&177) || version.eq(&240))))]
+    #[brw(if(!version.le(&248) || (version.eq(&213) || version.eq(&221) || version.eq(&177) || version.eq(&240))))]
     pub unk84: [u32; 2], // 0x84
 }
 
@@ -174,6 +176,7 @@ pub struct EXGeoTrigger {
     #[br(parse_with = parse_trigdata_values, args(trig_flags))]
     pub data: [Option<u32>; 16],
     #[br(parse_with = parse_trigdata_link, args(trig_flags))]
+    #[bw(write_with = write_trigdata_link, args(*trig_flags))]
     pub links: [i32; 8],
 
     #[br(args(trig_flags))]
@@ -217,6 +220,17 @@ fn parse_trigdata_link(trig_flags: u32) -> BinResult<[i32; 8]> {
     Ok(res)
 }
 
+#[binrw::writer(writer, endian)]
+fn write_trigdata_link(links: &[i32; 8], trig_flags: u32) -> BinResult<()> {
+    for i in 16..24 {
+        if trig_flags.is_set(i) {
+            links[i - 16].write_options(writer, endian, ())?;
+        }
+    }
+
+    Ok(())
+}
+
 impl BinRead for EXGeoTriggerEngineOptions {
     type Args<'a> = (u32,);
 
@@ -270,11 +284,21 @@ impl BinWrite for EXGeoTriggerEngineOptions {
 
     fn write_options<W: std::io::Write + std::io::Seek>(
         &self,
-        _writer: &mut W,
-        _endian: binrw::Endian,
+        writer: &mut W,
+        endian: binrw::Endian,
         _args: Self::Args<'_>,
     ) -> BinResult<()> {
-        todo!()
+        // Field presence is determined by the trigger flags, which are written by EXGeoTrigger
+        self.visual_object.write_options(writer, endian, ())?;
+        self.visual_object_file.write_options(writer, endian, ())?;
+        self.gamescript_index.write_options(writer, endian, ())?;
+        self.collision_index.write_options(writer, endian, ())?;
+        self.trigger_color.write_options(writer, endian, ())?;
+        self._unk5.write_options(writer, endian, ())?;
+        self._unk6.write_options(writer, endian, ())?;
+        self._unk7.write_options(writer, endian, ())?;
+
+        Ok(())
     }
 }
 
@@ -451,6 +475,19 @@ impl BinRead for EXGeoBspTree {
     }
 }
 
+impl BinWrite for EXGeoBspTree {
+    type Args<'a> = ();
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        self.0.write_options(writer, endian, ())
+    }
+}
+
 #[binrw]
 #[derive(Debug, Serialize, Clone)]
 pub struct EXGeoBspNode {
@@ -485,3 +522,16 @@ impl BinRead for EXGeoTriggerCollision {
         Ok(Self(datums))
     }
 }
+
+impl BinWrite for EXGeoTriggerCollision {
+    type Args<'a> = ();
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        self.0.write_options(writer, endian, ())
+    }
+}
@@ -41,6 +41,7 @@ pub struct EXGeoTexture {
     /// The first frame offset will be replaced with a texture hashcode from that file
     #[br(map = |x: i32| if x == -1 { None } else { Some(x as u32) } )]
     #[br(if(version >= 250))]
+    #[bw(if(version >= 250), map = |x: &Option<u32>| x.map(|v| v as i32).unwrap_or(-1))]
     pub external_file: Option<u32>, // 0x1c
 
     animseq_data: EXRelPtr<(), i16>, // 0x1c, OFFSET.W ANIMSEQDATA
@@ -4,7 +4,7 @@ use binrw::Endian;
 use std::{
     fmt::Display,
     fs::File,
-    io::{BufReader, Seek},
+    io::{BufReader, Read, Seek},
     path::Path,
 };
 use tracing::error;
@@ -45,6 +45,17 @@ impl Platform {
         let crap: File = File::open(trop).ok()?;
         let mut reader = BufReader::new(&crap);
 
+        Self::from_reader(&mut reader, path)
+    }
+
+    /// Detects the platform from the EDB header, falling back to the `_bin_` directory in the given path
+    pub fn from_reader<R, P>(reader: &mut R, path: P) -> Option<Self>
+    where
+        R: Read + Seek,
+        P: AsRef<Path>,
+    {
+        reader.rewind().ok();
+
         // swy: the magic value is the four-byte GEOM tag, for big endian the G '0x47' shows first,
         //      otherwise it's the M (0x4D) of MOEG; looks reversed to humans, little-endian
         let endian = if reader.read_ne::<u8>().ok()? == b'G'
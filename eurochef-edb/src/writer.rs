@@ -0,0 +1,278 @@
+use std::{
+    io::{Cursor, Seek, SeekFrom, Write},
+    mem::{size_of, transmute},
+};
+
+use binrw::{BinResult, BinWrite, Endian};
+use num::NumCast;
+
+pub trait DatabaseWriter: Write + Seek {
+    fn downcast_to_relocwriter(&mut self) -> Option<&mut RelocWriter>;
+}
+
+impl<W: Write + Seek + Sized> DatabaseWriter for W {
+    fn downcast_to_relocwriter(&mut self) -> Option<&mut RelocWriter> {
+        // Bail out early on anything that can't possibly be a RelocWriter
+        if size_of::<W>() != size_of::<RelocWriter>() {
+            return None;
+        }
+
+        // Safety: as long as the safety marker is present, we are good to downcast
+        unsafe {
+            let ptr: *mut RelocWriter = transmute(self as *mut _);
+
+            // Check alignment and safety marker
+            if (ptr as usize & 0x7) == 0 && (*ptr).safety_marker == RelocWriter::SAFETY_MARKER {
+                Some(&mut *ptr)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Where a relative pointer should end up pointing to once the writer is finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocTarget {
+    /// Absolute offset in the output file
+    Absolute(u64),
+
+    /// Start of an allocated chunk
+    Chunk(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Fixup {
+    chunk: usize,
+    position: u64,
+    size: usize,
+    bias: i64,
+    endian: Endian,
+    target: RelocTarget,
+}
+
+/// In-memory writer that allocates space for data referenced by relative pointers.
+///
+/// Pointed-to data is written into separate chunks that are appended after the main data when
+/// the writer is finished, after which all relative offsets are patched.
+/// Pointers that were read from a file keep pointing at their original location, and their
+/// data is written back in place.
+#[repr(C)]
+pub struct RelocWriter {
+    /// Using a marker to allow for safe downcasting from within BinWrite implementations
+    safety_marker: u64,
+
+    chunks: Vec<Cursor<Vec<u8>>>,
+    current: usize,
+    fixups: Vec<Fixup>,
+
+    /// Alignment of allocated chunks
+    pub alignment: u64,
+}
+
+impl RelocWriter {
+    pub const SAFETY_MARKER: u64 = 0xDEADC0FF57524954;
+
+    pub fn new() -> Self {
+        Self::with_data(vec![])
+    }
+
+    /// Creates a writer on top of existing data, eg. a file that needs to be patched
+    pub fn with_data(data: Vec<u8>) -> Self {
+        Self {
+            safety_marker: Self::SAFETY_MARKER,
+            chunks: vec![Cursor::new(data)],
+            current: 0,
+            fixups: vec![],
+            alignment: 4,
+        }
+    }
+
+    /// Writes `value` into a newly allocated chunk, returning the index of the chunk
+    pub fn allocate<T: BinWrite>(
+        &mut self,
+        value: &T,
+        endian: Endian,
+        args: T::Args<'_>,
+    ) -> BinResult<usize> {
+        let chunk = self.chunks.len();
+        self.chunks.push(Cursor::new(vec![]));
+
+        let previous = self.current;
+        self.current = chunk;
+        let res = value.write_options(self, endian, args);
+        self.current = previous;
+
+        res.map(|_| chunk)
+    }
+
+    /// Writes `value` at an absolute offset in the main chunk, restoring the position afterwards
+    pub fn write_at<T: BinWrite>(
+        &mut self,
+        offset: u64,
+        value: &T,
+        endian: Endian,
+        args: T::Args<'_>,
+    ) -> BinResult<()> {
+        let previous = self.current;
+        self.current = 0;
+
+        let pos_saved = self.stream_position()?;
+        self.seek(SeekFrom::Start(offset))?;
+        let res = value.write_options(self, endian, args);
+        self.seek(SeekFrom::Start(pos_saved))?;
+
+        self.current = previous;
+        res
+    }
+
+    /// Registers a relative pointer at `position` in the current chunk, to be patched in [`Self::finish`]
+    pub fn add_fixup(
+        &mut self,
+        position: u64,
+        size: usize,
+        bias: i64,
+        endian: Endian,
+        target: RelocTarget,
+    ) {
+        self.fixups.push(Fixup {
+            chunk: self.current,
+            position,
+            size,
+            bias,
+            endian,
+            target,
+        });
+    }
+
+    /// Lays out all allocated chunks after the main data and patches all relative pointers
+    pub fn finish(self) -> BinResult<Vec<u8>> {
+        let mut chunks = self.chunks.into_iter().map(|c| c.into_inner());
+        let mut data = chunks.next().unwrap_or_default();
+        let mut bases = vec![0u64];
+        for chunk in chunks {
+            let aligned_len =
+                (data.len() as u64 + self.alignment - 1) / self.alignment * self.alignment;
+            data.resize(aligned_len as usize, 0);
+            bases.push(aligned_len);
+            data.extend(chunk);
+        }
+
+        let mut cur = Cursor::new(data);
+        for f in self.fixups {
+            let position = bases[f.chunk] + f.position;
+            let target = match f.target {
+                RelocTarget::Absolute(offset) => offset,
+                RelocTarget::Chunk(c) => bases[c],
+            };
+
+            let offset = target as i64 - position as i64 - f.bias;
+            let out_of_range = || binrw::Error::AssertFail {
+                pos: position,
+                message: format!("Relative offset {offset} does not fit in {} bytes", f.size),
+            };
+
+            cur.seek(SeekFrom::Start(position))?;
+            match f.size {
+                2 => i16::write_options(
+                    &NumCast::from(offset).ok_or_else(out_of_range)?,
+                    &mut cur,
+                    f.endian,
+                    (),
+                )?,
+                4 => i32::write_options(
+                    &NumCast::from(offset).ok_or_else(out_of_range)?,
+                    &mut cur,
+                    f.endian,
+                    (),
+                )?,
+                _ => return Err(out_of_range()),
+            }
+        }
+
+        Ok(cur.into_inner())
+    }
+}
+
+impl Default for RelocWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for RelocWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.chunks[self.current].write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for RelocWriter {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.chunks[self.current].seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::{BinReaderExt, BinWrite, Endian, NullWideString};
+
+    use super::*;
+    use crate::{common::EXRelPtr, text::EXGeoTextItem};
+
+    #[test]
+    fn allocate_relptr() {
+        let item = EXGeoTextItem {
+            hashcode: 0x1234,
+            string: EXRelPtr::new(NullWideString::from("Hello")),
+            userdata: EXRelPtr::new(()),
+            sound_hashcode: u32::MAX,
+        };
+
+        let mut writer = RelocWriter::new();
+        item.write_options(&mut writer, Endian::Big, ()).unwrap();
+        let data = writer.finish().unwrap();
+
+        let read: EXGeoTextItem = Cursor::new(data).read_type(Endian::Big).unwrap();
+        assert_eq!(read.string.to_string(), "Hello");
+        assert_eq!(read.string.offset_absolute(), 16);
+        assert!(read.userdata.is_null());
+    }
+
+    #[test]
+    fn relptr_i16_in_place() {
+        let ptr: EXRelPtr<u32, i16> = EXRelPtr::new_with_offset(0, 8, 4, 0xCAFEBABE);
+
+        let mut writer = RelocWriter::with_data(vec![0; 4]);
+        writer.seek(SeekFrom::Start(2)).unwrap();
+        ptr.write_options(&mut writer, Endian::Little, ()).unwrap();
+        let data = writer.finish().unwrap();
+
+        assert_eq!(data, [0, 0, 6, 0, 0, 0, 0, 0, 0xBE, 0xBA, 0xFE, 0xCA]);
+    }
+
+    #[test]
+    fn relptr_grown_data_is_reallocated() {
+        // Pointer to "Hi", followed by data that must not be overwritten
+        let mut original = vec![4, 0, 0, 0, b'H', 0, b'i', 0, 0, 0];
+        original.extend_from_slice(&0xDEADBEEFu32.to_le_bytes());
+
+        let mut ptr: EXRelPtr<NullWideString> =
+            Cursor::new(&original).read_type(Endian::Little).unwrap();
+        *ptr.data_mut() = NullWideString::from("Hello");
+
+        let mut writer = RelocWriter::with_data(original.clone());
+        ptr.write_options(&mut writer, Endian::Little, ()).unwrap();
+        let data = writer.finish().unwrap();
+
+        assert_eq!(data[..original.len()][4..], original[4..]);
+        let read: EXRelPtr<NullWideString> = Cursor::new(&data).read_type(Endian::Little).unwrap();
+        assert_eq!(read.to_string(), "Hello");
+        assert!(read.offset_absolute() >= original.len() as u64);
+    }
+}
@@ -1,11 +1,14 @@
 pub mod path;
+pub mod scr;
 pub mod structures;
 pub mod unified;
 pub mod v4;
 pub mod v5;
 pub mod v9;
+pub mod vfs;
 
-pub use unified::UXFileList;
+pub use unified::{FileList, UXFileList};
 pub use v4::EXFileList4;
 pub use v5::EXFileList5;
 pub use v9::EXFileList9;
+pub use vfs::Vfs;
@@ -34,6 +34,15 @@ pub fn unscramble_filename_v10(file_index: u32, bytes: &mut [u8]) {
     }
 }
 
+pub fn scramble_filename_v10(file_index: u32, bytes: &mut [u8]) {
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (*b as u32)
+            .wrapping_add(0x6a)
+            .wrapping_add(file_index * 4)
+            .wrapping_add(i as u32 * 4) as u8;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,4 +73,16 @@ mod tests {
 
         assert_eq!(&input, &output)
     }
+
+    #[test]
+    fn scramble_path_v10() {
+        let path = b"x:\\goldeneye\\binary\\_bin_wii\\fe_menu.edb\0";
+        let mut input = path.to_vec();
+
+        scramble_filename_v10(12, &mut input);
+        assert_ne!(&input, path);
+
+        unscramble_filename_v10(12, &mut input);
+        assert_eq!(&input, path)
+    }
 }
@@ -0,0 +1,329 @@
+//! Parser and writer for `.scr` filelist scripts
+//!
+//! The `[FileInfomation]` and `[FileList]` sections with one path per line are what `filelist extract --create-scr` writes.
+//! Everything else (the keys in `[FileInfomation]`, wildcards, per-file options and `!` directives) is eurochef's own extension,
+//! it is not derived from Eurocom's tools or real .scr files.
+//!
+//! ```text
+//! ; Comments start with ';' or '//'
+//! [FileInfomation]
+//! SplitSize=0x80000000
+//! Alignment=0x800
+//!
+//! [FileList]
+//! x:\gforce\binary\_bin_pc\*.edb                 wildcards: '*' within a folder, '?' or '#' for a single character
+//! x:\gforce\binary\_bin_pc\frontend.edb | align=0x20, filelist=1
+//! !filelist 2                                     store the next file in data file 2
+//! !split 0x40000000                               maximum data file size for all following files
+//! !align 0x800                                    alignment for all following files
+//! ```
+
+use std::{fmt::Display, io::Read};
+
+use anyhow::Context;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScrFile {
+    /// Key/value pairs from the `[FileInfomation]` section, in order
+    pub info: Vec<(String, String)>,
+    pub entries: Vec<ScrEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScrEntry {
+    /// Path or wildcard pattern, with options that only apply to the matching files
+    File {
+        pattern: String,
+        options: ScrFileOptions,
+    },
+    /// Store the next file in the given data file
+    Filelist(u32),
+    /// Maximum data file size for all following files
+    SplitSize(u32),
+    /// Alignment for all following files
+    Alignment(u32),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScrFileOptions {
+    pub alignment: Option<u32>,
+    pub filelist: Option<u32>,
+}
+
+#[derive(PartialEq)]
+enum Section {
+    None,
+    Info,
+    FileList,
+}
+
+impl ScrFile {
+    pub fn read<R: Read>(reader: &mut R) -> anyhow::Result<Self> {
+        let mut data = String::new();
+        reader.read_to_string(&mut data)?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &str) -> anyhow::Result<Self> {
+        let mut scr = Self::default();
+        let mut section = Section::None;
+
+        for (i, l) in data.lines().enumerate() {
+            let line = strip_comment(l).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = match &line[1..line.len() - 1] {
+                    // Also accept the correctly spelled section name
+                    "FileInfomation" | "FileInformation" => Section::Info,
+                    "FileList" => Section::FileList,
+                    _ => Section::None,
+                };
+                continue;
+            }
+
+            let entry = match section {
+                Section::None => continue,
+                Section::Info => {
+                    let (key, value) = line
+                        .split_once('=')
+                        .with_context(|| format!("Line {}: expected key=value", i + 1))?;
+                    scr.info
+                        .push((key.trim().to_string(), value.trim().to_string()));
+                    continue;
+                }
+                Section::FileList => {
+                    parse_entry(line).with_context(|| format!("Line {}: {}", i + 1, line))?
+                }
+            };
+
+            scr.entries.push(entry);
+        }
+
+        Ok(scr)
+    }
+
+    /// Looks up a value in the `[FileInfomation]` section, case-insensitive
+    pub fn info_value(&self, key: &str) -> Option<&str> {
+        self.info
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn split_size(&self) -> anyhow::Result<Option<u32>> {
+        self.info_value("SplitSize").map(parse_number).transpose()
+    }
+
+    pub fn alignment(&self) -> anyhow::Result<Option<u32>> {
+        self.info_value("Alignment").map(parse_number).transpose()
+    }
+}
+
+impl Display for ScrFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[FileInfomation]")?;
+        for (k, v) in &self.info {
+            writeln!(f, "{k}={v}")?;
+        }
+
+        writeln!(f, "\n[FileList]\n")?;
+        for e in &self.entries {
+            match e {
+                ScrEntry::File { pattern, options } => {
+                    write!(f, "{pattern}")?;
+
+                    let mut opts = vec![];
+                    if let Some(a) = options.alignment {
+                        opts.push(format!("align={a:#x}"));
+                    }
+                    if let Some(n) = options.filelist {
+                        opts.push(format!("filelist={n}"));
+                    }
+                    if !opts.is_empty() {
+                        write!(f, " | {}", opts.join(", "))?;
+                    }
+
+                    writeln!(f)?;
+                }
+                ScrEntry::Filelist(n) => writeln!(f, "!filelist {n}")?,
+                ScrEntry::SplitSize(s) => writeln!(f, "!split {s:#x}")?,
+                ScrEntry::Alignment(a) => writeln!(f, "!align {a:#x}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with(';') || trimmed.starts_with("//") {
+        ""
+    } else {
+        line
+    }
+}
+
+fn parse_entry(line: &str) -> anyhow::Result<ScrEntry> {
+    if let Some(directive) = line.strip_prefix('!') {
+        let (name, value) = directive
+            .split_once(char::is_whitespace)
+            .context("Directive is missing a value")?;
+        let value = parse_number(value.trim())?;
+
+        return Ok(match name.to_ascii_lowercase().as_str() {
+            "filelist" => ScrEntry::Filelist(value),
+            "split" => ScrEntry::SplitSize(value),
+            "align" => ScrEntry::Alignment(value),
+            d => anyhow::bail!("Unknown directive '{d}'"),
+        });
+    }
+
+    let (pattern, options_str) = match line.split_once('|') {
+        Some((p, o)) => (p.trim(), Some(o)),
+        None => (line, None),
+    };
+
+    if pattern.get(1..3) != Some(":\\") {
+        anyhow::bail!("Path must start with a drive letter (eg. x:\\)");
+    }
+
+    let mut options = ScrFileOptions::default();
+    for o in options_str.iter().flat_map(|o| o.split(',')) {
+        let (key, value) = o
+            .split_once('=')
+            .with_context(|| format!("Expected key=value, got '{}'", o.trim()))?;
+        let value = parse_number(value.trim())?;
+        match key.trim().to_ascii_lowercase().as_str() {
+            "align" => options.alignment = Some(value),
+            "filelist" => options.filelist = Some(value),
+            k => anyhow::bail!("Unknown file option '{k}'"),
+        }
+    }
+
+    Ok(ScrEntry::File {
+        pattern: pattern.to_string(),
+        options,
+    })
+}
+
+/// Parses a decimal or hexadecimal (0x) number
+pub fn parse_number(s: &str) -> anyhow::Result<u32> {
+    let s = s.trim();
+    let result = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    result.with_context(|| format!("Invalid number '{s}'"))
+}
+
+pub fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '#'])
+}
+
+/// Matches a path against a pattern, case-insensitive and treating both slash types the same.
+/// `*` matches any amount of characters within a folder, `?` and `#` match a single character
+pub fn matches_pattern(pattern: &str, path: &str) -> bool {
+    fn normalize(s: &str) -> Vec<char> {
+        s.chars()
+            .map(|c| {
+                if c == '/' {
+                    '\\'
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect()
+    }
+
+    fn matches(p: &[char], s: &[char]) -> bool {
+        match p.first() {
+            None => s.is_empty(),
+            Some('*') => {
+                // Try every possible length that doesn't cross a folder boundary
+                for i in 0..=s.len() {
+                    if matches(&p[1..], &s[i..]) {
+                        return true;
+                    }
+                    if i < s.len() && s[i] == '\\' {
+                        break;
+                    }
+                }
+                false
+            }
+            Some('?' | '#') => !s.is_empty() && s[0] != '\\' && matches(&p[1..], &s[1..]),
+            Some(c) => s.first() == Some(c) && matches(&p[1..], &s[1..]),
+        }
+    }
+
+    matches(&normalize(pattern), &normalize(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let data = "; Generated by hand
+[FileInfomation]
+SplitSize=0x1000
+
+[FileList]
+// Everything in the frontend folder
+x:\\gforce\\binary\\_bin_pc\\fe_*.edb
+x:\\gforce\\binary\\_bin_pc\\fe_menu.edb | align=0x20, filelist=1
+!filelist 2
+!split 0x2000
+!align 0x800
+x:\\gforce\\music\\track##.sfx
+";
+        let scr = ScrFile::parse(data).unwrap();
+        assert_eq!(scr.split_size().unwrap(), Some(0x1000));
+        assert_eq!(scr.entries.len(), 6);
+        assert_eq!(
+            scr.entries[1],
+            ScrEntry::File {
+                pattern: "x:\\gforce\\binary\\_bin_pc\\fe_menu.edb".to_string(),
+                options: ScrFileOptions {
+                    alignment: Some(0x20),
+                    filelist: Some(1),
+                },
+            }
+        );
+        assert_eq!(scr.entries[2], ScrEntry::Filelist(2));
+
+        assert_eq!(ScrFile::parse(&scr.to_string()).unwrap(), scr);
+    }
+
+    #[test]
+    fn invalid_paths() {
+        for path in ["gforce\\fe.edb", "é:\\fe.edb", "x"] {
+            assert!(ScrFile::parse(&format!("[FileList]\n{path}\n")).is_err());
+        }
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches_pattern(
+            "x:\\gforce\\binary\\_bin_pc\\fe_*.edb",
+            "X:\\GForce\\binary\\_bin_pc\\FE_menu.edb"
+        ));
+        assert!(!matches_pattern(
+            "x:\\gforce\\*.edb",
+            "x:\\gforce\\binary\\fe_menu.edb"
+        ));
+        assert!(matches_pattern(
+            "x:\\music\\track##.sfx",
+            "x:/music/track01.sfx"
+        ));
+        assert!(!matches_pattern(
+            "x:\\music\\track##.sfx",
+            "x:\\music\\track1.sfx"
+        ));
+    }
+}
@@ -80,7 +80,7 @@ pub struct EXFileListHeader9 {
 }
 
 #[binrw]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileInfo9 {
     pub unk0: u32,
     pub hashcode: u32,
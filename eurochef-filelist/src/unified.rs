@@ -1,7 +1,8 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
 
 use anyhow::Result;
-use binrw::{BinReaderExt, Endian};
+use binrw::{BinReaderExt, BinWriterExt, Endian};
 
 use crate::{EXFileList4, EXFileList5, EXFileList9};
 
@@ -13,15 +14,45 @@ pub struct UXFileList {
     pub files: Vec<(String, UXFileInfo)>,
 }
 
+#[derive(Debug, Clone)]
 pub struct UXFileInfo {
-    pub addr: u32,
-    pub filelist_num: Option<u32>,
+    /// Locations of the file in the data files, some discs store copies of a file in multiple data files to reduce seeking
+    pub locations: Vec<UXFileLoc>,
 
     pub length: u32,
     pub hashcode: u32,
     pub version: u32,
     pub flags: u32,
-    // ? Should we consider multiple filelocs?
+    /// Unknown value stored by version 9-10 filelists, 0 for other versions
+    pub unk0: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UXFileLoc {
+    pub addr: u32,
+    /// `None` when using a single '.dat' file
+    pub filelist_num: Option<u32>,
+    /// Unknown value stored by version 9-10 filelists, 0 for other versions
+    pub unk0: u32,
+}
+
+/// A version-specific filelist that can be converted from and to a [`UXFileList`]
+pub trait FileList: Sized {
+    /// Filelist versions supported by this implementation
+    const VERSIONS: RangeInclusive<u32>;
+
+    fn read<R>(reader: &mut R) -> Result<Self>
+    where
+        R: Read + Seek;
+
+    fn write<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write + Seek;
+
+    fn to_unified(self) -> UXFileList;
+
+    /// Fails if `version` is not in [`FileList::VERSIONS`]
+    fn from_unified(filelist: &UXFileList, version: u32) -> Result<Self>;
 }
 
 // TODO: We should probably have our own error types, considering that this is a library
@@ -49,10 +80,86 @@ impl UXFileList {
         reader.seek(std::io::SeekFrom::Start(0))?;
 
         Ok(match version {
-            4 => EXFileList4::read(reader)?.into(),
-            5..=7 => EXFileList5::read(reader)?.into(),
-            9..=10 => EXFileList9::read(reader)?.into(),
+            4 => EXFileList4::read(reader)?.to_unified(),
+            5..=7 => EXFileList5::read(reader)?.to_unified(),
+            9..=10 => EXFileList9::read(reader)?.to_unified(),
             v => return Err(anyhow::anyhow!("Unsupported filelist version {}", v)),
         })
     }
+
+    /// Writes the filelist header (.bin) as the given version, using the endianness of this filelist
+    pub fn write<W>(&self, writer: &mut W, version: u32) -> Result<()>
+    where
+        W: Write + Seek,
+    {
+        match version {
+            4 => EXFileList4::from_unified(self, version)?.write(writer),
+            5..=7 => EXFileList5::from_unified(self, version)?.write(writer),
+            9..=10 => EXFileList9::from_unified(self, version)?.write(writer),
+            v => Err(anyhow::anyhow!(
+                "Writing filelist version {} is not supported",
+                v
+            )),
+        }
+    }
+
+    pub fn is_version_supported(version: u32) -> bool {
+        EXFileList4::VERSIONS.contains(&version)
+            || EXFileList5::VERSIONS.contains(&version)
+            || EXFileList9::VERSIONS.contains(&version)
+    }
+}
+
+/// Writes the filename table at the current position and patches the header fields that depend on it.
+///
+/// `offset_field` is the position of `filename_list_offset` in the header, which is also the base of the offset.
+pub(crate) fn write_filename_table<W>(
+    writer: &mut W,
+    endian: Endian,
+    filenames: &[String],
+    offset_field: u64,
+    scramble: Option<fn(u32, &mut [u8])>,
+) -> Result<()>
+where
+    W: Write + Seek,
+{
+    let filename_offset = writer.stream_position()?;
+
+    let filename_data_offset = filename_offset + filenames.len() as u64 * 4;
+    let mut offset = filename_data_offset;
+    for (i, v) in filenames.iter().enumerate() {
+        let ptr_offset = filename_offset + i as u64 * 4;
+        writer.write_type(&((offset - ptr_offset) as u32), endian)?;
+        offset += v.len() as u64 + 1;
+    }
+
+    for (i, v) in filenames.iter().enumerate() {
+        let mut path_buf = v.as_bytes().to_vec();
+        path_buf.push(0);
+
+        if let Some(scramble) = scramble {
+            scramble(i as u32, &mut path_buf);
+        }
+
+        writer.write_all(&path_buf)?;
+    }
+
+    // Pad the file to 32 bytes
+    let unaligned_size = writer.stream_position()?;
+    if unaligned_size & 0x1f != 0 {
+        let remainder = 32 - (unaligned_size % 32);
+        writer.write_all(&vec![0u8; remainder as usize])?;
+    }
+
+    let file_size = writer.stream_position()?;
+
+    writer.seek(SeekFrom::Start(4))?;
+    writer.write_type(&(file_size as u32), endian)?;
+
+    writer.seek(SeekFrom::Start(offset_field))?;
+    writer.write_type(&((filename_offset - offset_field) as u32), endian)?;
+
+    writer.seek(SeekFrom::Start(file_size))?;
+
+    Ok(())
 }
@@ -1,10 +1,11 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
+use std::ops::RangeInclusive;
 
-use crate::structures::EXFileListHeader4;
-use crate::unified::{UXFileInfo, UXFileList};
+use crate::structures::{EXFileListHeader4, FileInfo4};
+use crate::unified::{write_filename_table, FileList, UXFileInfo, UXFileList, UXFileLoc};
 
-use anyhow::Result;
-use binrw::{BinReaderExt, Endian};
+use anyhow::{Context, Result};
+use binrw::{BinReaderExt, BinWriterExt, Endian};
 use itertools::Itertools;
 
 #[derive(Debug)]
@@ -14,8 +15,10 @@ pub struct EXFileList4 {
     pub filenames: Vec<String>,
 }
 
-impl EXFileList4 {
-    pub fn read<R>(reader: &mut R) -> Result<Self>
+impl FileList for EXFileList4 {
+    const VERSIONS: RangeInclusive<u32> = 4..=4;
+
+    fn read<R>(reader: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -61,6 +64,56 @@ impl EXFileList4 {
 
         Ok(res)
     }
+
+    fn write<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write + Seek,
+    {
+        writer.write_type(&self.header, self.endian)?;
+        write_filename_table(writer, self.endian, &self.filenames, 0xc, None)
+    }
+
+    fn to_unified(self) -> UXFileList {
+        self.into()
+    }
+
+    /// Version 4 filelists only have a single data file and location per file, so only the first location is used
+    fn from_unified(filelist: &UXFileList, version: u32) -> Result<Self> {
+        if !Self::VERSIONS.contains(&version) {
+            return Err(anyhow::anyhow!(
+                "Unsupported version {} for {}",
+                version,
+                std::any::type_name::<Self>()
+            ));
+        }
+
+        Ok(Self {
+            endian: filelist.endian,
+            header: EXFileListHeader4 {
+                version: 4,
+                filesize: 0,
+                filename_list_offset: 0,
+                fileinfo: filelist
+                    .files
+                    .iter()
+                    .map(|(path, info)| {
+                        Ok(FileInfo4 {
+                            addr: info
+                                .locations
+                                .first()
+                                .with_context(|| format!("File {path} has no locations"))?
+                                .addr,
+                            length: info.length,
+                            hashcode: info.hashcode,
+                            version: info.version,
+                            flags: info.flags,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            },
+            filenames: filelist.files.iter().map(|(p, _)| p.clone()).collect(),
+        })
+    }
 }
 
 impl From<EXFileList4> for UXFileList {
@@ -77,12 +130,16 @@ impl From<EXFileList4> for UXFileList {
                     (
                         filename,
                         UXFileInfo {
-                            addr: info.addr,
-                            filelist_num: None,
+                            locations: vec![UXFileLoc {
+                                addr: info.addr,
+                                filelist_num: None,
+                                unk0: 0,
+                            }],
                             flags: info.flags,
                             hashcode: info.hashcode,
                             length: info.length,
                             version: info.version,
+                            unk0: 0,
                         },
                     )
                 })
@@ -90,3 +147,30 @@ impl From<EXFileList4> for UXFileList {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_location() {
+        let filelist = UXFileList {
+            num_filelists: None,
+            build_type: None,
+            endian: Endian::Little,
+            files: vec![(
+                "x:\\binary\\_bin_pc\\a.edb".to_string(),
+                UXFileInfo {
+                    locations: vec![],
+                    length: 0x10,
+                    hashcode: 0x04000001,
+                    version: 128,
+                    flags: 0,
+                    unk0: 0,
+                },
+            )],
+        };
+
+        assert!(EXFileList4::from_unified(&filelist, 4).is_err());
+    }
+}
@@ -1,11 +1,12 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
+use std::ops::RangeInclusive;
 
-use crate::path::unscramble_filename_v7;
-use crate::structures::EXFileListHeader5;
-use crate::unified::{UXFileInfo, UXFileList};
+use crate::path::{scramble_filename_v7, unscramble_filename_v7};
+use crate::structures::{EXFileListHeader5, FileInfo5, FileLoc5};
+use crate::unified::{write_filename_table, FileList, UXFileInfo, UXFileList, UXFileLoc};
 
 use anyhow::Result;
-use binrw::{BinReaderExt, Endian};
+use binrw::{BinReaderExt, BinWriterExt, Endian};
 use itertools::Itertools;
 
 #[derive(Debug)]
@@ -15,8 +16,10 @@ pub struct EXFileList5 {
     pub filenames: Vec<String>,
 }
 
-impl EXFileList5 {
-    pub fn read<R>(reader: &mut R) -> Result<Self>
+impl FileList for EXFileList5 {
+    const VERSIONS: RangeInclusive<u32> = 5..=7;
+
+    fn read<R>(reader: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -68,9 +71,66 @@ impl EXFileList5 {
 
         Ok(res)
     }
+
+    fn write<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write + Seek,
+    {
+        writer.write_type(&self.header, self.endian)?;
+
+        let scramble: Option<fn(u32, &mut [u8])> = if self.header.version >= 7 {
+            Some(scramble_filename_v7)
+        } else {
+            None
+        };
+        write_filename_table(writer, self.endian, &self.filenames, 0x10, scramble)
+    }
+
+    fn to_unified(self) -> UXFileList {
+        self.into()
+    }
+
+    fn from_unified(filelist: &UXFileList, version: u32) -> Result<Self> {
+        if !Self::VERSIONS.contains(&version) {
+            return Err(anyhow::anyhow!(
+                "Unsupported version {} for {}",
+                version,
+                std::any::type_name::<Self>()
+            ));
+        }
+
+        Ok(Self {
+            endian: filelist.endian,
+            header: EXFileListHeader5 {
+                version,
+                filesize: 0,
+                build_type: filelist.build_type.unwrap_or(1),
+                num_filelists: filelist.num_filelists.unwrap_or(0),
+                filename_list_offset: 0,
+                fileinfo: filelist
+                    .files
+                    .iter()
+                    .map(|(_, info)| FileInfo5 {
+                        length: info.length,
+                        hashcode: info.hashcode,
+                        version: info.version,
+                        flags: info.flags,
+                        fileloc: info
+                            .locations
+                            .iter()
+                            .map(|l| FileLoc5 {
+                                addr: l.addr,
+                                filelist_num: l.filelist_num.unwrap_or(0),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            },
+            filenames: filelist.files.iter().map(|(p, _)| p.clone()).collect(),
+        })
+    }
 }
 
-// TODO: Make a trait for filelists bundling both the read and from/into functions so that they can be used genericly
 impl From<EXFileList5> for UXFileList {
     fn from(val: EXFileList5) -> Self {
         UXFileList {
@@ -85,12 +145,20 @@ impl From<EXFileList5> for UXFileList {
                     (
                         filename,
                         UXFileInfo {
-                            addr: info.fileloc[0].addr,
-                            filelist_num: Some(info.fileloc[0].filelist_num),
+                            locations: info
+                                .fileloc
+                                .iter()
+                                .map(|l| UXFileLoc {
+                                    addr: l.addr,
+                                    filelist_num: Some(l.filelist_num),
+                                    unk0: 0,
+                                })
+                                .collect(),
                             flags: info.flags,
                             hashcode: info.hashcode,
                             length: info.length,
                             version: info.version,
+                            unk0: 0,
                         },
                     )
                 })
@@ -1,11 +1,14 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
+use std::ops::RangeInclusive;
 
-use crate::path::{unscramble_filename_v10, unscramble_filename_v7};
-use crate::structures::EXFileListHeader9;
-use crate::unified::{UXFileInfo, UXFileList};
+use crate::path::{
+    scramble_filename_v10, scramble_filename_v7, unscramble_filename_v10, unscramble_filename_v7,
+};
+use crate::structures::{EXFileListHeader9, FileInfo9, FileLoc9};
+use crate::unified::{write_filename_table, FileList, UXFileInfo, UXFileList, UXFileLoc};
 
 use anyhow::Result;
-use binrw::{BinReaderExt, Endian};
+use binrw::{BinReaderExt, BinWriterExt, Endian};
 use itertools::Itertools;
 
 #[derive(Debug)]
@@ -15,8 +18,10 @@ pub struct EXFileList9 {
     pub filenames: Vec<String>,
 }
 
-impl EXFileList9 {
-    pub fn read<R>(reader: &mut R) -> Result<Self>
+impl FileList for EXFileList9 {
+    const VERSIONS: RangeInclusive<u32> = 9..=10;
+
+    fn read<R>(reader: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -70,9 +75,68 @@ impl EXFileList9 {
 
         Ok(res)
     }
+
+    fn write<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write + Seek,
+    {
+        writer.write_type(&self.header, self.endian)?;
+
+        let scramble: fn(u32, &mut [u8]) = if self.header.version >= 10 {
+            scramble_filename_v10
+        } else {
+            scramble_filename_v7
+        };
+        write_filename_table(writer, self.endian, &self.filenames, 0x10, Some(scramble))
+    }
+
+    fn to_unified(self) -> UXFileList {
+        self.into()
+    }
+
+    fn from_unified(filelist: &UXFileList, version: u32) -> Result<Self> {
+        if !Self::VERSIONS.contains(&version) {
+            return Err(anyhow::anyhow!(
+                "Unsupported version {} for {}",
+                version,
+                std::any::type_name::<Self>()
+            ));
+        }
+
+        Ok(Self {
+            endian: filelist.endian,
+            header: EXFileListHeader9 {
+                version,
+                filesize: 0,
+                build_type: filelist.build_type.unwrap_or(1),
+                num_filelists: filelist.num_filelists.unwrap_or(0),
+                filename_list_offset: 0,
+                fileinfo: filelist
+                    .files
+                    .iter()
+                    .map(|(_, info)| FileInfo9 {
+                        unk0: info.unk0,
+                        hashcode: info.hashcode,
+                        version: info.version,
+                        flags: info.flags,
+                        length: info.length,
+                        fileloc: info
+                            .locations
+                            .iter()
+                            .map(|l| FileLoc9 {
+                                unk0: l.unk0,
+                                addr: l.addr,
+                                filelist_num: l.filelist_num.unwrap_or(0),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            },
+            filenames: filelist.files.iter().map(|(p, _)| p.clone()).collect(),
+        })
+    }
 }
 
-// TODO: Make a trait for filelists bundling both the read and from/into functions so that they can be used genericly
 impl From<EXFileList9> for UXFileList {
     fn from(val: EXFileList9) -> Self {
         UXFileList {
@@ -87,12 +151,20 @@ impl From<EXFileList9> for UXFileList {
                     (
                         filename,
                         UXFileInfo {
-                            addr: info.fileloc[0].addr,
-                            filelist_num: Some(info.fileloc[0].filelist_num),
+                            locations: info
+                                .fileloc
+                                .iter()
+                                .map(|l| UXFileLoc {
+                                    addr: l.addr,
+                                    filelist_num: Some(l.filelist_num),
+                                    unk0: l.unk0,
+                                })
+                                .collect(),
                             flags: info.flags,
                             hashcode: info.hashcode,
                             length: info.length,
                             version: info.version,
+                            unk0: info.unk0,
                         },
                     )
                 })
@@ -100,3 +172,71 @@ impl From<EXFileList9> for UXFileList {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn roundtrip_unified() {
+        let filelist = EXFileList9 {
+            endian: Endian::Big,
+            header: EXFileListHeader9 {
+                version: 10,
+                filesize: 0,
+                build_type: 1,
+                num_filelists: 2,
+                filename_list_offset: 0,
+                fileinfo: vec![
+                    FileInfo9 {
+                        unk0: 0x12345678,
+                        hashcode: 0x04000001,
+                        version: 248,
+                        flags: 0,
+                        length: 0x800,
+                        fileloc: vec![
+                            FileLoc9 {
+                                unk0: 0xdeadbeef,
+                                addr: 0,
+                                filelist_num: 0,
+                            },
+                            FileLoc9 {
+                                unk0: 1,
+                                addr: 0x800,
+                                filelist_num: 1,
+                            },
+                        ],
+                    },
+                    FileInfo9 {
+                        unk0: 7,
+                        hashcode: 0x04000002,
+                        version: 248,
+                        flags: 1,
+                        length: 0x10,
+                        fileloc: vec![FileLoc9 {
+                            unk0: 2,
+                            addr: 0x1000,
+                            filelist_num: 0,
+                        }],
+                    },
+                ],
+            },
+            filenames: vec![
+                "x:\\binary\\_bin_gc\\a.edb".to_string(),
+                "x:\\binary\\_bin_gc\\b.edb".to_string(),
+            ],
+        };
+
+        let mut original = Cursor::new(vec![]);
+        filelist.write(&mut original).unwrap();
+        let original = original.into_inner();
+
+        let unified = UXFileList::read(&mut Cursor::new(&original)).unwrap();
+        let mut written = Cursor::new(vec![]);
+        unified.write(&mut written, 10).unwrap();
+
+        assert_eq!(written.into_inner(), original);
+    }
+}
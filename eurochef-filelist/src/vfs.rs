@@ -0,0 +1,245 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use binrw::BinReaderExt;
+
+use crate::{unified::UXFileInfo, UXFileList};
+
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Read-only view of the files inside a filelist
+pub struct Vfs {
+    pub filelist: UXFileList,
+
+    /// Path to the filelist header (.bin)
+    path: PathBuf,
+
+    /// Normalized paths to file indices
+    paths: HashMap<String, usize>,
+    hashcodes: HashMap<u32, usize>,
+}
+
+impl Vfs {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path)
+            .with_context(|| format!("Failed to open filelist {}", path.display()))?;
+        let filelist = UXFileList::read(&mut file)?;
+
+        let paths = filelist
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, (p, _))| (normalize_path(p), i))
+            .collect();
+        let hashcodes = filelist
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, (_, info))| (info.hashcode, i))
+            .collect();
+
+        Ok(Self {
+            filelist,
+            path: path.to_path_buf(),
+            paths,
+            hashcodes,
+        })
+    }
+
+    /// Splits a path that goes through a filelist into the filelist path and the path inside of it
+    ///
+    /// eg. `games/Filelist.bin/gforce/binary/_bin_pc/foo.edb` -> (`games/Filelist.bin`, `gforce/binary/_bin_pc/foo.edb`)
+    pub fn split_path<P: AsRef<Path>>(path: P) -> Option<(PathBuf, String)> {
+        let path = path.as_ref();
+        let filelist = path.ancestors().skip(1).find(|p| {
+            p.is_file()
+                && p.extension()
+                    .map_or(false, |e| e.eq_ignore_ascii_case("bin"))
+        })?;
+
+        let inner = path
+            .strip_prefix(filelist)
+            .ok()?
+            .to_string_lossy()
+            .to_string();
+        if inner.is_empty() {
+            return None;
+        }
+
+        Some((filelist.to_path_buf(), inner))
+    }
+
+    /// Paths of all files in the filelist, as stored in the filelist
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.filelist.files.iter().map(|(p, _)| p.as_str())
+    }
+
+    /// Looks up a file by path. Paths are case-insensitive, the drive letter is optional and both slash types are accepted
+    pub fn find(&self, path: &str) -> Option<(&str, &UXFileInfo)> {
+        let index = *self.paths.get(&normalize_path(path))?;
+        let (path, info) = &self.filelist.files[index];
+        Some((path.as_str(), info))
+    }
+
+    pub fn find_hashcode(&self, hashcode: u32) -> Option<(&str, &UXFileInfo)> {
+        let index = *self.hashcodes.get(&hashcode)?;
+        let (path, info) = &self.filelist.files[index];
+        Some((path.as_str(), info))
+    }
+
+    pub fn open_file(&self, path: &str) -> anyhow::Result<VfsFile> {
+        let (_, info) = self
+            .find(path)
+            .with_context(|| format!("File {path} not found in filelist"))?;
+
+        self.open_info(info)
+    }
+
+    pub fn open_hashcode(&self, hashcode: u32) -> anyhow::Result<VfsFile> {
+        let (_, info) = self
+            .find_hashcode(hashcode)
+            .with_context(|| format!("File {hashcode:x} not found in filelist"))?;
+
+        self.open_info(info)
+    }
+
+    /// Opens the first copy of a file
+    pub fn open_info(&self, info: &UXFileInfo) -> anyhow::Result<VfsFile> {
+        // Copies of a file are identical, so any location will do
+        let location = info
+            .locations
+            .first()
+            .context("File doesn't have any locations")?;
+        let data_path = self.data_file_path(location.filelist_num);
+        let mut file = File::open(&data_path)
+            .with_context(|| format!("Failed to open {}", data_path.display()))?;
+
+        file.seek(SeekFrom::Start(location.addr as u64))?;
+        let magic: u32 = file.read_type(self.filelist.endian)?;
+
+        // The filelist length isn't accurate for geometry files, the header contains the real size
+        let mut length = info.length;
+        if magic == 0x47454F4D {
+            file.seek(SeekFrom::Current(0x10))?;
+            length = file.read_type(self.filelist.endian)?;
+        }
+
+        Ok(VfsFile {
+            file,
+            start: location.addr as u64,
+            length: length as u64,
+            position: 0,
+        })
+    }
+
+    fn data_file_path(&self, filelist_num: Option<u32>) -> PathBuf {
+        match (self.filelist.num_filelists, filelist_num) {
+            (Some(_), num) => self.path.with_extension(format!("{:03}", num.unwrap_or(0))),
+            (None, _) => self.path.with_extension("DAT"),
+        }
+    }
+}
+
+/// Opens a file on disk, or inside of a filelist when the path goes through one (see [`Vfs::split_path`])
+pub fn open_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Box<dyn ReadSeek>> {
+    if let Some((filelist, inner)) = Vfs::split_path(&path) {
+        Ok(Box::new(Vfs::open(filelist)?.open_file(&inner)?))
+    } else {
+        let path = path.as_ref();
+        Ok(Box::new(File::open(path).with_context(|| {
+            format!("Failed to open {}", path.display())
+        })?))
+    }
+}
+
+/// Lazily read handle to a file inside of a filelist data file
+pub struct VfsFile {
+    file: File,
+    start: u64,
+    length: u64,
+    position: u64,
+}
+
+impl VfsFile {
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
+impl Read for VfsFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.length.saturating_sub(self.position);
+        let count = (buf.len() as u64).min(remaining) as usize;
+        if count == 0 {
+            return Ok(0);
+        }
+
+        self.file
+            .seek(SeekFrom::Start(self.start + self.position))?;
+        let read = self.file.read(&mut buf[..count])?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for VfsFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(p) => (self.length as i64).checked_add(p).map(|p| p as u64),
+            SeekFrom::Current(p) => (self.position as i64).checked_add(p).map(|p| p as u64),
+        }
+        .filter(|&p| p as i64 >= 0);
+
+        match position {
+            Some(p) => {
+                self.position = p;
+                Ok(p)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Lowercases the path, converts it to backslashes and strips the drive letter
+fn normalize_path(path: &str) -> String {
+    let path = path.replace('/', "\\").to_lowercase();
+    let path = match path.split_once(':') {
+        Some((drive, rest)) if drive.len() == 1 => rest,
+        _ => &path,
+    };
+
+    path.trim_start_matches('\\').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(
+            normalize_path("x:\\gforce\\binary\\_bin_pc\\MW_IntObj.edb"),
+            "gforce\\binary\\_bin_pc\\mw_intobj.edb"
+        );
+        assert_eq!(
+            normalize_path("/gforce/binary/_bin_pc/mw_intobj.edb"),
+            "gforce\\binary\\_bin_pc\\mw_intobj.edb"
+        );
+    }
+}
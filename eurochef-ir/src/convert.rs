@@ -0,0 +1,343 @@
+use std::io::Seek;
+
+use anyhow::Context;
+use eurochef_edb::{
+    binrw::BinReaderExt,
+    edb::{EdbFile, EdbPatcher},
+    header::EXGeoHeader,
+    texture::EXGeoTexture,
+    versions::Platform,
+};
+use eurochef_shared::{
+    platform::texture::{self, BlockFormat},
+    textures::{self, UXGeoTexture},
+};
+use image::RgbaImage;
+use tracing::info;
+
+use crate::{maps, spreadsheets, textures::IrTexture, IrDatabase};
+
+/// Converts an EDB file to another platform, by reading it into the IR and writing that for the target platform.
+///
+/// See [`convert_ir`] for what can be converted
+pub fn convert(edb: &mut EdbFile, target: Platform) -> anyhow::Result<Vec<u8>> {
+    let ir = IrDatabase::from_edb(edb)?;
+    convert_ir(&ir, edb, target)
+}
+
+/// Writes `ir` as an EDB file for another platform. `base` is the file the IR was read from, which is used for everything the IR doesn't cover.
+///
+/// Textures are re-encoded from the IR frames, unless both platforms use the same texture formats.
+/// Maps and text spreadsheets are written from the IR, which only works between platforms with the same byte order.
+/// Sections that can't be converted (yet) are reported as an error instead of producing a broken file.
+pub fn convert_ir(
+    ir: &IrDatabase,
+    base: &mut EdbFile,
+    target: Platform,
+) -> anyhow::Result<Vec<u8>> {
+    let source = base.platform;
+    if source == target {
+        anyhow::bail!("File is already built for {target}");
+    }
+
+    // PS2 textures have extra header fields that we can't fill in, the others don't have texture encoders
+    if matches!(target, Platform::Ps2 | Platform::WiiU | Platform::Ps3) {
+        anyhow::bail!("Converting to {target} is not supported yet");
+    }
+
+    let unsupported = unsupported_sections(&base.header, source, target);
+    if !unsupported.is_empty() {
+        anyhow::bail!(
+            "Converting {} from {source} to {target} is not supported yet",
+            unsupported.join(", ")
+        );
+    }
+
+    let mut writer = EdbPatcher::new(base)?;
+    writer.platform = target;
+    writer.endian = target.endianness();
+    writer.header.hashcode = ir.hashcode;
+    writer.header.flags = ir.flags;
+    writer.header.time = ir.build_time;
+    set_platform_flags(&mut writer.header, source, target);
+
+    convert_textures(&ir.textures, base, &mut writer)?;
+    maps::write_all(&ir.maps, base, &mut writer)?;
+    spreadsheets::write_all(&ir.spreadsheets, base, &mut writer)?;
+
+    info!(
+        "Converted {} textures from {source} to {target}",
+        writer.header.texture_list.len()
+    );
+
+    Ok(writer.finish()?)
+}
+
+/// Returns the names of sections that contain data we can't convert between the given platforms
+fn unsupported_sections(
+    header: &EXGeoHeader,
+    source: Platform,
+    target: Platform,
+) -> Vec<&'static str> {
+    let mut unsupported = vec![];
+    if source.endianness() != target.endianness() {
+        // Layouts of these sections are not fully known, so we can't swap their byte order
+        if !header.section_list.is_empty() {
+            unsupported.push("the section list");
+        }
+        if !header.refpointer_list.is_empty() {
+            unsupported.push("refpointers");
+        }
+        if !header.spreadsheet_list.is_empty() {
+            unsupported.push("spreadsheets");
+        }
+        if !header.map_list.is_empty() {
+            unsupported.push("maps");
+        }
+    }
+
+    // Only the format byte of the 64-byte GX texture header is known, the rest can only be copied from another GX file
+    if target.is_gx() && !source.is_gx() && !header.texture_list.is_empty() {
+        unsupported.push("textures");
+    }
+
+    // Platform-specific data that needs to be re-encoded
+    let sections = [
+        ("entities", header.entity_list.len()),
+        ("animations", header.anim_list.len()),
+        ("animation skins", header.animskin_list.len()),
+        ("scripts", header.animscript_list.len()),
+        ("animation modes", header.animmode_list.len()),
+        ("animation sets", header.animset_list.len()),
+        ("particles", header.particle_list.len()),
+        ("swooshes", header.swoosh_list.len()),
+        ("fonts", header.font_list.len()),
+        ("force feedback", header.forcefeedback_list.len()),
+        ("materials", header.material_list.len()),
+    ];
+
+    for (name, count) in sections {
+        if count != 0 {
+            unsupported.push(name);
+        }
+    }
+
+    unsupported
+}
+
+/// Whether texture data can be copied between platforms without re-encoding
+fn same_texture_codec(a: Platform, b: Platform) -> bool {
+    a == b || (a.is_gx() && b.is_gx())
+}
+
+/// Sets the platform bits that are used for platform detection (see [`Platform::from_path`])
+fn set_platform_flags(header: &mut EXGeoHeader, source: Platform, target: Platform) {
+    header.flags &= !((1 << 28) | (1 << 29));
+    match target {
+        Platform::Ps2 => header.flags |= 1 << 28,
+        Platform::Pc | Platform::Xbox | Platform::GameCube | Platform::Wii => {
+            header.flags |= 1 << 29
+        }
+        _ => {}
+    }
+
+    // The first platform version is set on Xbox, other platforms normally leave it at zero
+    if target == Platform::Xbox {
+        header.platform_versions[0] = header.platform_versions[0].max(1);
+    } else if source == Platform::Xbox {
+        header.platform_versions[0] = 0;
+    }
+}
+
+fn convert_textures(
+    textures: &[IrTexture],
+    base: &mut EdbFile,
+    writer: &mut EdbPatcher,
+) -> anyhow::Result<()> {
+    let version = base.header.version;
+    let source = base.platform;
+    let target = writer.platform;
+    let decoder = texture::create_for_platform(source);
+    let encoder = if same_texture_codec(source, target) {
+        None
+    } else {
+        Some(texture::create_encoder_for_platform(target)?)
+    };
+
+    let mut texture_list = writer.header.texture_list.data().clone();
+    for t in texture_list.iter_mut() {
+        let hashcode = t.common.hashcode;
+        let ir = textures
+            .iter()
+            .find(|ir| ir.hashcode == hashcode)
+            .with_context(|| format!("Texture {hashcode:x} couldn't be read"))?;
+
+        base.seek(std::io::SeekFrom::Start(t.common.address as u64))?;
+        let mut tex = base
+            .read_type_args::<EXGeoTexture>(base.endian, (version, source))
+            .with_context(|| format!("Failed to read texture {hashcode:x}"))?;
+
+        match &encoder {
+            Some(encoder) if ir.external.is_none() => {
+                // Only the compression is taken from the original texture, mip levels are generated from the IR frames
+                let block_format =
+                    UXGeoTexture::read(t.common.address, base, decoder.as_ref(), t.flags)
+                        .with_context(|| format!("Failed to decode texture {hashcode:x}"))?
+                        .block_format;
+
+                let (width, height) = (ir.width as u32, ir.height as u32 * ir.depth.max(1) as u32);
+                let frames = ir
+                    .frames
+                    .iter()
+                    .map(|f| {
+                        RgbaImage::from_raw(width, height, f.clone())
+                            .map(|f| vec![f])
+                            .with_context(|| {
+                                format!("Frame data of texture {hashcode:x} has the wrong size")
+                            })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                tex.format = target_format(target, block_format);
+                tex.clut_offset = None;
+                tex.data_size = has_data_size(version, target).then_some(0);
+                textures::write_frames(&mut tex, &frames, encoder.as_ref(), writer, None)
+                    .with_context(|| format!("Failed to encode texture {hashcode:x}"))?;
+            }
+            _ => {
+                if has_data_size(version, target) {
+                    if tex.data_size.is_none() && tex.external_file.is_none() {
+                        tex.data_size = Some(decoder.get_data_size(
+                            tex.width as u32,
+                            tex.height as u32,
+                            tex.depth as u32,
+                            tex.format,
+                        )? as u32);
+                    }
+                } else {
+                    tex.data_size = None;
+                }
+            }
+        }
+
+        if platform_fields_size(version, source) == platform_fields_size(version, target) {
+            writer.write_at(t.common.address as u64, &tex, (version, target))?;
+        } else {
+            // The texture header changed size, so it has to be moved
+            t.common.address = writer.append(&tex, (version, target))?;
+        }
+    }
+
+    writer
+        .header
+        .texture_list
+        .data_mut()
+        .clone_from(&texture_list);
+
+    Ok(())
+}
+
+/// Format to re-encode textures in, keeping block compression where the target supports it.
+/// Everything else is stored as uncompressed 32-bit RGBA
+fn target_format(target: Platform, block_format: Option<BlockFormat>) -> u8 {
+    match (target, block_format) {
+        (Platform::Pc | Platform::Xbox, Some(BlockFormat::Bc1)) => 2,
+        (Platform::Pc, Some(BlockFormat::Bc2)) => 7,
+        (Platform::Pc, Some(BlockFormat::Bc3)) => 9,
+        (Platform::Xbox, Some(BlockFormat::Bc2)) => 10,
+        (Platform::Xbox, Some(BlockFormat::Bc3)) => 12,
+        (Platform::Xbox360, Some(BlockFormat::Bc1)) => 0,
+        (Platform::Xbox360, Some(BlockFormat::Bc2)) => 3,
+        (Platform::Xbox360, Some(BlockFormat::Bc3)) => 5,
+        (Platform::GameCube | Platform::Wii, Some(BlockFormat::Bc1)) => 0,
+        (Platform::GameCube | Platform::Wii, _) => 1,
+        // Linear ARGB8, the swizzled variant needs power of two dimensions
+        (Platform::Xbox, _) => 9,
+        (Platform::Xbox360, _) => 9,
+        _ => 6,
+    }
+}
+
+/// Size of the platform-specific fields of `EXGeoTexture`, mirrors the conditions on the struct
+fn platform_fields_size(version: u32, platform: Platform) -> usize {
+    let mut size = 0;
+    if platform == Platform::Ps2 {
+        // _unk2 and clut_offset
+        if version != 248 && version != 177 && version != 168 {
+            size += 4;
+        }
+        size += 4;
+    }
+
+    if has_data_size(version, platform) {
+        size += 4;
+    }
+
+    size
+}
+
+/// Mirrors the condition for `EXGeoTexture::data_size`
+fn has_data_size(version: u32, platform: Platform) -> bool {
+    platform != Platform::Pc
+        && platform != Platform::Ps2
+        && !(version == 170 && platform == Platform::Xbox)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::tests::build_edb;
+
+    fn read_texture(data: Vec<u8>, platform: Platform) -> anyhow::Result<UXGeoTexture> {
+        let mut edb = EdbFile::new(Box::new(Cursor::new(data)), platform)?;
+        let t = edb.header.texture_list.data()[0].clone();
+        UXGeoTexture::read(
+            t.common.address,
+            &mut edb,
+            texture::create_for_platform(platform).as_ref(),
+            t.flags,
+        )
+    }
+
+    fn convert_texture(target: Platform) -> anyhow::Result<UXGeoTexture> {
+        let mut edb = EdbFile::new(Box::new(Cursor::new(build_edb())), Platform::Pc)?;
+        read_texture(convert(&mut edb, target)?, target)
+    }
+
+    #[test]
+    fn reencode_textures() {
+        let source = read_texture(build_edb(), Platform::Pc).unwrap();
+        for target in [Platform::Xbox, Platform::Xbox360] {
+            let texture = convert_texture(target).unwrap();
+            assert_eq!((texture.width, texture.height), (4, 4));
+            assert_eq!(texture.frames, source.frames, "{target}");
+        }
+    }
+
+    #[test]
+    fn platform_versions() {
+        let mut edb = EdbFile::new(Box::new(Cursor::new(build_edb())), Platform::Pc).unwrap();
+        let data = convert(&mut edb, Platform::Xbox).unwrap();
+        let mut edb = EdbFile::new(Box::new(Cursor::new(data)), Platform::Xbox).unwrap();
+        assert_eq!(edb.header.platform_versions[0], 1);
+
+        let data = convert(&mut edb, Platform::Pc).unwrap();
+        let edb = EdbFile::new(Box::new(Cursor::new(data)), Platform::Pc).unwrap();
+        assert_eq!(edb.header.platform_versions[0], 0);
+    }
+
+    #[test]
+    fn unsupported_targets() {
+        // GX textures can't be created without an original GX header
+        for target in [
+            Platform::GameCube,
+            Platform::Ps2,
+            Platform::WiiU,
+            Platform::Ps3,
+        ] {
+            assert!(convert_texture(target).is_err());
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::io::Seek;
+
+use eurochef_edb::{
+    binrw::BinReaderExt, edb::EdbFile, entity::EXGeoEntity, versions::Platform, Hashcode,
+};
+use eurochef_shared::entities::read_entity;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::util::ensure_unchanged;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrEntity {
+    pub hashcode: Hashcode,
+
+    pub vertices: Vec<IrVertex>,
+    /// Triangle list indices
+    pub indices: Vec<u32>,
+    pub strips: Vec<IrStrip>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IrVertex {
+    pub pos: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Range of triangles sharing the same texture and flags
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrStrip {
+    pub start_index: u32,
+    pub index_count: u32,
+    pub texture: Option<Hashcode>,
+    pub transparency: u16,
+    pub flags: u16,
+}
+
+pub(crate) fn read_all(edb: &mut EdbFile) -> anyhow::Result<Vec<IrEntity>> {
+    if edb.platform == Platform::Ps2 {
+        warn!("PS2 entities are not supported yet, skipping");
+        return Ok(vec![]);
+    }
+
+    let header = edb.header.clone();
+    let mut entities = vec![];
+    for e in header.entity_list.iter() {
+        edb.seek(std::io::SeekFrom::Start(e.common.address as u64))?;
+        let ent =
+            match edb.read_type_args::<EXGeoEntity>(edb.endian, (header.version, edb.platform)) {
+                Ok(ent) => ent,
+                Err(err) => {
+                    error!("Failed to read entity {:x}: {err}", e.common.hashcode);
+                    continue;
+                }
+            };
+
+        let mut vertex_data = vec![];
+        let mut indices = vec![];
+        let mut strips = vec![];
+        if let Err(err) = read_entity(
+            &ent,
+            &mut vertex_data,
+            &mut indices,
+            &mut strips,
+            edb,
+            4,
+            false,
+            true,
+        ) {
+            error!("Failed to read entity {:x}: {err}", e.common.hashcode);
+            continue;
+        }
+
+        entities.push(IrEntity {
+            hashcode: e.common.hashcode,
+            vertices: vertex_data
+                .into_iter()
+                .map(|v| IrVertex {
+                    pos: v.pos,
+                    normal: v.norm,
+                    uv: v.uv,
+                    color: v.color,
+                })
+                .collect(),
+            indices,
+            strips: strips
+                .into_iter()
+                .map(|s| IrStrip {
+                    start_index: s.start_index,
+                    index_count: s.index_count,
+                    texture: header
+                        .texture_list
+                        .data()
+                        .get(s.texture_index as usize)
+                        .map(|t| t.common.hashcode),
+                    transparency: s.transparency,
+                    flags: s.flags,
+                })
+                .collect(),
+        });
+    }
+
+    Ok(entities)
+}
+
+/// Entity geometry can't be encoded yet, so it has to be identical to the entities in `base`
+pub(crate) fn write_all(entities: &[IrEntity], base: &mut EdbFile) -> anyhow::Result<()> {
+    ensure_unchanged("entities", entities, &read_all(base)?, |e| e.hashcode)
+}
@@ -0,0 +1,230 @@
+//! Platform- and version-neutral intermediate representation of EDB files
+
+pub mod convert;
+pub mod entities;
+pub mod maps;
+pub mod scripts;
+pub mod spreadsheets;
+pub mod textures;
+mod util;
+
+use std::{fs::File, io::BufReader, io::BufWriter, path::Path};
+
+use anyhow::Context;
+use eurochef_edb::{
+    edb::{EdbFile, EdbPatcher},
+    versions::Platform,
+    Hashcode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::IrEntity, maps::IrMap, scripts::IrScript, spreadsheets::IrSpreadsheet,
+    textures::IrTexture,
+};
+
+/// Intermediate representation of a single EDB file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrDatabase {
+    pub hashcode: Hashcode,
+    pub flags: u32,
+    pub build_time: u32,
+
+    /// Where this database was read from, only informational
+    pub source: IrSource,
+
+    pub textures: Vec<IrTexture>,
+    pub entities: Vec<IrEntity>,
+    pub maps: Vec<IrMap>,
+    pub scripts: Vec<IrScript>,
+    pub spreadsheets: Vec<IrSpreadsheet>,
+    pub references: IrReferences,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrSource {
+    pub version: u32,
+    /// Platform shorthand (eg. `pc`, `gc`)
+    pub platform: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IrExternalReference {
+    pub file: Hashcode,
+    pub hashcode: Hashcode,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IrReferences {
+    /// Objects in this file that are used by other objects in this file
+    pub internal: Vec<Hashcode>,
+    /// Objects in other files
+    pub external: Vec<IrExternalReference>,
+}
+
+impl IrDatabase {
+    /// Reads every supported object from the given file.
+    /// Objects that fail to read are skipped with an error message.
+    pub fn from_edb(edb: &mut EdbFile) -> anyhow::Result<Self> {
+        let textures = textures::read_all(edb);
+        let entities = entities::read_all(edb)?;
+        let maps = maps::read_all(edb)?;
+        let scripts = scripts::read_all(edb)?;
+        let spreadsheets = spreadsheets::read_all(edb)?;
+
+        Ok(Self {
+            hashcode: edb.header.hashcode,
+            flags: edb.header.flags,
+            build_time: edb.header.time,
+            source: IrSource {
+                version: edb.header.version,
+                platform: edb.platform.shorthand().to_string(),
+            },
+            textures,
+            entities,
+            maps,
+            scripts,
+            spreadsheets,
+            references: IrReferences {
+                internal: edb.internal_references.clone(),
+                external: edb
+                    .external_references
+                    .iter()
+                    .map(|&(file, hashcode)| IrExternalReference { file, hashcode })
+                    .collect(),
+            },
+        })
+    }
+
+    /// Writes this database back into an EDB, using `base` for everything that can't be encoded from the IR yet.
+    ///
+    /// Currently supported are header information, textures, map objects and text spreadsheets.
+    /// Entity geometry, scripts and data spreadsheets can't be encoded yet, so changing them is an error.
+    pub fn to_edb(&self, base: &mut EdbFile) -> anyhow::Result<Vec<u8>> {
+        let mut writer = EdbPatcher::new(base)?;
+        writer.header.hashcode = self.hashcode;
+        writer.header.flags = self.flags;
+        writer.header.time = self.build_time;
+
+        textures::write_all(&self.textures, base, &mut writer)?;
+        entities::write_all(&self.entities, base)?;
+        maps::write_all(&self.maps, base, &mut writer)?;
+        scripts::write_all(&self.scripts, base)?;
+        spreadsheets::write_all(&self.spreadsheets, base, &mut writer)?;
+
+        Ok(writer.finish()?)
+    }
+
+    pub fn source_platform(&self) -> Option<Platform> {
+        Platform::from_shorthand(&self.source.platform)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        serde_json::from_reader(BufReader::new(file)).context("Failed to parse IR document")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer(BufWriter::new(file), self).context("Failed to write IR document")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    use eurochef_edb::binrw::BinWriterExt;
+
+    use super::*;
+    use crate::scripts::IrScript;
+
+    /// Builds a big endian (v259) PC EDB with a single 4x4 ARGB8 texture
+    pub(crate) fn build_edb() -> Vec<u8> {
+        let mut cur = Cursor::new(vec![0u8; 0x180]);
+        let mut w = |offset: u64, values: &[u32]| {
+            cur.seek(SeekFrom::Start(offset)).unwrap();
+            for v in values {
+                cur.write_be(v).unwrap();
+            }
+        };
+
+        // Magic, hashcode, version, flags, time, file size, base file size
+        w(0x0, &[0x47454F4D, 0x80000001, 259, 0, 0, 0x180, 0x180]);
+        // Texture list (1 entry, hash size 1)
+        w(0xb8, &[0x00010001, 0xd0 - 0xbc]);
+        w(0xd0, &[0x06000000, 0, 0x100, 0, (4 << 16) | 4, 0, 0]);
+        // Texture with a single frame, no external file and no extra data
+        w(
+            0x100,
+            &[
+                (4 << 16) | 4,
+                1 << 16,
+                0,
+                0x01010000,
+                6,
+                0,
+                0,
+                u32::MAX,
+                0,
+                0,
+                0x140 - 0x128,
+            ],
+        );
+        // Frame data
+        w(0x140, &[0x80112233; 16]);
+
+        cur.into_inner()
+    }
+
+    fn open(data: Vec<u8>) -> EdbFile {
+        EdbFile::new(Box::new(Cursor::new(data)), Platform::Pc).unwrap()
+    }
+
+    #[test]
+    fn roundtrip_unmodified() {
+        let data = build_edb();
+        let mut edb = open(data.clone());
+        let ir = IrDatabase::from_edb(&mut edb).unwrap();
+        assert_eq!(ir.textures.len(), 1);
+
+        assert_eq!(ir.to_edb(&mut edb).unwrap(), data);
+    }
+
+    #[test]
+    fn roundtrip_texture() {
+        let mut edb = open(build_edb());
+        let ir = IrDatabase::from_edb(&mut edb).unwrap();
+
+        // Go through the serialized document, like an edited IR file would
+        let mut ir: IrDatabase =
+            serde_json::from_str(&serde_json::to_string(&ir).unwrap()).unwrap();
+        let frame: Vec<u8> = (0..64).collect();
+        ir.textures[0].frames[0] = frame.clone();
+        ir.textures[0].game_flags = 0x10;
+        ir.textures[0].color = [1, 2, 3, 4];
+
+        let mut edb = open(ir.to_edb(&mut edb).unwrap());
+        let ir = IrDatabase::from_edb(&mut edb).unwrap();
+        assert_eq!(ir.textures[0].frames, vec![frame]);
+        assert_eq!(ir.textures[0].game_flags, 0x10);
+        assert_eq!(ir.textures[0].color, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn unsupported_changes() {
+        let mut edb = open(build_edb());
+        let mut ir = IrDatabase::from_edb(&mut edb).unwrap();
+        ir.scripts.push(IrScript {
+            hashcode: 0x1b000000,
+            framerate: 30.0,
+            length: 0,
+            commands: vec![],
+        });
+        assert!(ir.to_edb(&mut edb).is_err());
+
+        let mut ir = IrDatabase::from_edb(&mut edb).unwrap();
+        ir.textures[0].frames[0].truncate(16);
+        assert!(ir.to_edb(&mut edb).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use std::io::Seek;
+
+use anyhow::Context;
+use eurochef_edb::{
+    binrw::BinReaderExt,
+    edb::{EdbFile, EdbPatcher},
+    map::{EXGeoLight, EXGeoMap, EXGeoPlacement, EXGeoSound},
+    Hashcode,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrMap {
+    pub hashcode: Hashcode,
+    pub placements: Vec<IrPlacement>,
+    pub lights: Vec<IrLight>,
+    pub sounds: Vec<IrSound>,
+    pub triggers: Vec<IrTrigger>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrPlacement {
+    pub hashcode: Hashcode,
+    pub object_ref: u32,
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+    pub flags: u32,
+    pub engine_flags: u16,
+    pub map_on: u16,
+    pub light_set: u16,
+    pub group: i16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrLight {
+    pub hashcode: Hashcode,
+    pub position: [f32; 3],
+    pub beam: [f32; 3],
+    pub flags: u32,
+    pub ltype: u16,
+    pub beam_angle: u16,
+    pub colour: [u8; 4],
+    pub radius: f32,
+    pub max_effect_fraction: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrSound {
+    pub hashcode: Hashcode,
+    pub sound_ref: u32,
+    pub position: [f32; 3],
+    pub flags: u32,
+    pub color: [u8; 4],
+    pub volume: u8,
+    pub fade_in: u8,
+    pub fade_out: u8,
+    pub tracking_type: u8,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrTrigger {
+    pub trig_type: u32,
+    pub trig_subtype: u32,
+    pub link_ref: i32,
+
+    pub game_flags: u32,
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+
+    pub data: Vec<Option<u32>>,
+    pub links: Vec<i32>,
+}
+
+impl From<&EXGeoPlacement> for IrPlacement {
+    fn from(p: &EXGeoPlacement) -> Self {
+        Self {
+            hashcode: p.hashcode,
+            object_ref: p.object_ref,
+            position: p.position,
+            rotation: p.rotation,
+            scale: p.scale,
+            flags: p.flags,
+            engine_flags: p.engine_flags,
+            map_on: p.map_on,
+            light_set: p.light_set,
+            group: p.group,
+        }
+    }
+}
+
+impl IrPlacement {
+    fn apply(&self, p: &mut EXGeoPlacement) {
+        p.hashcode = self.hashcode;
+        p.object_ref = self.object_ref;
+        p.position = self.position;
+        p.rotation = self.rotation;
+        p.scale = self.scale;
+        p.flags = self.flags;
+        p.engine_flags = self.engine_flags;
+        p.map_on = self.map_on;
+        p.light_set = self.light_set;
+        p.group = self.group;
+    }
+}
+
+impl From<&EXGeoLight> for IrLight {
+    fn from(l: &EXGeoLight) -> Self {
+        Self {
+            hashcode: l.hashcode,
+            position: l.position,
+            beam: l.beam,
+            flags: l.flags,
+            ltype: l.ltype,
+            beam_angle: l.beam_angle,
+            colour: l.colour,
+            radius: l.radius,
+            max_effect_fraction: l.max_effect_fraction,
+        }
+    }
+}
+
+impl IrLight {
+    fn apply(&self, l: &mut EXGeoLight) {
+        l.hashcode = self.hashcode;
+        l.position = self.position;
+        l.beam = self.beam;
+        l.flags = self.flags;
+        l.ltype = self.ltype;
+        l.beam_angle = self.beam_angle;
+        l.colour = self.colour;
+        l.radius = self.radius;
+        l.max_effect_fraction = self.max_effect_fraction;
+    }
+}
+
+impl From<&EXGeoSound> for IrSound {
+    fn from(s: &EXGeoSound) -> Self {
+        Self {
+            hashcode: s.hashcode,
+            sound_ref: s.sound_ref,
+            position: s.position,
+            flags: s.flags,
+            color: s.color,
+            volume: s.volume,
+            fade_in: s.fade_in,
+            fade_out: s.fade_out,
+            tracking_type: s.tracking_type,
+            inner_radius: s.inner_radius,
+            outer_radius: s.outer_radius,
+        }
+    }
+}
+
+impl IrSound {
+    fn apply(&self, s: &mut EXGeoSound) {
+        s.hashcode = self.hashcode;
+        s.sound_ref = self.sound_ref;
+        s.position = self.position;
+        s.flags = self.flags;
+        s.color = self.color;
+        s.volume = self.volume;
+        s.fade_in = self.fade_in;
+        s.fade_out = self.fade_out;
+        s.tracking_type = self.tracking_type;
+        s.inner_radius = self.inner_radius;
+        s.outer_radius = self.outer_radius;
+    }
+}
+
+fn read_map(edb: &mut EdbFile, address: u32) -> anyhow::Result<EXGeoMap> {
+    edb.seek(std::io::SeekFrom::Start(address as u64))?;
+    edb.read_type_args::<EXGeoMap>(edb.endian, (edb.header.version,))
+        .context("Failed to read map")
+}
+
+pub(crate) fn read_all(edb: &mut EdbFile) -> anyhow::Result<Vec<IrMap>> {
+    let header = edb.header.clone();
+    let mut maps = vec![];
+    for m in header.map_list.iter() {
+        let map = read_map(edb, m.address)?;
+
+        let triggers = map
+            .trigger_header
+            .triggers
+            .iter()
+            .map(|t| {
+                let trig = &t.trigger;
+                let ttype = &map.trigger_header.trigger_types[trig.type_index as usize];
+                IrTrigger {
+                    trig_type: ttype.trig_type,
+                    trig_subtype: ttype.trig_subtype,
+                    link_ref: t.link_ref,
+                    game_flags: trig.game_flags,
+                    position: trig.position,
+                    rotation: trig.rotation,
+                    scale: trig.scale,
+                    data: trig.data.to_vec(),
+                    links: trig.links.to_vec(),
+                }
+            })
+            .collect();
+
+        maps.push(IrMap {
+            hashcode: m.hashcode,
+            placements: map.placements.iter().map(IrPlacement::from).collect(),
+            lights: map.lights.iter().map(IrLight::from).collect(),
+            sounds: map.sounds.iter().map(IrSound::from).collect(),
+            triggers,
+        });
+    }
+
+    Ok(maps)
+}
+
+/// Writes map objects back in place.
+/// Objects can be modified, but adding or removing them is not supported yet.
+pub(crate) fn write_all(
+    maps: &[IrMap],
+    base: &mut EdbFile,
+    writer: &mut EdbPatcher,
+) -> anyhow::Result<()> {
+    for m in maps {
+        let Some(address) = writer
+            .header
+            .map_list
+            .iter()
+            .find(|h| h.hashcode == m.hashcode)
+            .map(|h| h.address)
+        else {
+            anyhow::bail!("Adding maps is not supported yet ({:x})", m.hashcode);
+        };
+
+        let mut map = read_map(base, address)?;
+        if map.placements.len() != m.placements.len()
+            || map.lights.len() != m.lights.len()
+            || map.sounds.len() != m.sounds.len()
+            || map.trigger_header.triggers.len() != m.triggers.len()
+        {
+            anyhow::bail!(
+                "Adding or removing objects from map {:x} is not supported yet",
+                m.hashcode
+            );
+        }
+
+        for (p, ir) in map.placements.data_mut().iter_mut().zip(&m.placements) {
+            ir.apply(p);
+        }
+
+        for (l, ir) in map.lights.data_mut().iter_mut().zip(&m.lights) {
+            ir.apply(l);
+        }
+
+        for (s, ir) in map.sounds.data_mut().iter_mut().zip(&m.sounds) {
+            ir.apply(s);
+        }
+
+        let trigger_types: Vec<(u32, u32)> = map
+            .trigger_header
+            .trigger_types
+            .iter()
+            .map(|t| (t.trig_type, t.trig_subtype))
+            .collect();
+        for (i, (t, ir)) in map
+            .trigger_header
+            .data_mut()
+            .triggers
+            .data_mut()
+            .iter_mut()
+            .zip(&m.triggers)
+            .enumerate()
+        {
+            t.link_ref = ir.link_ref;
+
+            let trig = t.trigger.data_mut();
+            let type_index = trigger_types
+                .iter()
+                .position(|&t| t == (ir.trig_type, ir.trig_subtype))
+                .with_context(|| {
+                    format!(
+                        "Trigger {i} in map {:x} uses a trigger type that isn't in the map, which is not supported yet",
+                        m.hashcode
+                    )
+                })?;
+
+            // Values and links are only stored when their flag is set, changing which ones are set would resize the trigger
+            let data_set = ir.data.len() == trig.data.len()
+                && ir
+                    .data
+                    .iter()
+                    .zip(&trig.data)
+                    .all(|(a, b)| a.is_some() == b.is_some());
+            let links_set = ir.links.len() == trig.links.len()
+                && ir
+                    .links
+                    .iter()
+                    .zip(&trig.links)
+                    .enumerate()
+                    .all(|(j, (a, b))| trig.trig_flags & (1 << (16 + j)) != 0 || a == b);
+            if !data_set || !links_set {
+                anyhow::bail!(
+                    "Changing which data values or links are set on trigger {i} in map {:x} is not supported yet",
+                    m.hashcode
+                );
+            }
+
+            trig.type_index = type_index as u16;
+            trig.game_flags = ir.game_flags;
+            trig.position = ir.position;
+            trig.rotation = ir.rotation;
+            trig.scale = ir.scale;
+            trig.data.copy_from_slice(&ir.data);
+            trig.links.copy_from_slice(&ir.links);
+        }
+
+        writer.write_at(address as u64, &map, (base.header.version,))?;
+    }
+
+    Ok(())
+}
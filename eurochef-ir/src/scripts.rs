@@ -0,0 +1,120 @@
+use eurochef_edb::{edb::EdbFile, Hashcode};
+use eurochef_shared::script::{UXGeoScript, UXGeoScriptCommandData};
+use serde::{Deserialize, Serialize};
+
+use crate::util::ensure_unchanged;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrScript {
+    pub hashcode: Hashcode,
+    pub framerate: f32,
+    pub length: u32,
+    pub commands: Vec<IrScriptCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrScriptCommand {
+    pub start: i16,
+    pub length: u16,
+    pub thread: u8,
+    pub parent_thread: u8,
+    pub controller_index: u8,
+
+    pub data: IrScriptCommandData,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IrScriptCommandData {
+    Entity {
+        hashcode: Hashcode,
+        file: Hashcode,
+    },
+    Animation {
+        skin_file: Hashcode,
+        skin_hashcode: Hashcode,
+        anim_file: Hashcode,
+        anim_hashcode: Hashcode,
+    },
+    Sound {
+        hashcode: Hashcode,
+    },
+    Particle {
+        hashcode: Hashcode,
+        file: Hashcode,
+    },
+    Event {
+        event_type: Hashcode,
+        data: Vec<u8>,
+    },
+    SubScript {
+        hashcode: Hashcode,
+        file: Hashcode,
+    },
+    Unknown {
+        cmd: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl From<UXGeoScriptCommandData> for IrScriptCommandData {
+    fn from(d: UXGeoScriptCommandData) -> Self {
+        match d {
+            UXGeoScriptCommandData::Entity { hashcode, file } => Self::Entity { hashcode, file },
+            UXGeoScriptCommandData::Animation {
+                skin_file,
+                skin_hashcode,
+                anim_file,
+                anim_hashcode,
+            } => Self::Animation {
+                skin_file,
+                skin_hashcode,
+                anim_file,
+                anim_hashcode,
+            },
+            UXGeoScriptCommandData::Sound { hashcode } => Self::Sound { hashcode },
+            UXGeoScriptCommandData::Particle { hashcode, file } => {
+                Self::Particle { hashcode, file }
+            }
+            UXGeoScriptCommandData::Event { event_type, data } => Self::Event { event_type, data },
+            UXGeoScriptCommandData::SubScript { hashcode, file } => {
+                Self::SubScript { hashcode, file }
+            }
+            UXGeoScriptCommandData::Unknown { cmd, data } => Self::Unknown { cmd, data },
+        }
+    }
+}
+
+impl From<UXGeoScript> for IrScript {
+    fn from(s: UXGeoScript) -> Self {
+        Self {
+            hashcode: s.hashcode,
+            framerate: s.framerate,
+            length: s.length,
+            commands: s
+                .commands
+                .into_iter()
+                .map(|c| IrScriptCommand {
+                    start: c.start,
+                    length: c.length,
+                    thread: c.thread,
+                    parent_thread: c.parent_thread,
+                    controller_index: c.controller_index,
+                    data: c.data.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
+pub(crate) fn read_all(edb: &mut EdbFile) -> anyhow::Result<Vec<IrScript>> {
+    Ok(UXGeoScript::read_all(edb)?
+        .into_iter()
+        .map(IrScript::from)
+        .collect())
+}
+
+/// Scripts can't be encoded yet, so they have to be identical to the ones in `base`
+pub(crate) fn write_all(scripts: &[IrScript], base: &mut EdbFile) -> anyhow::Result<()> {
+    ensure_unchanged("scripts", scripts, &read_all(base)?, |s| s.hashcode)
+}
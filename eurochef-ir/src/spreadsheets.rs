@@ -0,0 +1,151 @@
+use eurochef_edb::{
+    edb::{EdbFile, EdbPatcher},
+    Hashcode,
+};
+use eurochef_shared::spreadsheets::UXGeoSpreadsheet;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrSpreadsheet {
+    pub hashcode: Hashcode,
+    pub data: IrSpreadsheetData,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IrSpreadsheetData {
+    Text {
+        sections: Vec<IrTextSection>,
+    },
+    /// Row layouts are game-specific, only the row count of each sheet is kept
+    Data {
+        row_counts: Vec<u32>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrTextSection {
+    pub hashcode: Hashcode,
+    pub entries: Vec<IrTextItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrTextItem {
+    pub hashcode: Hashcode,
+    pub text: String,
+    pub sound_hashcode: Hashcode,
+}
+
+impl From<(Hashcode, UXGeoSpreadsheet)> for IrSpreadsheet {
+    fn from((hashcode, s): (Hashcode, UXGeoSpreadsheet)) -> Self {
+        let data = match s {
+            UXGeoSpreadsheet::Text(sections) => IrSpreadsheetData::Text {
+                sections: sections
+                    .into_iter()
+                    .map(|s| IrTextSection {
+                        hashcode: s.hashcode,
+                        entries: s
+                            .entries
+                            .into_iter()
+                            .map(|e| IrTextItem {
+                                hashcode: e.hashcode,
+                                text: e.text,
+                                sound_hashcode: e.sound_hashcode,
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            },
+            UXGeoSpreadsheet::Data(sheets) => IrSpreadsheetData::Data {
+                row_counts: sheets.iter().map(|s| s.row_count).collect(),
+            },
+        };
+
+        Self { hashcode, data }
+    }
+}
+
+pub(crate) fn read_all(edb: &mut EdbFile) -> anyhow::Result<Vec<IrSpreadsheet>> {
+    Ok(UXGeoSpreadsheet::read_all(edb)?
+        .into_iter()
+        .map(IrSpreadsheet::from)
+        .collect())
+}
+
+/// Writes the text and sound hashcodes of text spreadsheets back to the file.
+/// Other changes, like adding items or changing data spreadsheets, are not supported yet
+pub(crate) fn write_all(
+    spreadsheets: &[IrSpreadsheet],
+    base: &mut EdbFile,
+    writer: &mut EdbPatcher,
+) -> anyhow::Result<()> {
+    let original = UXGeoSpreadsheet::read_all(base)?;
+    if let Some((hashcode, _)) = original
+        .iter()
+        .find(|(h, _)| !spreadsheets.iter().any(|s| s.hashcode == *h))
+    {
+        anyhow::bail!("Removing spreadsheets is not supported yet ({hashcode:x})");
+    }
+
+    for s in spreadsheets {
+        let Some((_, spreadsheet)) = original.iter().find(|(h, _)| *h == s.hashcode) else {
+            anyhow::bail!(
+                "Adding spreadsheets is not supported yet ({:x})",
+                s.hashcode
+            );
+        };
+
+        match (&s.data, spreadsheet) {
+            (IrSpreadsheetData::Text { sections }, UXGeoSpreadsheet::Text(original_sections)) => {
+                let same_layout = sections.len() == original_sections.len()
+                    && sections.iter().zip(original_sections).all(|(a, b)| {
+                        a.hashcode == b.hashcode
+                            && a.entries.len() == b.entries.len()
+                            && a.entries
+                                .iter()
+                                .zip(&b.entries)
+                                .all(|(a, b)| a.hashcode == b.hashcode)
+                    });
+                if !same_layout {
+                    anyhow::bail!(
+                        "Adding, removing or reordering text in spreadsheet {:x} is not supported yet",
+                        s.hashcode
+                    );
+                }
+
+                for (section, original_section) in sections.iter().zip(original_sections) {
+                    let changed = section
+                        .entries
+                        .iter()
+                        .zip(&original_section.entries)
+                        .any(|(a, b)| a.text != b.text || a.sound_hashcode != b.sound_hashcode);
+                    if !changed {
+                        continue;
+                    }
+
+                    let mut updated = original_section.clone();
+                    for (item, ir) in updated.entries.iter_mut().zip(&section.entries) {
+                        item.text = ir.text.clone();
+                        item.sound_hashcode = ir.sound_hashcode;
+                    }
+
+                    updated.write(base, writer)?;
+                }
+            }
+            (IrSpreadsheetData::Data { row_counts }, UXGeoSpreadsheet::Data(sheets)) => {
+                if !row_counts.iter().eq(sheets.iter().map(|s| &s.row_count)) {
+                    anyhow::bail!(
+                        "Changing the row counts of spreadsheet {:x} is not supported yet",
+                        s.hashcode
+                    );
+                }
+            }
+            _ => anyhow::bail!(
+                "Changing the type of spreadsheet {:x} is not supported yet",
+                s.hashcode
+            ),
+        }
+    }
+
+    Ok(())
+}
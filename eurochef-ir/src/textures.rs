@@ -0,0 +1,161 @@
+use std::io::Seek;
+
+use anyhow::Context;
+use eurochef_edb::{
+    binrw::BinReaderExt,
+    edb::{EdbFile, EdbPatcher},
+    texture::EXGeoTexture,
+    Hashcode,
+};
+use eurochef_shared::{
+    platform::texture,
+    textures::{self, UXGeoTexture},
+};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{util::base64_vec, IrExternalReference};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrTexture {
+    pub hashcode: Hashcode,
+    pub width: u16,
+    pub height: u16,
+    pub depth: u16,
+
+    pub flags: u32,
+    pub game_flags: u16,
+
+    /// UV scroll rate, in pixels per second
+    pub scroll: [i16; 2],
+    /// Framerate in frames per second
+    pub framerate: u8,
+    pub color: [u8; 4],
+
+    /// RGBA frame data
+    #[serde(with = "base64_vec")]
+    pub frames: Vec<Vec<u8>>,
+
+    /// Set if this texture is stored in another file
+    pub external: Option<IrExternalReference>,
+}
+
+impl From<(Hashcode, UXGeoTexture)> for IrTexture {
+    fn from((hashcode, t): (Hashcode, UXGeoTexture)) -> Self {
+        Self {
+            hashcode,
+            width: t.width,
+            height: t.height,
+            depth: t.depth,
+            flags: t.flags,
+            game_flags: t.game_flags,
+            scroll: t.scroll,
+            framerate: t.framerate,
+            color: t.color,
+            frames: t.frames,
+            external: t
+                .external_texture
+                .map(|(file, hashcode)| IrExternalReference { file, hashcode }),
+        }
+    }
+}
+
+pub(crate) fn read_all(edb: &mut EdbFile) -> Vec<IrTexture> {
+    UXGeoTexture::read_all(edb)
+        .into_iter()
+        .filter_map(|(_, t)| match t.data {
+            Ok(data) => Some((t.hashcode, data).into()),
+            Err(e) => {
+                error!("Failed to read texture {:x}: {e:?}", t.hashcode);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Writes textures back to the file.
+/// Frames that differ from `base` are re-encoded in the original format, mip levels are generated from the top level
+pub(crate) fn write_all(
+    textures: &[IrTexture],
+    base: &mut EdbFile,
+    writer: &mut EdbPatcher,
+) -> anyhow::Result<()> {
+    let version = writer.header.version;
+    let platform = writer.platform;
+    for t in textures {
+        let Some(header) = writer
+            .header
+            .texture_list
+            .data_mut()
+            .iter_mut()
+            .find(|h| h.common.hashcode == t.hashcode)
+        else {
+            anyhow::bail!("Adding textures is not supported yet ({:x})", t.hashcode);
+        };
+
+        if header.width != t.width || header.height != t.height {
+            anyhow::bail!(
+                "Texture {:x} has been resized, which is not supported yet",
+                t.hashcode
+            );
+        }
+
+        header.flags = t.flags;
+        let (address, flags) = (header.common.address, header.flags);
+
+        base.seek(std::io::SeekFrom::Start(address as u64))?;
+        let mut tex = base
+            .read_type_args::<EXGeoTexture>(base.endian, (version, platform))
+            .context("Failed to read texture")?;
+
+        if tex.depth != t.depth {
+            anyhow::bail!(
+                "Texture {:x} has a different depth, which is not supported yet",
+                t.hashcode
+            );
+        }
+
+        tex.game_flags = t.game_flags;
+        tex.scroll_u = t.scroll[0];
+        tex.scroll_v = t.scroll[1];
+        tex.frame_rate = t.framerate;
+        tex.color = t.color;
+
+        let decoder = texture::create_for_platform(platform);
+        let original = UXGeoTexture::read(address, base, decoder.as_ref(), flags)
+            .with_context(|| format!("Failed to read texture {:x}", t.hashcode))?;
+        let external = original
+            .external_texture
+            .map(|(file, hashcode)| IrExternalReference { file, hashcode });
+        if t.external != external {
+            anyhow::bail!(
+                "Changing the external reference of texture {:x} is not supported yet",
+                t.hashcode
+            );
+        }
+
+        if t.frames != original.frames {
+            let (width, height) = (t.width as u32, t.height as u32 * t.depth.max(1) as u32);
+            let frames = t
+                .frames
+                .iter()
+                .map(|f| {
+                    RgbaImage::from_raw(width, height, f.clone())
+                        .map(|f| vec![f])
+                        .with_context(|| {
+                            format!("Frame data of texture {:x} has the wrong size", t.hashcode)
+                        })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let encoder = texture::create_encoder_for_platform(platform)?;
+            textures::write_frames(&mut tex, &frames, encoder.as_ref(), writer, Some(base))
+                .with_context(|| format!("Failed to write texture {:x}", t.hashcode))?;
+        }
+
+        writer.write_at(address as u64, &tex, (version, platform))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use base64::Engine;
+use eurochef_edb::Hashcode;
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+/// (De)serializes lists of binary blobs as base64 strings
+pub mod base64_vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(data: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            data.iter()
+                .map(|v| base64::engine::general_purpose::STANDARD.encode(v)),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(s)
+                    .map_err(D::Error::custom)
+            })
+            .collect()
+    }
+}
+
+/// Checks that objects which can't be written yet are identical to the ones in the base file
+pub(crate) fn ensure_unchanged<T: PartialEq>(
+    name: &str,
+    objects: &[T],
+    original: &[T],
+    hashcode: impl Fn(&T) -> Hashcode,
+) -> anyhow::Result<()> {
+    for o in objects {
+        match original.iter().find(|b| hashcode(b) == hashcode(o)) {
+            Some(b) if b == o => {}
+            Some(_) => anyhow::bail!("Modifying {name} is not supported yet ({:x})", hashcode(o)),
+            None => anyhow::bail!("Adding {name} is not supported yet ({:x})", hashcode(o)),
+        }
+    }
+
+    if let Some(b) = original
+        .iter()
+        .find(|b| !objects.iter().any(|o| hashcode(o) == hashcode(b)))
+    {
+        anyhow::bail!("Removing {name} is not supported yet ({:x})", hashcode(b));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,375 @@
+//! Sonix sound banks (.sfx)
+//!
+//! Only the layout used by version 201 banks is known right now. The MUSX header is always little
+//! endian and contains a platform identifier, the section table starts at 0x20. The sections
+//! themselves use the endianness of the platform.
+//!
+//! The sample info layout and the codec used by each platform haven't been checked against real banks yet,
+//! platforms with an unknown codec (Xbox 360, PS3, Wii U, 3DS) are refused.
+
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use anyhow::Context;
+use binrw::{binrw, BinReaderExt, Endian};
+use eurochef_edb::versions::Platform;
+
+use crate::codec::{self, dsp::DspHeader, SampleCodec};
+
+/// Sound effect hashcodes are stored without their type in the bank
+pub const SFX_HASHCODE_BASE: u32 = 0x1a000000;
+
+#[binrw]
+#[brw(little, magic = b"MUSX")]
+#[derive(Debug, Clone)]
+pub struct MusxHeader {
+    pub hashcode: u32,
+    pub version: u32,
+    pub file_size: u32,
+    /// Platform identifier, eg. `PC__` or `GC__`
+    pub platform_id: [u8; 4],
+    pub _unk14: [u32; 3],
+}
+
+impl MusxHeader {
+    pub fn platform(&self) -> anyhow::Result<Platform> {
+        Ok(match &self.platform_id {
+            b"PC__" => Platform::Pc,
+            b"XB__" => Platform::Xbox,
+            b"XE__" => Platform::Xbox360,
+            b"GC__" => Platform::GameCube,
+            b"WII_" => Platform::Wii,
+            b"WIIU" => Platform::WiiU,
+            b"PS2_" => Platform::Ps2,
+            b"PS3_" => Platform::Ps3,
+            b"3DS_" => Platform::ThreeDS,
+            id => anyhow::bail!(
+                "Unknown sound bank platform '{}'",
+                String::from_utf8_lossy(id)
+            ),
+        })
+    }
+}
+
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy)]
+pub struct MusxSection {
+    pub start: u32,
+    pub length: u32,
+}
+
+#[binrw]
+#[derive(Debug, Clone)]
+pub struct SampleInfo {
+    pub flags: u32,
+    /// Offset into the sample data section
+    pub address: u32,
+    /// Size of the encoded data
+    pub memory_size: u32,
+    pub frequency: u32,
+    /// Size of the data once decoded to 16-bit PCM
+    pub real_size: u32,
+    pub channels: u32,
+    pub bits: u32,
+    /// Offset into the special sample info section (eg. DSP headers)
+    pub psi_offset: u32,
+    pub loop_offset: u32,
+    pub duration: u32,
+}
+
+#[binrw]
+#[derive(Debug, Clone)]
+pub struct SoundEffect {
+    #[brw(ignore)]
+    pub hashcode: u32,
+
+    pub ducker_length: i16,
+    pub min_delay: i16,
+    pub max_delay: i16,
+    pub inner_radius: i16,
+    pub outer_radius: i16,
+    pub reverb_send: i8,
+    pub tracking_type: i8,
+    pub max_voices: i8,
+    pub priority: i8,
+    pub ducker: i8,
+    pub master_volume: i8,
+    pub flags: u16,
+
+    #[bw(calc = samples.len() as u16)]
+    pub sample_count: u16,
+    #[br(count = sample_count as usize)]
+    pub samples: Vec<SoundEffectSample>,
+}
+
+#[binrw]
+#[derive(Debug, Clone)]
+pub struct SoundEffectSample {
+    /// Index into the sample table, negative values refer to streamed samples
+    pub file_ref: i16,
+    pub pitch_offset: i16,
+    pub random_pitch_offset: i16,
+    pub base_volume: i8,
+    pub random_volume_offset: i8,
+    pub pan: i8,
+    #[brw(pad_after = 2)]
+    pub random_pan: i8,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecodedSample {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved 16-bit PCM
+    pub data: Vec<i16>,
+}
+
+#[derive(Debug)]
+pub struct SoundBank {
+    pub header: MusxHeader,
+    pub platform: Platform,
+    pub sound_effects: Vec<SoundEffect>,
+    pub samples: Vec<SampleInfo>,
+    pub special_sample_info: Vec<u8>,
+    pub sample_data: Vec<u8>,
+}
+
+impl SoundBank {
+    pub fn read<R: Read + Seek>(reader: &mut R) -> anyhow::Result<Self> {
+        let header: MusxHeader = reader.read_le().context("Failed to read MUSX header")?;
+        if header.version != 201 {
+            anyhow::bail!(
+                "Sound bank version {} is not supported right now",
+                header.version
+            );
+        }
+        let platform = header.platform()?;
+
+        reader.seek(SeekFrom::Start(0x20))?;
+        let [sfx_section, sample_info_section, special_section, data_section]: [MusxSection; 4] =
+            reader.read_le()?;
+        let endian = platform.endianness();
+
+        let sfx_data = read_section(reader, sfx_section)?;
+        let mut sfx_reader = Cursor::new(&sfx_data);
+        let sfx_count: u32 = sfx_reader.read_type(endian)?;
+        let sfx_table: Vec<(u32, u32)> = (0..sfx_count)
+            .map(|_| sfx_reader.read_type(endian))
+            .collect::<Result<_, _>>()?;
+
+        let mut sound_effects = Vec::with_capacity(sfx_table.len());
+        for (hashcode, offset) in sfx_table {
+            sfx_reader.seek(SeekFrom::Start(offset as u64))?;
+            let mut sfx: SoundEffect = sfx_reader
+                .read_type(endian)
+                .with_context(|| format!("Failed to read sound effect {hashcode:x}"))?;
+            sfx.hashcode = SFX_HASHCODE_BASE | hashcode;
+            sound_effects.push(sfx);
+        }
+
+        let sample_info_data = read_section(reader, sample_info_section)?;
+        let mut sample_info_reader = Cursor::new(&sample_info_data);
+        let sample_count: u32 = sample_info_reader.read_type(endian)?;
+        let samples = (0..sample_count)
+            .map(|_| sample_info_reader.read_type(endian))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            header,
+            platform,
+            sound_effects,
+            samples,
+            special_sample_info: read_section(reader, special_section)?,
+            sample_data: read_section(reader, data_section)?,
+        })
+    }
+
+    pub fn codec(&self) -> anyhow::Result<SampleCodec> {
+        Ok(match self.platform {
+            Platform::Pc => SampleCodec::Pcm16(Endian::Little),
+            Platform::Xbox => SampleCodec::XboxIma,
+            Platform::Ps2 => SampleCodec::Vag,
+            Platform::GameCube | Platform::Wii => SampleCodec::Dsp,
+            p => anyhow::bail!("Sounds for {p} are not supported right now"),
+        })
+    }
+
+    /// Hashcode of the first sound effect that uses each sample, if any
+    pub fn sample_hashcodes(&self) -> Vec<Option<u32>> {
+        let mut hashcodes = vec![None; self.samples.len()];
+        for sfx in &self.sound_effects {
+            for s in &sfx.samples {
+                if let Some(h @ None) = usize::try_from(s.file_ref)
+                    .ok()
+                    .and_then(|i| hashcodes.get_mut(i))
+                {
+                    *h = Some(sfx.hashcode);
+                }
+            }
+        }
+
+        hashcodes
+    }
+
+    pub fn decode_sample(&self, index: usize) -> anyhow::Result<DecodedSample> {
+        let info = self
+            .samples
+            .get(index)
+            .with_context(|| format!("Sample {index} is out of bounds"))?;
+        let start = info.address as usize;
+        let data = self
+            .sample_data
+            .get(start..start + info.memory_size as usize)
+            .with_context(|| format!("Sample {index} is outside of the sample data"))?;
+
+        let codec = self.codec()?;
+        let channels = info.channels.max(1) as u16;
+        if channels != 1 && !matches!(codec, SampleCodec::Pcm16(_)) {
+            anyhow::bail!("Sample {index} has {channels} channels, only mono ADPCM is supported");
+        }
+
+        let data = match codec {
+            SampleCodec::Pcm16(endian) => codec::decode_pcm16(data, endian),
+            SampleCodec::Vag => codec::vag::decode(data),
+            SampleCodec::XboxIma => codec::ima::decode(data),
+            SampleCodec::Dsp => {
+                let mut psi_reader = Cursor::new(&self.special_sample_info);
+                psi_reader.seek(SeekFrom::Start(info.psi_offset as u64))?;
+                let header: DspHeader = psi_reader
+                    .read_be()
+                    .with_context(|| format!("Failed to read DSP header for sample {index}"))?;
+                codec::dsp::decode(data, &header)
+            }
+        };
+
+        Ok(DecodedSample {
+            sample_rate: info.frequency,
+            channels,
+            data,
+        })
+    }
+}
+
+fn read_section<R: Read + Seek>(reader: &mut R, section: MusxSection) -> anyhow::Result<Vec<u8>> {
+    let stream_length = reader.seek(SeekFrom::End(0))?;
+    anyhow::ensure!(
+        section.start as u64 + section.length as u64 <= stream_length,
+        "Section at 0x{:x} (0x{:x} bytes) extends past the end of the file",
+        section.start,
+        section.length
+    );
+
+    reader.seek(SeekFrom::Start(section.start as u64))?;
+    let mut data = vec![0u8; section.length as usize];
+    reader
+        .read_exact(&mut data)
+        .context("Section extends past the end of the file")?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a little endian version 201 bank with a single sound effect and PCM sample
+    fn build_bank(platform_id: &[u8; 4], data_length: u32) -> Vec<u8> {
+        let mut sfx = vec![];
+        sfx.extend(1u32.to_le_bytes());
+        sfx.extend(0x12u32.to_le_bytes());
+        sfx.extend(12u32.to_le_bytes());
+        sfx.extend([0u8; 16]);
+        sfx.extend(0u16.to_le_bytes()); // flags
+        sfx.extend(1u16.to_le_bytes()); // sample_count
+        sfx.extend(0i16.to_le_bytes()); // file_ref
+        sfx.extend([0u8; 10]);
+
+        let mut sample_info = vec![];
+        sample_info.extend(1u32.to_le_bytes());
+        for v in [0u32, 0, 4, 22050, 4, 1, 16, 0, 0, 0] {
+            sample_info.extend(v.to_le_bytes());
+        }
+
+        let sample_data = [0x01, 0x00, 0xff, 0xff];
+
+        let mut bank = vec![];
+        bank.extend(b"MUSX");
+        bank.extend(0x1234u32.to_le_bytes());
+        bank.extend(201u32.to_le_bytes());
+        bank.extend(0u32.to_le_bytes());
+        bank.extend(platform_id);
+        bank.extend([0u8; 12]);
+
+        let mut offset = 0x40;
+        for length in [sfx.len() as u32, sample_info.len() as u32, 0, data_length] {
+            bank.extend((offset as u32).to_le_bytes());
+            bank.extend(length.to_le_bytes());
+            offset += length as usize;
+        }
+
+        bank.extend(sfx);
+        bank.extend(sample_info);
+        bank.extend(sample_data);
+        bank
+    }
+
+    #[test]
+    fn read_bank() {
+        let bank = SoundBank::read(&mut Cursor::new(build_bank(b"PC__", 4))).unwrap();
+        assert_eq!(bank.platform, Platform::Pc);
+        assert_eq!(bank.sound_effects.len(), 1);
+        assert_eq!(bank.sound_effects[0].hashcode, SFX_HASHCODE_BASE | 0x12);
+        assert_eq!(bank.samples.len(), 1);
+        assert_eq!(bank.decode_sample(0).unwrap().data, vec![1, -1]);
+    }
+
+    #[test]
+    fn section_out_of_bounds() {
+        assert!(SoundBank::read(&mut Cursor::new(build_bank(b"PC__", u32::MAX - 0x100))).is_err());
+    }
+
+    #[test]
+    fn unsupported_platforms() {
+        assert!(SoundBank::read(&mut Cursor::new(build_bank(b"????", 4))).is_err());
+
+        // The codec used by 3DS banks isn't known yet
+        let bank = SoundBank::read(&mut Cursor::new(build_bank(b"3DS_", 4))).unwrap();
+        assert_eq!(bank.platform, Platform::ThreeDS);
+        assert!(bank.decode_sample(0).is_err());
+    }
+
+    /// Reads every bank in the folder pointed to by `EUROCHEF_SFX_FIXTURES`
+    #[test]
+    #[ignore]
+    fn read_fixtures() {
+        let folder = std::env::var("EUROCHEF_SFX_FIXTURES")
+            .expect("EUROCHEF_SFX_FIXTURES should point to a folder with sound banks");
+
+        let mut tested = 0;
+        for entry in std::fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path
+                .extension()
+                .map_or(true, |e| !e.eq_ignore_ascii_case("sfx"))
+            {
+                continue;
+            }
+
+            let data = std::fs::read(&path).unwrap();
+            let bank = SoundBank::read(&mut Cursor::new(&data))
+                .unwrap_or_else(|e| panic!("Failed to read {}: {e:?}", path.display()));
+            assert_eq!(
+                bank.header.file_size as usize,
+                data.len(),
+                "{}",
+                path.display()
+            );
+            for i in 0..bank.samples.len() {
+                bank.decode_sample(i)
+                    .unwrap_or_else(|e| panic!("{} sample {i}: {e:?}", path.display()));
+            }
+            tested += 1;
+        }
+
+        assert!(tested > 0, "No sound banks found");
+    }
+}
@@ -0,0 +1,88 @@
+//! GameCube/Wii DSP ADPCM
+
+use binrw::binrw;
+
+pub const FRAME_SIZE: usize = 8;
+pub const SAMPLES_PER_FRAME: usize = 14;
+
+/// Standard 0x60 byte DSP ADPCM header, always big endian
+#[binrw]
+#[brw(big)]
+#[derive(Debug, Clone)]
+pub struct DspHeader {
+    pub num_samples: u32,
+    pub num_nibbles: u32,
+    pub sample_rate: u32,
+    pub loop_flag: u16,
+    pub format: u16,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    pub current_address: u32,
+    pub coefficients: [i16; 16],
+    pub gain: u16,
+    pub initial_ps: u16,
+    pub initial_hist1: i16,
+    pub initial_hist2: i16,
+    pub loop_ps: u16,
+    pub loop_hist1: i16,
+    #[brw(pad_after = 22)]
+    pub loop_hist2: i16,
+}
+
+/// Decodes mono DSP ADPCM data, stopping after `num_samples` samples
+pub fn decode(data: &[u8], header: &DspHeader) -> Vec<i16> {
+    let num_samples = header.num_samples as usize;
+    let mut samples = Vec::with_capacity(num_samples);
+    let (mut hist1, mut hist2) = (header.initial_hist1 as i32, header.initial_hist2 as i32);
+
+    'frames: for frame in data.chunks_exact(FRAME_SIZE) {
+        let scale = 1i32 << (frame[0] & 0xf);
+        let predictor = ((frame[0] >> 4) & 0x7) as usize;
+        let c1 = header.coefficients[predictor * 2] as i32;
+        let c2 = header.coefficients[predictor * 2 + 1] as i32;
+
+        for i in 0..SAMPLES_PER_FRAME {
+            if samples.len() >= num_samples {
+                break 'frames;
+            }
+
+            let byte = frame[1 + i / 2];
+            let nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0xf };
+            let nibble = ((nibble << 4) as i8 >> 4) as i32;
+
+            let sample = ((nibble * scale) << 11) + 1024 + c1 * hist1 + c2 * hist2;
+            let sample = (sample >> 11).clamp(i16::MIN as i32, i16::MAX as i32);
+
+            samples.push(sample as i16);
+            hist2 = hist1;
+            hist1 = sample;
+        }
+    }
+
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binrw::BinRead;
+
+    #[test]
+    fn decode_frames() {
+        let mut header_data = vec![0u8; 0x60];
+        // 16 samples, spanning two frames
+        header_data[3] = 16;
+        let header = DspHeader::read(&mut std::io::Cursor::new(header_data)).unwrap();
+
+        let mut data = vec![0u8; FRAME_SIZE * 2];
+        // Scale 1 with zeroed coefficients makes the nibbles map directly onto sample values
+        data[1] = 0x1f;
+        data[8] = 0x01;
+        data[9] = 0x70;
+
+        let samples = decode(&data, &header);
+        assert_eq!(samples.len(), 16);
+        assert_eq!(&samples[..3], &[1, -1, 0]);
+        assert_eq!(&samples[14..], &[14, 0]);
+    }
+}
@@ -0,0 +1,67 @@
+//! Xbox IMA ADPCM
+
+const INDEX_TABLE: [i32; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+pub const BLOCK_SIZE: usize = 0x24;
+/// The header sample only seeds the decoder, it isn't part of the output
+pub const SAMPLES_PER_BLOCK: usize = 64;
+
+/// Decodes mono Xbox IMA ADPCM data
+pub fn decode(data: &[u8]) -> Vec<i16> {
+    let mut samples = Vec::with_capacity(data.len() / BLOCK_SIZE * SAMPLES_PER_BLOCK);
+
+    for block in data.chunks_exact(BLOCK_SIZE) {
+        let mut hist = i16::from_le_bytes([block[0], block[1]]) as i32;
+        let mut index = (block[2] as i32).clamp(0, 88);
+
+        for &byte in &block[4..] {
+            for nibble in [byte & 0xf, byte >> 4] {
+                let step = STEP_TABLE[index as usize];
+                let mut delta = step >> 3;
+                if nibble & 1 != 0 {
+                    delta += step >> 2;
+                }
+                if nibble & 2 != 0 {
+                    delta += step >> 1;
+                }
+                if nibble & 4 != 0 {
+                    delta += step;
+                }
+                if nibble & 8 != 0 {
+                    delta = -delta;
+                }
+
+                hist = (hist + delta).clamp(i16::MIN as i32, i16::MAX as i32);
+                index = (index + INDEX_TABLE[nibble as usize]).clamp(0, 88);
+                samples.push(hist as i16);
+            }
+        }
+    }
+
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_block() {
+        let mut data = vec![0u8; BLOCK_SIZE];
+        data[0..2].copy_from_slice(&100i16.to_le_bytes());
+        data[4] = 0x84;
+
+        let samples = decode(&data);
+        assert_eq!(samples.len(), SAMPLES_PER_BLOCK);
+        // Nibble 4 adds the full step (7), nibble 8 only negates the base delta (9 >> 3)
+        assert_eq!(&samples[..2], &[107, 106]);
+    }
+}
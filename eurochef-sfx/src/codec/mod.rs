@@ -0,0 +1,27 @@
+use binrw::Endian;
+
+pub mod dsp;
+pub mod ima;
+pub mod vag;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleCodec {
+    /// 16-bit PCM (PC, Xbox 360)
+    Pcm16(Endian),
+    /// PS-ADPCM (PS2)
+    Vag,
+    /// DSP ADPCM (GameCube, Wii)
+    Dsp,
+    /// Xbox IMA ADPCM (Xbox)
+    XboxIma,
+}
+
+/// Decodes interleaved 16-bit PCM
+pub fn decode_pcm16(data: &[u8], endian: Endian) -> Vec<i16> {
+    data.chunks_exact(2)
+        .map(|b| match endian {
+            Endian::Big => i16::from_be_bytes([b[0], b[1]]),
+            Endian::Little => i16::from_le_bytes([b[0], b[1]]),
+        })
+        .collect()
+}
@@ -0,0 +1,61 @@
+//! PlayStation 2 VAG ADPCM (PS-ADPCM)
+
+/// Prediction filters, in 1/64ths
+const FILTERS: [(i32, i32); 5] = [(0, 0), (60, 0), (115, -52), (98, -55), (122, -60)];
+
+pub const FRAME_SIZE: usize = 16;
+pub const SAMPLES_PER_FRAME: usize = 28;
+
+/// Decodes mono PS-ADPCM data. Decoding stops at the first frame with the end flag set
+pub fn decode(data: &[u8]) -> Vec<i16> {
+    let mut samples = Vec::with_capacity(data.len() / FRAME_SIZE * SAMPLES_PER_FRAME);
+    let (mut hist1, mut hist2) = (0i32, 0i32);
+
+    for frame in data.chunks_exact(FRAME_SIZE) {
+        let shift = (frame[0] & 0xf) as i32;
+        let (f0, f1) = FILTERS
+            .get((frame[0] >> 4) as usize)
+            .copied()
+            .unwrap_or_default();
+        let flags = frame[1];
+
+        for i in 0..SAMPLES_PER_FRAME {
+            let byte = frame[2 + i / 2];
+            let nibble = if i % 2 == 0 { byte & 0xf } else { byte >> 4 };
+
+            // Sign-extend the nibble into the top of a 16-bit value, then apply the shift
+            let mut sample = (((nibble as i16) << 12) >> shift.min(12)) as i32;
+            sample += (hist1 * f0 + hist2 * f1 + 32) >> 6;
+            let sample = sample.clamp(i16::MIN as i32, i16::MAX as i32);
+
+            samples.push(sample as i16);
+            hist2 = hist1;
+            hist1 = sample;
+        }
+
+        if flags & 1 != 0 {
+            break;
+        }
+    }
+
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_frames() {
+        let mut data = vec![0u8; FRAME_SIZE * 2];
+        // Shift 12 with no filter makes the nibbles map directly onto sample values
+        data[0] = 0x0c;
+        data[2] = 0xf1;
+        // The end flag stops decoding before the second frame
+        data[1] = 1;
+
+        let samples = decode(&data);
+        assert_eq!(samples.len(), SAMPLES_PER_FRAME);
+        assert_eq!(&samples[..3], &[1, -1, 0]);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod bank;
+pub mod codec;
+pub mod wav;
+
+pub use bank::SoundBank;
@@ -0,0 +1,50 @@
+use std::io::Write;
+
+/// Writes 16-bit PCM samples as a RIFF WAVE file. Multichannel samples are expected to be interleaved
+pub fn write_wav<W: Write>(
+    writer: &mut W,
+    samples: &[i16],
+    sample_rate: u32,
+    channels: u16,
+) -> std::io::Result<()> {
+    let data_size = (samples.len() * 2) as u32;
+    let block_align = channels * 2;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    for s in samples {
+        writer.write_all(&s.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let mut data = vec![];
+        write_wav(&mut data, &[1, -1], 22050, 1).unwrap();
+
+        assert_eq!(data.len(), 44 + 4);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 40);
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 22050);
+        assert_eq!(&data[44..], &[1, 0, 0xff, 0xff]);
+    }
+}
@@ -1,15 +1,14 @@
 use std::{
     collections::HashMap,
     fs::File,
-    io::{BufReader, Cursor, Seek},
+    io::{Cursor, Seek},
     path::Path,
 };
 
 use anyhow::Context;
 use base64::Engine;
 use eurochef_edb::{
-    anim::EXGeoBaseAnimSkin, binrw::BinReaderExt, edb::EdbFile, entity::EXGeoEntity,
-    versions::Platform,
+    anim::EXGeoBaseAnimSkin, binrw::BinReaderExt, entity::EXGeoEntity, versions::Platform,
 };
 use eurochef_shared::{entities::read_entity, textures::UXGeoTexture};
 use image::ImageOutputFormat;
@@ -23,6 +22,7 @@ pub fn execute_command(
     filename: String,
     platform: Option<PlatformArg>,
     output_folder: Option<String>,
+    approximate_weights: bool,
 ) -> anyhow::Result<()> {
     warn!("THIS COMMAND IS A WORK IN PROGRESS");
 
@@ -32,18 +32,13 @@ pub fn execute_command(
     ));
     let output_folder = Path::new(&output_folder);
 
-    let platform = platform
-        .map(|p| p.into())
-        .or(Platform::from_path(&filename))
-        .expect("Failed to detect platform");
+    let mut edb = super::open_edb(&filename, platform)?;
+    let platform = edb.platform;
 
     if platform != Platform::Pc && platform != Platform::Xbox && platform != Platform::Xbox360 {
         anyhow::bail!("Entity extraction is only supported for PC and Xbox (360) (for now)")
     }
 
-    let file = File::open(&filename)?;
-    let reader = BufReader::new(file);
-    let mut edb = EdbFile::new(Box::new(reader), platform)?;
     let header = edb.header.clone();
 
     if header.animskin_list.len() == 0 {
@@ -93,7 +88,8 @@ pub fn execute_command(
             let mut cur = Cursor::new(Vec::new());
             image::write_buffer_with_format(
                 &mut cur,
-                &t.frames[0],
+                // Only the first slice of volume textures
+                &t.frames[0][..t.width as usize * t.height as usize * 4],
                 t.width as u32,
                 t.height as u32,
                 image::ColorType::Rgba8,
@@ -134,6 +130,7 @@ pub fn execute_command(
             .chain(skin.more_entities.iter())
             .map(|d| d.entity_index & 0x00ffffff)
             .collect();
+        let positions = gltf_export::rest_positions(&skin.rot_data_relative, &skin.hier_data);
 
         let mut gltf = gltf_export::create_mesh_scene(&skin_id);
 
@@ -199,6 +196,7 @@ pub fn execute_command(
                 continue;
             }
 
+            let first_primitive = gltf.meshes[0].primitives.len();
             gltf_export::add_mesh_to_scene(
                 &mut gltf,
                 &vertex_data,
@@ -208,8 +206,23 @@ pub fn execute_command(
                 &texture_uri_map,
                 header.hashcode,
             );
+            if approximate_weights {
+                let weights: Vec<_> = vertex_data
+                    .iter()
+                    .map(|v| nearest_bone(v.pos, &positions))
+                    .collect();
+                gltf_export::add_weights_to_mesh(&mut gltf, first_primitive, &weights);
+            }
         }
 
+        gltf_export::add_skeleton_to_scene(
+            &mut gltf,
+            &skin_id,
+            &skin.rot_data_relative,
+            &skin.hier_data,
+            approximate_weights,
+        );
+
         let mut outfile = File::create(output_folder.join(format!("{}.gltf", skin_id)))?;
         gltf::json::serialize::to_writer(&mut outfile, &gltf)
             .context("glTF serialization error")?;
@@ -217,3 +230,21 @@ pub fn execute_command(
 
     Ok(())
 }
+
+/// Binds a vertex to the bone that's closest to it in the rest pose.
+/// This is an approximation until the real vertex weights can be read
+fn nearest_bone(pos: [f32; 3], positions: &[[f32; 3]]) -> gltf_export::UXVertexWeights {
+    let distance =
+        |p: &[f32; 3]| (p[0] - pos[0]).powi(2) + (p[1] - pos[1]).powi(2) + (p[2] - pos[2]).powi(2);
+
+    let bone = positions
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+        .map_or(0, |(i, _)| i);
+
+    gltf_export::UXVertexWeights {
+        joints: [bone as u16, 0, 0, 0],
+        weights: [1.0, 0.0, 0.0, 0.0],
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use anyhow::Context;
+use eurochef_edb::versions::Platform;
+
+use crate::PlatformArg;
+
+pub fn execute_command(
+    filename: String,
+    platform: Option<PlatformArg>,
+    target: PlatformArg,
+    output: Option<String>,
+) -> anyhow::Result<()> {
+    let mut edb = super::open_edb(&filename, platform)?;
+    let target: Platform = target.into();
+
+    let output = output.unwrap_or(format!(
+        "./converted/{}/{}",
+        target.shorthand(),
+        Path::new(&filename).file_name().unwrap().to_string_lossy(),
+    ));
+    if let Some(parent) = Path::new(&output).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let data = eurochef_ir::convert::convert(&mut edb, target)
+        .with_context(|| format!("Failed to convert {filename} to {target}"))?;
+    std::fs::write(&output, data)?;
+
+    info!("Written {output}");
+
+    Ok(())
+}
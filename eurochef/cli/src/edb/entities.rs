@@ -1,13 +1,13 @@
 use std::{
     collections::HashMap,
     fs::File,
-    io::{BufReader, Cursor, Seek},
+    io::{Cursor, Seek},
     path::Path,
 };
 
 use anyhow::Context;
 use base64::Engine;
-use eurochef_edb::{binrw::BinReaderExt, edb::EdbFile, entity::EXGeoEntity, versions::Platform};
+use eurochef_edb::{binrw::BinReaderExt, entity::EXGeoEntity, versions::Platform};
 use eurochef_shared::{entities::read_entity, textures::UXGeoTexture};
 use image::ImageOutputFormat;
 use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
@@ -38,14 +38,9 @@ pub fn execute_command(
     ));
     let output_folder = Path::new(&output_folder);
 
-    let platform = platform
-        .map(|p| p.into())
-        .or(Platform::from_path(&filename))
-        .expect("Failed to detect platform");
+    let mut edb = super::open_edb(&filename, platform)?;
+    let platform = edb.platform;
 
-    let file = File::open(&filename)?;
-    let reader = BufReader::new(file);
-    let mut edb = EdbFile::new(Box::new(reader), platform)?;
     let header = edb.header.clone();
 
     match platform {
@@ -120,7 +115,8 @@ pub fn execute_command(
                 let mut cur = Cursor::new(Vec::new());
                 image::write_buffer_with_format(
                     &mut cur,
-                    &t.frames[0],
+                    // Only the first slice of volume textures
+                    &t.frames[0][..t.width as usize * t.height as usize * 4],
                     t.width as u32,
                     t.height as u32,
                     image::ColorType::Rgba8,
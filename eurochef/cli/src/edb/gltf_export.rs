@@ -1,4 +1,5 @@
 use base64::Engine;
+use eurochef_edb::{anim::EXGeoAnimSkinHierData, common::EXVector};
 use eurochef_shared::entities::{TriStrip, UXVertex};
 use gltf::json::{self as gjson, validation::Checked};
 use std::collections::HashMap;
@@ -27,6 +28,13 @@ use super::entities::Transparency;
 //     *n = (*n + 3) & !3;
 // }
 
+/// Joints and weights of a single vertex, written to the JOINTS_0 and WEIGHTS_0 attributes
+#[derive(Debug, Copy, Clone, Default)]
+pub struct UXVertexWeights {
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
+}
+
 /// Creates a scene with a single mesh in it
 pub fn create_mesh_scene(name: &str) -> gjson::Root {
     let node = gjson::Node {
@@ -353,6 +361,225 @@ pub fn add_mesh_to_scene(
     }
 }
 
+/// Adds JOINTS_0 and WEIGHTS_0 to the primitives of the first mesh, starting at `first_primitive`.
+///
+/// The primitives have to share a single vertex buffer, as they do when they're added by a single [`add_mesh_to_scene`] call
+pub fn add_weights_to_mesh(
+    root: &mut gjson::Root,
+    first_primitive: usize,
+    weights: &[UXVertexWeights],
+) {
+    // Joints are stored as 4 u16s, followed by 4 f32 weights
+    let wdata: Vec<u8> = weights
+        .iter()
+        .flat_map(|w| {
+            w.joints
+                .iter()
+                .flat_map(|j| j.to_le_bytes())
+                .chain(w.weights.iter().flat_map(|w| w.to_le_bytes()))
+        })
+        .collect();
+    root.buffers.push(gjson::Buffer {
+        byte_length: wdata.len().into(),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        uri: Some(create_data_uri(&wdata)),
+    });
+    root.buffer_views.push(gjson::buffer::View {
+        buffer: gjson::Index::new(root.buffers.len() as u32 - 1),
+        byte_length: wdata.len().into(),
+        byte_offset: None,
+        byte_stride: Some(gjson::buffer::Stride(24)),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        target: Some(Checked::Valid(gjson::buffer::Target::ArrayBuffer)),
+    });
+
+    let view_index = root.buffer_views.len() as u32 - 1;
+    let joints = gjson::Accessor {
+        buffer_view: Some(gjson::Index::new(view_index)),
+        byte_offset: None,
+        count: weights.len().into(),
+        component_type: Checked::Valid(gjson::accessor::GenericComponentType(
+            gjson::accessor::ComponentType::U16,
+        )),
+        extensions: Default::default(),
+        extras: Default::default(),
+        type_: Checked::Valid(gjson::accessor::Type::Vec4),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    };
+    let weights = gjson::Accessor {
+        byte_offset: Some((4 * std::mem::size_of::<u16>()).into()),
+        component_type: Checked::Valid(gjson::accessor::GenericComponentType(
+            gjson::accessor::ComponentType::F32,
+        )),
+        ..joints.clone()
+    };
+
+    let a_joints_index = root.accessors.len() as u32;
+    root.accessors.push(joints);
+    root.accessors.push(weights);
+
+    for primitive in root.meshes[0].primitives.iter_mut().skip(first_primitive) {
+        primitive.attributes.insert(
+            Checked::Valid(gjson::mesh::Semantic::Joints(0)),
+            gjson::Index::new(a_joints_index),
+        );
+        primitive.attributes.insert(
+            Checked::Valid(gjson::mesh::Semantic::Weights(0)),
+            gjson::Index::new(a_joints_index + 1),
+        );
+    }
+}
+
+/// Returns the parent of every bone. Parents always come before their children, the root bone links to itself
+fn bone_parents(bone_count: usize, hierarchy: &[EXGeoAnimSkinHierData]) -> Vec<Option<usize>> {
+    let mut parents = vec![None; bone_count];
+    for (i, h) in hierarchy.iter().enumerate().take(bone_count) {
+        let parent = h.link_index as usize;
+        if parent < i {
+            parents[i] = Some(parent);
+        }
+    }
+
+    parents
+}
+
+/// Absolute rest positions of every bone, accumulated from the positions relative to the parent bone.
+/// X is flipped to match the mesh data
+pub fn rest_positions(relative: &[EXVector], hierarchy: &[EXGeoAnimSkinHierData]) -> Vec<[f32; 3]> {
+    let parents = bone_parents(relative.len(), hierarchy);
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(relative.len());
+    for (i, p) in relative.iter().enumerate() {
+        let p = [-p[0], p[1], p[2]];
+        positions.push(match parents[i] {
+            Some(parent) => {
+                let pp = positions[parent];
+                [pp[0] + p[0], pp[1] + p[1], pp[2] + p[2]]
+            }
+            None => p,
+        });
+    }
+
+    positions
+}
+
+/// Adds a joint hierarchy and a skin with inverse bind matrices for the rest pose to the scene.
+///
+/// `relative` are the rest positions of every bone, assumed to be relative to the parent bone (going by the field name). `hierarchy` links every bone to its parent.
+/// With `bind_to_mesh`, the skin is bound to the mesh, every primitive of the mesh needs joints and weights then (see [`add_weights_to_mesh`])
+pub fn add_skeleton_to_scene(
+    root: &mut gjson::Root,
+    name: &str,
+    relative: &[EXVector],
+    hierarchy: &[EXGeoAnimSkinHierData],
+    bind_to_mesh: bool,
+) {
+    let positions = rest_positions(relative, hierarchy);
+    let parents = bone_parents(positions.len(), hierarchy);
+
+    let first_joint = root.nodes.len() as u32;
+    let mut children: Vec<Vec<gjson::Index<gjson::Node>>> = vec![vec![]; positions.len()];
+    for (i, parent) in parents.iter().enumerate() {
+        if let Some(parent) = parent {
+            children[*parent].push(gjson::Index::new(first_joint + i as u32));
+        }
+    }
+
+    let mut inverse_bind_matrices: Vec<f32> = Vec::with_capacity(positions.len() * 16);
+    for (i, (p, children)) in positions.iter().zip(children).enumerate() {
+        let r = relative[i];
+        root.nodes.push(gjson::Node {
+            camera: None,
+            children: (!children.is_empty()).then_some(children),
+            extensions: Default::default(),
+            extras: Default::default(),
+            matrix: None,
+            mesh: None,
+            name: Some(format!("bone_{i}")),
+            rotation: None,
+            scale: None,
+            translation: Some([-r[0], r[1], r[2]]),
+            skin: None,
+            weights: None,
+        });
+
+        // Column-major translation by the inverse of the absolute rest position
+        #[rustfmt::skip]
+        inverse_bind_matrices.extend_from_slice(&[
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            -p[0], -p[1], -p[2], 1.0,
+        ]);
+    }
+
+    let roots: Vec<gjson::Index<gjson::Node>> = parents
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.is_none())
+        .map(|(i, _)| gjson::Index::new(first_joint + i as u32))
+        .collect();
+    root.scenes[0].nodes.extend(roots.iter().copied());
+
+    let ibm_data: &[u8] = bytemuck::cast_slice(&inverse_bind_matrices);
+    root.buffers.push(gjson::Buffer {
+        byte_length: ibm_data.len().into(),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        uri: Some(create_data_uri(ibm_data)),
+    });
+    root.buffer_views.push(gjson::buffer::View {
+        buffer: gjson::Index::new(root.buffers.len() as u32 - 1),
+        byte_length: ibm_data.len().into(),
+        byte_offset: None,
+        byte_stride: None,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        target: None,
+    });
+    root.accessors.push(gjson::Accessor {
+        buffer_view: Some(gjson::Index::new(root.buffer_views.len() as u32 - 1)),
+        byte_offset: None,
+        count: positions.len().into(),
+        component_type: Checked::Valid(gjson::accessor::GenericComponentType(
+            gjson::accessor::ComponentType::F32,
+        )),
+        extensions: Default::default(),
+        extras: Default::default(),
+        type_: Checked::Valid(gjson::accessor::Type::Mat4),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    });
+
+    root.skins.push(gjson::Skin {
+        extensions: Default::default(),
+        extras: Default::default(),
+        inverse_bind_matrices: Some(gjson::Index::new(root.accessors.len() as u32 - 1)),
+        joints: (0..positions.len() as u32)
+            .map(|i| gjson::Index::new(first_joint + i))
+            .collect(),
+        name: Some(name.to_string()),
+        skeleton: roots.first().copied(),
+    });
+
+    if bind_to_mesh {
+        // The mesh node created by create_mesh_scene
+        root.nodes[0].skin = Some(gjson::Index::new(root.skins.len() as u32 - 1));
+    }
+}
+
 fn bounding_coords(vertices: &[UXVertex]) -> ([f32; 3], [f32; 3]) {
     let mut min = [f32::MAX, f32::MAX, f32::MAX];
     let mut max = [f32::MIN, f32::MIN, f32::MIN];
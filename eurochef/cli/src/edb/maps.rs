@@ -7,10 +7,8 @@ use std::{
 use anyhow::Context;
 use eurochef_edb::{
     binrw::BinReaderExt,
-    edb::EdbFile,
     entity::{EXGeoEntity, EXGeoMapZoneEntity},
     map::{EXGeoLight, EXGeoMap, EXGeoPath, EXGeoPlacement},
-    versions::Platform,
 };
 
 use eurochef_shared::maps::{TriggerInformation, UXGeoTrigger};
@@ -35,15 +33,8 @@ pub fn execute_command(
         None
     };
 
-    let platform = platform_arg
-        .clone()
-        .map(|p| p.into())
-        .or(Platform::from_path(&filename))
-        .expect("Failed to detect platform");
-
-    let file = File::open(&filename)?;
-    let reader = BufReader::new(file);
-    let mut edb = EdbFile::new(Box::new(reader), platform)?;
+    let mut edb = super::open_edb(&filename, platform_arg.clone())?;
+    let platform = edb.platform;
     let header = edb.header.clone();
 
     if header.map_list.len() == 0 {
@@ -1,8 +1,28 @@
+use std::io::BufReader;
+
+use anyhow::Context;
+use eurochef_edb::{edb::EdbFile, versions::Platform};
+use eurochef_filelist::vfs;
+
+use crate::PlatformArg;
+
 const TICK_STRINGS: &str = "⠁⠂⠄⡀⢀⠠⠐⠈";
 
 pub mod animations;
+pub mod convert;
 pub mod entities;
 mod gltf_export;
 pub mod maps;
 pub mod spreadsheets;
 pub mod textures;
+
+/// Opens an EDB file on disk, or inside of a filelist (eg. `Filelist.bin/gforce/binary/_bin_pc/foo.edb`)
+pub fn open_edb(filename: &str, platform: Option<PlatformArg>) -> anyhow::Result<EdbFile> {
+    let mut reader = BufReader::new(vfs::open_path(filename)?);
+    let platform = platform
+        .map(|p| p.into())
+        .or_else(|| Platform::from_reader(&mut reader, filename))
+        .context("Failed to detect platform")?;
+
+    Ok(EdbFile::new(Box::new(reader), platform)?)
+}
@@ -1,10 +1,12 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::process::exit;
-use std::{fs::File, io::BufReader};
 
+use anyhow::Context;
 use eurochef_edb::binrw::BinReaderExt;
-use eurochef_edb::{edb::EdbFile, versions::Platform, Hashcode};
+use eurochef_edb::edb::EdbPatcher;
+use eurochef_edb::Hashcode;
 use eurochef_shared::filesystem::path::DissectedFilelistPath;
 use eurochef_shared::maps::{format_hashcode, DefinitionDataType};
 use eurochef_shared::spreadsheets::{SpreadsheetDefinitions, UXGeoSpreadsheet};
@@ -17,9 +19,7 @@ pub fn execute_command(filename: String, output_folder: Option<String>) -> anyho
     let output_folder = Path::new(&output_folder);
     std::fs::create_dir_all(output_folder)?;
 
-    let file = File::open(&filename)?;
-    let reader = BufReader::new(file);
-    let mut edb = EdbFile::new(Box::new(reader), Platform::Pc)?;
+    let mut edb = super::open_edb(&filename, Some(crate::PlatformArg::Pc))?;
 
     let (spreadsheet_definitions, hashcodes) =
         if let Some(dissected_path) = DissectedFilelistPath::dissect(&filename) {
@@ -167,3 +167,90 @@ pub fn execute_command(filename: String, output_folder: Option<String>) -> anyho
 
     Ok(())
 }
+
+/// Replaces the text in text spreadsheets with the contents of a CSV file, or a folder of them
+pub fn execute_import_command(
+    filename: String,
+    input: String,
+    output_file: Option<String>,
+) -> anyhow::Result<()> {
+    let output_file = output_file.unwrap_or(format!(
+        "./patched/{}",
+        Path::new(&filename).file_name().unwrap().to_string_lossy(),
+    ));
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let input = Path::new(&input);
+    let csv_files = if input.is_dir() {
+        let mut files = vec![];
+        for e in std::fs::read_dir(input)? {
+            let path = e?.path();
+            if !path
+                .extension()
+                .map_or(false, |e| e.eq_ignore_ascii_case("csv"))
+            {
+                continue;
+            }
+
+            // Data spreadsheets are exported as CSV as well
+            if !UXGeoSpreadsheet::is_text_csv(BufReader::new(File::open(&path)?))? {
+                info!("Skipping {}, not a text spreadsheet", path.display());
+                continue;
+            }
+
+            files.push(path);
+        }
+        files.sort();
+        files
+    } else {
+        vec![input.to_path_buf()]
+    };
+
+    let mut rows = vec![];
+    for path in &csv_files {
+        rows.extend(
+            UXGeoSpreadsheet::read_text_csv(BufReader::new(File::open(path)?))
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+        );
+    }
+
+    let mut edb = super::open_edb(&filename, Some(crate::PlatformArg::Pc))?;
+    let mut spreadsheets = UXGeoSpreadsheet::read_all(&mut edb)?;
+    let mut writer = EdbPatcher::new(&mut edb)?;
+
+    let mut changed = 0;
+    for (hashcode, spreadsheet) in spreadsheets.iter_mut() {
+        if !matches!(spreadsheet, UXGeoSpreadsheet::Text(_)) {
+            continue;
+        }
+
+        let changed_sections = spreadsheet.import_text(&rows)?;
+        let UXGeoSpreadsheet::Text(sections) = spreadsheet else {
+            unreachable!()
+        };
+
+        for i in changed_sections {
+            info!(
+                "Importing spreadsheet {hashcode:08x} section {:08x}",
+                sections[i].hashcode
+            );
+            sections[i]
+                .write(&mut edb, &mut writer)
+                .with_context(|| format!("Failed to write section {:08x}", sections[i].hashcode))?;
+            changed += 1;
+        }
+    }
+
+    if changed == 0 {
+        println!("No changed text found");
+        return Ok(());
+    }
+
+    std::fs::write(&output_file, writer.finish()?)?;
+
+    info!("Imported {changed} text sections into {output_file}");
+
+    Ok(())
+}
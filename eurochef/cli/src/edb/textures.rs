@@ -1,12 +1,22 @@
 use std::{
     fs::File,
-    io::{BufReader, Write},
+    io::{BufReader, Seek, Write},
     path::Path,
 };
 
 use anyhow::Context;
-use eurochef_edb::{edb::EdbFile, versions::Platform};
-use eurochef_shared::textures::UXGeoTexture;
+use eurochef_edb::{
+    binrw::BinReaderExt,
+    edb::{EdbFile, EdbPatcher},
+    texture::EXGeoTexture,
+    Hashcode,
+};
+use eurochef_shared::{
+    platform::texture::{self, TextureEncoder},
+    texture_containers::{self, PixelFormat},
+    textures::{self, UXGeoTexture},
+};
+use image::{codecs::png::PngDecoder, imageops::FilterType, AnimationDecoder, RgbaImage};
 use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
 
 use crate::{edb::TICK_STRINGS, PlatformArg};
@@ -17,6 +27,7 @@ pub fn execute_command(
     output_folder: Option<String>,
     file_format: String,
     no_apngs: bool,
+    volume_layout: String,
 ) -> anyhow::Result<()> {
     let output_folder = output_folder.unwrap_or(format!(
         "./textures/{}/",
@@ -25,14 +36,8 @@ pub fn execute_command(
     let output_folder = Path::new(&output_folder);
     std::fs::create_dir_all(output_folder)?;
 
-    let platform = platform
-        .map(|p| p.into())
-        .or(Platform::from_path(&filename))
-        .expect("Failed to detect platform");
+    let mut edb = super::open_edb(&filename, platform)?;
 
-    let file = File::open(&filename)?;
-    let reader = BufReader::new(file);
-    let mut edb = EdbFile::new(Box::new(reader), platform)?;
     let header = edb.header.clone();
 
     let pb = ProgressBar::new(header.texture_list.len() as u64)
@@ -55,8 +60,22 @@ pub fn execute_command(
 
         match it.data {
             Ok(t) => {
-                if t.depth > 1 {
-                    warn!("Texture is 3D, skipping");
+                // Volume textures are decoded as a vertical atlas of all slices
+                let depth = t.depth.max(1) as u32;
+                let (width, height) = (t.width as u32, t.height as u32 * depth);
+                let is_container = file_format == "dds" || file_format == "ktx2";
+
+                if depth > 1 && !is_container && volume_layout == "slices" {
+                    for (i, f) in t.frames.iter().enumerate() {
+                        for (z, slice) in f.chunks_exact(f.len() / depth as usize).enumerate() {
+                            let filename = output_folder.join(format!(
+                                "{:08x}_frame{}_slice{}.{}",
+                                it.hashcode, i, z, file_format
+                            ));
+                            save_image(&filename, slice, width, t.height as u32, &file_format)?;
+                        }
+                    }
+
                     continue;
                 }
 
@@ -64,59 +83,47 @@ pub fn execute_command(
                     let filename =
                         output_folder.join(format!("{:08x}.{}", it.hashcode, file_format));
                     if t.frames.len() > 1 {
-                        let png_frames: Vec<apng::PNGImage> = t
-                            .frames
-                            .into_iter()
-                            .map(|data| {
-                                apng::load_dynamic_image(
-                                    image::RgbaImage::from_vec(
-                                        t.width as u32,
-                                        t.height as u32,
-                                        data,
-                                    )
-                                    .unwrap()
-                                    .into(),
-                                )
-                                .unwrap()
-                            })
-                            .collect();
-
-                        let apng_config = apng::create_config(&png_frames, Some(0))?;
-                        let mut imgfile =
-                            File::create(filename).context("Failed to create output image")?;
-                        let mut encoder = apng::Encoder::new(&mut imgfile, apng_config)?;
-                        encoder.encode_all(
-                            png_frames,
-                            Some(&apng::Frame {
-                                delay_den: Some(1000),
-                                delay_num: Some((1000.0 / t.framerate as f32) as u16),
-                                ..Default::default()
-                            }),
-                        )?;
-                        encoder.finish_encode()?;
+                        save_apng(&filename, &t.frames, width, height, t.framerate)?;
                     } else if let Some(f) = t.frames.into_iter().next() {
-                        let image =
-                            image::RgbaImage::from_vec(t.width as u32, t.height as u32, f).unwrap();
+                        let image = image::RgbaImage::from_vec(width, height, f).unwrap();
                         image.save(filename)?;
                     }
                 } else {
-                    for (i, f) in t.frames.into_iter().enumerate() {
+                    for (i, f) in t.frames.iter().enumerate() {
                         let filename = output_folder
                             .join(format!("{:08x}_frame{}.{}", it.hashcode, i, file_format));
-                        match file_format.as_str() {
-                            "qoi" => {
-                                let filedata =
-                                    qoi::encode_to_vec(f, t.width as u32, t.height as u32)?;
-                                let mut imgfile = File::create(filename)
-                                    .context("Failed to create output image")?;
-                                imgfile.write_all(&filedata)?;
-                            }
-                            _ => {
-                                let image =
-                                    image::RgbaImage::from_vec(t.width as u32, t.height as u32, f)
-                                        .unwrap();
-                                image.save(filename)?;
+                        if is_container {
+                            let mut imgfile =
+                                File::create(filename).context("Failed to create output image")?;
+                            // Keep the original compressed blocks when the format maps to a standard one
+                            let (format, levels) = match (t.block_format, t.blocks.get(i)) {
+                                (Some(b), Some(blocks)) => (
+                                    PixelFormat::Block(b),
+                                    blocks.iter().map(|l| l.as_slice()).collect(),
+                                ),
+                                _ => (PixelFormat::Rgba8, t.mip_chain(i)),
+                            };
+                            if file_format == "dds" {
+                                texture_containers::write_dds(
+                                    &mut imgfile,
+                                    t.width as u32,
+                                    t.height as u32,
+                                    depth,
+                                    format,
+                                    &levels,
+                                )?;
+                            } else {
+                                texture_containers::write_ktx2(
+                                    &mut imgfile,
+                                    t.width as u32,
+                                    t.height as u32,
+                                    depth,
+                                    format,
+                                    &levels,
+                                )?;
                             }
+                        } else {
+                            save_image(&filename, f, width, height, &file_format)?;
                         }
                     }
                 }
@@ -129,3 +136,279 @@ pub fn execute_command(
 
     Ok(())
 }
+
+/// Writes every frame in storage order, so the APNG can be imported again
+fn save_apng(
+    path: &Path,
+    frames: &[Vec<u8>],
+    width: u32,
+    height: u32,
+    framerate: u8,
+) -> anyhow::Result<()> {
+    let png_frames: Vec<apng::PNGImage> = frames
+        .iter()
+        .map(|data| {
+            apng::load_dynamic_image(
+                image::RgbaImage::from_vec(width, height, data.clone())
+                    .unwrap()
+                    .into(),
+            )
+            .unwrap()
+        })
+        .collect();
+
+    let apng_config = apng::create_config(&png_frames, Some(0))?;
+    let mut imgfile = File::create(path).context("Failed to create output image")?;
+    let mut encoder = apng::Encoder::new(&mut imgfile, apng_config)?;
+    encoder.encode_all(
+        png_frames,
+        Some(&apng::Frame {
+            delay_den: Some(1000),
+            delay_num: Some((1000.0 / framerate.max(1) as f32) as u16),
+            ..Default::default()
+        }),
+    )?;
+
+    Ok(())
+}
+
+/// Saves RGBA data as a regular image file
+fn save_image(
+    path: &Path,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    file_format: &str,
+) -> anyhow::Result<()> {
+    if file_format == "qoi" {
+        let filedata = qoi::encode_to_vec(data, width, height)?;
+        let mut imgfile = File::create(path).context("Failed to create output image")?;
+        imgfile.write_all(&filedata)?;
+    } else {
+        let image = image::RgbaImage::from_vec(width, height, data.to_vec()).unwrap();
+        image.save(path)?;
+    }
+
+    Ok(())
+}
+
+pub fn execute_import_command(
+    filename: String,
+    platform: Option<PlatformArg>,
+    input_folder: String,
+    output_file: Option<String>,
+    resize: bool,
+) -> anyhow::Result<()> {
+    let output_file = output_file.unwrap_or(format!(
+        "./patched/{}",
+        Path::new(&filename).file_name().unwrap().to_string_lossy(),
+    ));
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut edb = super::open_edb(&filename, platform)?;
+    let platform = edb.platform;
+
+    let header = edb.header.clone();
+
+    let mut writer = EdbPatcher::new(&mut edb)?;
+    let encoder = texture::create_encoder_for_platform(platform)?;
+
+    let pb = ProgressBar::new(header.texture_list.len() as u64)
+        .with_finish(indicatif::ProgressFinish::AndLeave);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {msg} ({pos}/{len})",
+        )
+        .unwrap()
+        .progress_chars("##-")
+        .tick_chars(TICK_STRINGS),
+    );
+    pb.set_message("Importing textures");
+
+    let mut imported = 0;
+    for t in header.texture_list.iter().progress_with(pb) {
+        let hash_str = format!("0x{:x}", t.common.hashcode);
+        let _span = error_span!("texture", hash = %hash_str);
+        let _span_enter = _span.enter();
+
+        match import_texture(
+            &mut edb,
+            &mut writer,
+            encoder.as_ref(),
+            t.common.hashcode,
+            t.common.address,
+            Path::new(&input_folder),
+            resize,
+        ) {
+            Ok(true) => imported += 1,
+            Ok(false) => {}
+            Err(e) => error!("Failed to import texture: {e:?}"),
+        }
+    }
+
+    std::fs::write(&output_file, writer.finish()?)?;
+
+    info!("Imported {imported} textures into {output_file}");
+
+    Ok(())
+}
+
+/// Replaces the frames of a single texture. Returns `false` if there are no images for it
+fn import_texture(
+    edb: &mut EdbFile,
+    writer: &mut EdbPatcher,
+    encoder: &dyn TextureEncoder,
+    hashcode: Hashcode,
+    address: u32,
+    input_folder: &Path,
+    resize: bool,
+) -> anyhow::Result<bool> {
+    let version = edb.header.version;
+    edb.seek(std::io::SeekFrom::Start(address as u64))?;
+    let mut tex = edb
+        .read_type_args::<EXGeoTexture>(edb.endian, (version, edb.platform))
+        .context("Failed to read texture")?;
+
+    let Some(mut frames) = load_frames(input_folder, hashcode, tex.frame_offsets.len())? else {
+        return Ok(false);
+    };
+
+    // Volume textures are imported as a vertical atlas of all slices
+    let (width, height, depth) = (tex.width as u32, tex.height as u32, tex.depth.max(1) as u32);
+    for (i, levels) in frames.iter_mut().enumerate() {
+        let frame = levels.first_mut().context("Frame has no data")?;
+        if frame.dimensions() != (width, height * depth) {
+            anyhow::ensure!(
+                resize,
+                "Frame {i} is {}x{}, but the texture is {width}x{} (use --resize to resize it)",
+                frame.width(),
+                frame.height(),
+                height * depth
+            );
+            anyhow::ensure!(
+                frame.height() % depth == 0,
+                "Frame {i} is {}x{}, which can't be split into {depth} slices",
+                frame.width(),
+                frame.height()
+            );
+
+            *frame =
+                textures::resize_volume(frame, depth, width, height, depth, FilterType::Lanczos3);
+        }
+    }
+
+    let platform = edb.platform;
+    textures::write_frames(&mut tex, &frames, encoder, writer, Some(edb))?;
+    writer.write_at(address as u64, &tex, (version, platform))?;
+
+    Ok(true)
+}
+
+/// Loads the frames for a texture, in any of the formats that textures are extracted to.
+/// Every frame contains the top level, followed by any mip levels stored in the image file
+fn load_frames(
+    folder: &Path,
+    hashcode: Hashcode,
+    frame_count: usize,
+) -> anyhow::Result<Option<Vec<Vec<RgbaImage>>>> {
+    for ext in ["png", "tga", "qoi", "dds", "ktx2"] {
+        let path = folder.join(format!("{hashcode:08x}.{ext}"));
+        if path.exists() {
+            if ext == "png" {
+                let decoder = PngDecoder::new(BufReader::new(File::open(&path)?))?;
+                if decoder.is_apng() {
+                    let frames = decoder.apng().into_frames().collect_frames()?;
+                    return Ok(Some(
+                        frames.into_iter().map(|f| vec![f.into_buffer()]).collect(),
+                    ));
+                }
+            }
+
+            return Ok(Some(vec![load_image(&path)?]));
+        }
+
+        if folder.join(format!("{hashcode:08x}_frame0.{ext}")).exists() {
+            return (0..frame_count)
+                .map(|i| load_image(&folder.join(format!("{hashcode:08x}_frame{i}.{ext}"))))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Loads an image with all of its mip levels
+fn load_image(path: &Path) -> anyhow::Result<Vec<RgbaImage>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    match extension {
+        "qoi" => {
+            let data = std::fs::read(path)?;
+            let mut decoder = qoi::Decoder::new(&data)?.with_channels(qoi::Channels::Rgba);
+            let (width, height) = (decoder.header().width, decoder.header().height);
+            return Ok(vec![RgbaImage::from_vec(
+                width,
+                height,
+                decoder.decode_to_vec()?,
+            )
+            .context("Invalid QOI image")?]);
+        }
+        "dds" | "ktx2" => {
+            let data = std::fs::read(path)?;
+            let levels = if extension == "dds" {
+                texture_containers::read_dds(&data)
+            } else {
+                texture_containers::read_ktx2(&data)
+            };
+
+            return levels.with_context(|| format!("Failed to open {}", path.display()));
+        }
+        _ => {}
+    }
+
+    Ok(vec![image::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .into_rgba8()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apng_roundtrip() {
+        let folder = std::env::temp_dir().join(format!("eurochef-apng-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        // Frames with distinct colors, so reordering or repeating frames would show up
+        let frames: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| [i * 80, 255 - i * 80, i, 255].repeat(4 * 4))
+            .collect();
+        let hashcode = 0x06000001;
+        save_apng(
+            &folder.join(format!("{hashcode:08x}.png")),
+            &frames,
+            4,
+            4,
+            15,
+        )
+        .unwrap();
+
+        let loaded = load_frames(&folder, hashcode, frames.len())
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(loaded.len(), frames.len());
+        for (l, f) in loaded.iter().zip(&frames) {
+            assert_eq!(l.len(), 1);
+            assert_eq!(l[0].dimensions(), (4, 4));
+            assert_eq!(l[0].as_raw(), f);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashSet, fs::File, io::Read, path::Path};
+
+use anyhow::Context;
+use eurochef_edb::{binrw::Endian, versions::Platform};
+use eurochef_filelist::{unified::UXFileInfo, UXFileList, Vfs};
+use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
+
+use crate::filelist::{datafile::DataFileWriter, read_filelist_version, TICK_STRINGS};
+use crate::PlatformArg;
+
+pub fn execute_command(
+    filename: String,
+    output_file: Option<String>,
+    version: Option<u32>,
+    platform: Option<PlatformArg>,
+    split_size: u32,
+) -> anyhow::Result<()> {
+    let vfs = Vfs::open(&filename)?;
+    let source_version = read_filelist_version(&filename, vfs.filelist.endian)?;
+
+    let version = version.unwrap_or(source_version);
+    if !UXFileList::is_version_supported(version) {
+        anyhow::bail!("Filelist version {version} is not supported for packing right now")
+    }
+
+    let endian = platform
+        .map(|p| Platform::from(p).endianness())
+        .unwrap_or(vfs.filelist.endian);
+
+    let output_file = output_file.unwrap_or(format!(
+        "./converted/v{version}/{}",
+        Path::new(&filename).file_stem().unwrap().to_string_lossy(),
+    ));
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    println!("Converting {filename} (version {source_version}) to version {version}");
+
+    let (num_files, num_data_files) = repack(&vfs, &output_file, version, endian, split_size)?;
+
+    println!("Successfully converted {num_files} files into {num_data_files} data files");
+
+    Ok(())
+}
+
+/// Copies all files from a filelist into new data files and writes a new header for them.
+/// Returns the amount of files and data files written
+pub fn repack(
+    vfs: &Vfs,
+    output_file: &str,
+    version: u32,
+    endian: Endian,
+    split_size: u32,
+) -> anyhow::Result<(usize, u32)> {
+    let single_data_file = version == 4;
+
+    // Copy the files in the order they're stored in the source data files, so the layout is kept intact
+    let mut copies: Vec<_> = vfs
+        .filelist
+        .files
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (_, info))| info.locations.iter().map(move |l| (*l, i)))
+        .collect();
+    copies.sort_by_key(|(l, _)| (l.filelist_num, l.addr));
+
+    if single_data_file {
+        // Version 4 filelists only store a single location per file, keep the first copy of each
+        let mut seen = HashSet::new();
+        copies.retain(|(_, i)| seen.insert(*i));
+    }
+
+    let mut files: Vec<(String, UXFileInfo)> = vfs
+        .filelist
+        .files
+        .iter()
+        .map(|(path, info)| {
+            (
+                path.clone(),
+                UXFileInfo {
+                    locations: vec![],
+                    ..info.clone()
+                },
+            )
+        })
+        .collect();
+
+    let pb = ProgressBar::new(copies.len() as u64);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {msg} ({pos}/{len})",
+        )
+        .unwrap()
+        .progress_chars("##-")
+        .tick_chars(TICK_STRINGS),
+    );
+    pb.set_message("Copying files");
+
+    let mut data_writer = DataFileWriter::new(output_file, split_size, single_data_file)?;
+    for (_, i) in copies.into_iter().progress_with(pb) {
+        let (path, info) = &vfs.filelist.files[i];
+
+        let mut filedata = vec![];
+        vfs.open_info(info)
+            .with_context(|| format!("Failed to open {path}"))?
+            .read_to_end(&mut filedata)?;
+
+        let (location, _) = data_writer.write_file(&filedata)?;
+        files[i].1.locations.push(location);
+    }
+
+    let filelist = UXFileList {
+        num_filelists: Some(data_writer.filelist_num() as u16),
+        build_type: vfs.filelist.build_type,
+        endian,
+        files,
+    };
+
+    let mut f_info = File::create(format!("{output_file}.bin"))?;
+    filelist.write(&mut f_info, version)?;
+
+    Ok((filelist.files.len(), data_writer.filelist_num() + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eurochef_filelist::unified::UXFileLoc;
+
+    #[test]
+    fn repack_v5_to_v4() {
+        let base = std::env::temp_dir().join(format!("eurochef-convert-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+
+        let a: Vec<u8> = (0..0x1000).map(|v| v as u8).collect();
+        let b: Vec<u8> = (0..0x800).map(|v| (v * 7) as u8).collect();
+
+        // The first file has a copy in the second data file, which isn't adjacent to it when sorted by location
+        let loc = |addr: u32, filelist_num: u32| UXFileLoc {
+            addr,
+            filelist_num: Some(filelist_num),
+            unk0: 0,
+        };
+        let info = |data: &[u8], hashcode: u32, locations: Vec<UXFileLoc>| UXFileInfo {
+            locations,
+            length: data.len() as u32,
+            hashcode,
+            version: 1,
+            flags: 0,
+            unk0: 0,
+        };
+
+        std::fs::write(base.join("Filelist.000"), [a.as_slice(), &b].concat()).unwrap();
+        std::fs::write(base.join("Filelist.001"), &a).unwrap();
+        let source = UXFileList {
+            num_filelists: Some(2),
+            build_type: None,
+            endian: Endian::Little,
+            files: vec![
+                (
+                    "x:\\a.bin".to_string(),
+                    info(&a, 0x1000, vec![loc(0, 0), loc(0, 1)]),
+                ),
+                (
+                    "x:\\b.bin".to_string(),
+                    info(&b, 0x1001, vec![loc(a.len() as u32, 0)]),
+                ),
+            ],
+        };
+        source
+            .write(&mut File::create(base.join("Filelist.bin")).unwrap(), 5)
+            .unwrap();
+
+        let vfs = Vfs::open(base.join("Filelist.bin")).unwrap();
+        let output = base.join("converted").to_string_lossy().to_string();
+        repack(&vfs, &output, 4, Endian::Little, 0x80000000).unwrap();
+
+        let converted = Vfs::open(format!("{output}.bin")).unwrap();
+        assert_eq!(converted.filelist.files.len(), 2);
+        for (path, data) in [("x:\\a.bin", &a), ("x:\\b.bin", &b)] {
+            let (_, info) = converted.find(path).unwrap();
+            assert_eq!(info.locations.len(), 1);
+
+            let mut filedata = vec![];
+            converted
+                .open_info(info)
+                .unwrap()
+                .read_to_end(&mut filedata)
+                .unwrap();
+            assert_eq!(&filedata, data, "{path} differs");
+        }
+
+        // The copy of the first file isn't written to the data file again
+        let dat_size = std::fs::metadata(format!("{output}.DAT")).unwrap().len() as usize;
+        assert!(
+            dat_size < a.len() * 2 + b.len(),
+            "Data file is {dat_size:#x} bytes"
+        );
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,21 +1,34 @@
 use anyhow::Context;
-use eurochef_edb::binrw::{BinReaderExt, BinWriterExt};
-use eurochef_edb::versions::{transform_windows_path, Platform};
+use eurochef_edb::binrw::{BinReaderExt, Endian};
+use eurochef_edb::versions::Platform;
 use eurochef_filelist::{
-    path,
-    structures::{EXFileListHeader5, FileInfo5, FileLoc5},
+    scr::{has_wildcards, matches_pattern, ScrEntry, ScrFile},
+    unified::UXFileInfo,
+    UXFileList,
 };
 use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
 use std::{
+    collections::HashMap,
     fs::File,
-    io::{Read, Seek, Write},
+    io::{Cursor, Read, Seek},
     path::Path,
 };
 use walkdir::WalkDir;
 
-use crate::filelist::TICK_STRINGS;
+use crate::filelist::{datafile::DataFileWriter, TICK_STRINGS};
 use crate::PlatformArg;
 
+struct PackFile {
+    /// Path inside of the filelist
+    vpath: String,
+    /// Path on disk
+    rpath: String,
+    alignment: u32,
+    split_size: u32,
+    /// Data file to continue in before writing this file
+    filelist: Option<u32>,
+}
+
 pub fn execute_command(
     input_folder: String,
     output_file: String,
@@ -25,21 +38,22 @@ pub fn execute_command(
     split_size: u32,
     scr_file: Option<String>,
 ) -> anyhow::Result<()> {
-    // TODO: Make a trait for filelists bundling both the read and from/into functions so that they can be used genericly
     let platform: Platform = platform.into();
     let endian = platform.endianness();
 
-    if !(5..=7).contains(&version) {
-        panic!("Only version 5, 6 and 7 are supported for packing right now")
+    if !UXFileList::is_version_supported(version) {
+        anyhow::bail!("Filelist version {version} is not supported for packing right now")
     }
 
+    // Version 4 filelists only have a single data file
+    let single_data_file = version == 4;
+
     println!("Packing files from {input_folder} with drive letter {drive_letter}:");
 
-    let fp_data = format!("{output_file}.000");
     let fp_info = format!("{output_file}.bin");
-    let mut f_data = File::create(fp_data).context("Failed to create output file")?;
+    let mut data_writer = DataFileWriter::new(&output_file, split_size, single_data_file)?;
 
-    let mut files: Vec<(String, FileInfo5)> = vec![];
+    let mut files: Vec<(String, UXFileInfo)> = vec![];
 
     // TODO: Handle absolute paths on unix
     #[cfg(not(target_os = "windows"))]
@@ -50,91 +64,52 @@ pub fn execute_command(
         }
     }
 
-    let mut file_paths = vec![];
-
-    if let Some(scr_file) = scr_file {
-        println!("Reading files in SCR order");
-        let scr_files = parse_scr_filelist(scr_file).context("Failed to read SCR file")?;
-        let mut file_paths_temp = vec![];
-        for s in scr_files {
-            if &s[1..=2] != ":\\" {
-                panic!("Invalid path in scr file: {s}");
-            }
-
-            file_paths_temp.push(s)
-        }
-        println!("Loaded {} paths from SCR", file_paths_temp.len());
-        let pb = ProgressBar::new(0);
-        pb.set_style(
-            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {msg}")
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {msg}")
+            .unwrap()
+            .progress_chars("##-")
+            .tick_chars(TICK_STRINGS),
+    );
+    pb.set_message("Locating files");
+
+    // Path relative to the input folder, real path
+    let mut disk_files = vec![];
+    for e in WalkDir::new(&input_folder) {
+        pb.tick();
+        let e = e?;
+        if e.file_type().is_file() {
+            let fpath = pathdiff::diff_paths(e.path(), &input_folder)
                 .unwrap()
-                .progress_chars("##-")
-                .tick_chars(TICK_STRINGS),
-        );
-        pb.set_message("Locating files");
-
-        for p in &file_paths_temp {
-            // Join path to root folder and change globbing pattern to be `glob`-compatible
-            let path_on_disk =
-                Path::new(&input_folder).join(transform_windows_path(&p[3..].replace('#', "?")));
-
-            let mut found_files = false;
-            #[allow(for_loops_over_fallibles)]
-            for entry in glob::glob(&path_on_disk.to_string_lossy())
-                .context("Failed to parse SCR glob pattern, report to cohae")?
-            {
-                let entry = entry?;
-                if std::fs::metadata(&entry)?.is_file() {
-                    let fpath = pathdiff::diff_paths(&entry, &input_folder)
-                        .unwrap()
-                        .to_string_lossy()
-                        .replace('/', "\\");
-
-                    file_paths.push((
-                        format!("{drive_letter}:\\{fpath}"),
-                        entry.to_string_lossy().to_string(),
-                    ))
-                }
-
-                found_files = true;
-            }
+                .to_string_lossy()
+                .replace('/', "\\");
 
-            if !found_files {
-                // TODO: log crate when?
-                println!("Warning: SCR path {p} yielded no results")
-            }
+            disk_files.push((fpath, e.path().to_string_lossy().to_string()))
         }
-    } else {
-        let pb = ProgressBar::new(0);
-        pb.set_style(
-            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {msg}")
-                .unwrap()
-                .progress_chars("##-")
-                .tick_chars(TICK_STRINGS),
-        );
-        pb.set_message("Locating files");
-
-        for e in WalkDir::new(&input_folder) {
-            pb.tick();
-            let e = e?;
-            if e.file_type().is_file() {
-                let fpath = pathdiff::diff_paths(e.path(), &input_folder)
-                    .unwrap()
-                    .to_string_lossy()
-                    .replace('/', "\\");
-
-                file_paths.push((
-                    format!("{drive_letter}:\\{fpath}"),
-                    e.path().to_string_lossy().to_string(),
-                ))
-            }
-        }
-
-        pb.finish_and_clear();
-        println!("Located {} files", file_paths.len());
     }
 
-    let mut filelist_num = 0;
+    pb.finish_and_clear();
+    println!("Located {} files", disk_files.len());
+
+    let file_paths = if let Some(scr_file) = scr_file {
+        println!("Reading files in SCR order");
+        let scr = ScrFile::read(&mut File::open(scr_file).context("Failed to open SCR file")?)
+            .context("Failed to read SCR file")?;
+        println!("Loaded {} entries from SCR", scr.entries.len());
+
+        resolve_scr_files(&scr, &disk_files, drive_letter, split_size)?
+    } else {
+        disk_files
+            .into_iter()
+            .map(|(fpath, rpath)| PackFile {
+                vpath: format!("{drive_letter}:\\{fpath}"),
+                rpath,
+                alignment: 0x800,
+                split_size,
+                filelist: None,
+            })
+            .collect()
+    };
 
     let pb =
         ProgressBar::new(file_paths.len() as u64).with_finish(indicatif::ProgressFinish::AndLeave);
@@ -148,179 +123,257 @@ pub fn execute_command(
     );
     pb.set_message("Packing files");
 
-    let mut common_garbage_buf = vec![];
+    // Lowercase virtual path to index in `files`, a path that is listed more than once gets an extra copy of its data
+    let mut file_indices: HashMap<String, usize> = HashMap::new();
 
-    // Virtual path, real path
-    for (i, (vpath, rpath)) in file_paths.iter().enumerate().progress_with(pb) {
+    for (i, f) in file_paths.iter().enumerate().progress_with(pb) {
+        let vpath = &f.vpath;
         let mut filedata = vec![];
-        let mut infile = File::open(rpath)?;
-        infile.read_to_end(&mut filedata)?;
-
-        let mut length = filedata.len() as u32;
-
-        let (hashcode, version, flags) = if vpath.to_ascii_lowercase().ends_with(".edb") {
-            // Use base filesize instead of full filesize
-            infile.seek(std::io::SeekFrom::Start(0x18))?;
-            length = infile.read_type(endian)?;
-
-            // TODO: Kind of inefficient to read from the file again instead of using the buffer
-            infile.seek(std::io::SeekFrom::Start(4))?;
-            (
-                infile.read_type(endian)?,
-                infile.read_type(endian)?,
-                infile.read_type(endian)?,
-            )
-        } else if vpath.to_ascii_lowercase().ends_with(".sfx") {
-            infile.seek(std::io::SeekFrom::Start(4))?;
-            (
-                infile.read_le::<u32>()? | 0x21000000,
-                infile.read_type::<u8>(endian)? as u32,
-                0,
-            )
-        } else {
-            (0x81000000 | i as u32, 0, 0)
-        };
+        File::open(&f.rpath)?.read_to_end(&mut filedata)?;
 
-        if f_data.stream_position()? as usize + filedata.len() > split_size as usize {
-            filelist_num += 1;
-
-            let fp_data = format!("{}.{:03}", output_file, filelist_num);
-            f_data = File::create(&fp_data)?;
+        let existing_index = file_indices.get(&vpath.to_lowercase()).copied();
+        if existing_index.is_some() && single_data_file {
+            anyhow::bail!("Version {version} filelists can't store multiple copies of {vpath}");
         }
 
-        files.push((
-            vpath.clone(),
-            FileInfo5 {
-                version,
-                flags,
-                length,
-                hashcode,
-                fileloc: vec![FileLoc5 {
-                    addr: f_data.stream_position()? as u32,
-                    filelist_num,
-                }],
-            },
-        ));
-
-        // swy: write the actual file contents
-        f_data.write_all(&filedata)?;
-
-        // Pad next data to 2048 bytes
-        let unaligned_pos = f_data.stream_position()?;
-        let aligned_pos = (unaligned_pos + 0x7ff) & !0x7ff; /* swy: 2048 - 1 = 0x7ff */
-        let difference: usize = (aligned_pos - unaligned_pos) as usize;
-
-        // swy: this funky buffer holds the cumulative overwritten contents of everything that came before;
-        //      we need to use this as a sort of emulation layer for matching how the original XUtil memcpy()'ed not only
-        //      until the end of the current file, but also extending it to any garbage data that may lay beyond the limit, what's there?
-        //      probably the data at that offset of any previous file big enough to reach there, otherwise we'll use zeroes
-
-        //      so here we're always making the buffer big enough to cover the total space that we need, and then pasting the file to cover
-        //      from the start, until its maximum size, anything that remains keeps the previous data, because that's what we like ¯\_(ツ)_/¯
-        let filedata_len_plus_padding = filedata.len() + difference;
-
-        common_garbage_buf.resize(
-            common_garbage_buf.len().max(filedata_len_plus_padding),
-            0x00,
-        );
-        common_garbage_buf[0..filedata.len()].copy_from_slice(&filedata);
+        let (length, hashcode, version, flags) =
+            read_file_metadata(vpath, &filedata, i as u32, endian)?;
+
+        data_writer.set_split_size(f.split_size);
+        data_writer.set_alignment(f.alignment);
+        if let Some(filelist_num) = f.filelist {
+            data_writer.start_data_file(filelist_num)?;
+        }
 
+        let (location, difference) = data_writer.write_file(&filedata)?;
+        let unaligned_pos = location.addr as usize + filedata.len();
         println!(
             "{} {} remaining space: {:#x} - {:#x} = {:#x}",
-            i, vpath, unaligned_pos, aligned_pos, difference
+            i,
+            vpath,
+            unaligned_pos,
+            unaligned_pos + difference,
+            difference
         );
 
-        if difference > 0 {
-            // swy: fill out the padding with the correct garbage at that offset,
-            //      this should make the diff engines' life easier. and we should
-            //      get a byte-by-byte perfect reconstruction for pristine files,
-            //      (as long as they get stored in the same order with the help of a handy .scr spec file)
-            f_data.write_all(&common_garbage_buf[filedata.len()..filedata_len_plus_padding])?;
+        if let Some(index) = existing_index {
+            files[index].1.locations.push(location);
+        } else {
+            file_indices.insert(vpath.to_lowercase(), files.len());
+            files.push((
+                vpath.to_lowercase(),
+                UXFileInfo {
+                    locations: vec![location],
+                    length,
+                    hashcode,
+                    version,
+                    flags,
+                    unk0: 0,
+                },
+            ));
         }
     }
 
-    let filelist = EXFileListHeader5 {
-        version,
-        filesize: 0,
-        build_type: 1,
-        num_filelists: filelist_num as u16,
-        filename_list_offset: 0,
-        fileinfo: files.iter().map(|(_, v)| v.clone()).collect(),
+    let num_files = files.len();
+    let filelist = UXFileList {
+        num_filelists: Some(data_writer.filelist_num() as u16),
+        build_type: Some(1),
+        endian,
+        files,
     };
 
     let mut f_info = File::create(fp_info)?;
-    f_info.write_type(&filelist, endian)?;
-    let filename_offset = f_info.stream_position()?;
-
-    let filename_table_size = files.len() * 4;
-    let filename_data_offset = filename_offset + filename_table_size as u64;
-    let mut offset = filename_data_offset;
-    for (i, (v, _)) in files.iter().enumerate() {
-        let ptr_offset = filename_offset + i as u64 * 4;
-        f_info.write_type(&((offset - ptr_offset) as u32), endian)?;
-        offset += v.len() as u64 + 1;
-    }
+    filelist.write(&mut f_info, version)?;
 
-    f_info.seek(std::io::SeekFrom::Start(filename_data_offset))?;
-
-    for (i, (v, _)) in files.iter().enumerate() {
-        let mut path_buf = v.to_lowercase().as_bytes().to_vec();
-        path_buf.push(0);
+    println!(
+        "Successfully packed {} files into {} data files",
+        num_files,
+        data_writer.filelist_num() + 1
+    );
 
-        if version >= 7 {
-            path::scramble_filename_v7(i as u32, &mut path_buf);
-        }
+    Ok(())
+}
 
-        f_info.write_all(&path_buf)?;
-    }
+/// Returns the length, hashcode, version and flags to store in the filelist for a file
+pub(crate) fn read_file_metadata(
+    vpath: &str,
+    filedata: &[u8],
+    index: u32,
+    endian: Endian,
+) -> anyhow::Result<(u32, u32, u32, u32)> {
+    let mut infile = Cursor::new(filedata);
+    let mut length = filedata.len() as u32;
+
+    let (hashcode, version, flags) = if vpath.to_ascii_lowercase().ends_with(".edb") {
+        // Use base filesize instead of full filesize
+        infile.seek(std::io::SeekFrom::Start(0x18))?;
+        length = infile.read_type(endian)?;
+
+        infile.seek(std::io::SeekFrom::Start(4))?;
+        (
+            infile.read_type(endian)?,
+            infile.read_type(endian)?,
+            infile.read_type(endian)?,
+        )
+    } else if vpath.to_ascii_lowercase().ends_with(".sfx") {
+        infile.seek(std::io::SeekFrom::Start(4))?;
+        (
+            infile.read_le::<u32>()? | 0x21000000,
+            infile.read_type::<u8>(endian)? as u32,
+            0,
+        )
+    } else {
+        (0x81000000 | index, 0, 0)
+    };
 
-    // Pad the file to 32 bytes
-    f_info.seek(std::io::SeekFrom::End(0))?;
-    let unaligned_size = f_info.stream_position()?;
-    if unaligned_size & 0x1f != 0 {
-        let remainder = 32 - (unaligned_size % 32);
-        f_info.write_all(&vec![0u8; remainder as usize])?;
-    }
+    Ok((length, hashcode, version, flags))
+}
 
-    let file_size = f_info.stream_position()?;
+/// Expands the entries of an .scr file into a list of files to pack, in order
+fn resolve_scr_files(
+    scr: &ScrFile,
+    disk_files: &[(String, String)],
+    drive_letter: char,
+    split_size: u32,
+) -> anyhow::Result<Vec<PackFile>> {
+    let mut split_size = scr.split_size()?.unwrap_or(split_size);
+    let mut alignment = scr.alignment()?.unwrap_or(0x800);
+    let mut next_filelist = None;
+
+    let disk_file_indices: HashMap<String, usize> = disk_files
+        .iter()
+        .enumerate()
+        .map(|(i, (fpath, _))| (fpath.to_lowercase(), i))
+        .collect();
+
+    let mut files = vec![];
+    for e in &scr.entries {
+        let (pattern, options) = match e {
+            ScrEntry::File { pattern, options } => (pattern, options),
+            ScrEntry::Filelist(n) => {
+                next_filelist = Some(*n);
+                continue;
+            }
+            ScrEntry::SplitSize(s) => {
+                split_size = *s;
+                continue;
+            }
+            ScrEntry::Alignment(a) => {
+                alignment = *a;
+                continue;
+            }
+        };
 
-    f_info.seek(std::io::SeekFrom::Start(4))?;
-    f_info.write_type(&(file_size as u32), endian)?;
+        // Paths are relative to the input folder, the drive letter is ignored
+        let relative_pattern = &pattern[3..];
+        let mut matches: Vec<&(String, String)> = if has_wildcards(relative_pattern) {
+            disk_files
+                .iter()
+                .filter(|(fpath, _)| matches_pattern(relative_pattern, fpath))
+                .collect()
+        } else {
+            disk_file_indices
+                .get(&relative_pattern.to_lowercase().replace('/', "\\"))
+                .map(|&i| &disk_files[i])
+                .into_iter()
+                .collect()
+        };
 
-    f_info.seek(std::io::SeekFrom::Start(0x10))?;
-    f_info.write_type(&(filename_offset as u32 - 0x10), endian)?;
+        if matches.is_empty() {
+            // TODO: log crate when?
+            println!("Warning: SCR path {pattern} yielded no results");
+            continue;
+        }
 
-    println!(
-        "Successfully packed {} files into {} data files",
-        files.len(),
-        filelist_num + 1
-    );
+        // Wildcard matches are sorted folder by folder
+        matches.sort_by(|(a, _), (b, _)| a.split('\\').cmp(b.split('\\')));
+
+        for (fpath, rpath) in matches {
+            files.push(PackFile {
+                vpath: format!("{drive_letter}:\\{fpath}"),
+                rpath: rpath.clone(),
+                alignment: options.alignment.unwrap_or(alignment),
+                split_size,
+                filelist: options.filelist.or(next_filelist.take()),
+            });
+        }
+    }
 
-    Ok(())
+    Ok(files)
 }
 
-// TODO: Proper parser for scr files
-fn parse_scr_filelist<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<String>> {
-    let mut result = vec![];
-    let mut filebuf = String::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scr_roundtrip() {
+        let base = std::env::temp_dir().join(format!("eurochef-scr-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+
+        // Absolute input paths aren't supported on all platforms yet
+        let base = pathdiff::diff_paths(&base, std::env::current_dir().unwrap()).unwrap();
+        let path = |p: &str| base.join(p).to_string_lossy().to_string();
+
+        for (i, name) in ["a.txt", "sub/b.bin", "sub/deeper/c.bin", "d.txt", "e.bin"]
+            .iter()
+            .enumerate()
+        {
+            let file = base.join("input").join(name);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            let data: Vec<u8> = (0..(i + 1) * 0x900).map(|v| (v * (i + 3)) as u8).collect();
+            std::fs::write(file, data).unwrap();
+        }
 
-    let mut f = File::open(path).expect("Failed to open SCR file");
-    f.read_to_string(&mut filebuf)?;
+        for dir in ["first", "extracted", "second"] {
+            std::fs::create_dir_all(base.join(dir)).unwrap();
+        }
 
-    let mut in_filesection = false;
-    for l in filebuf.lines() {
-        let line = l.trim();
+        execute_command(
+            path("input"),
+            path("first/Filelist"),
+            'x',
+            7,
+            PlatformArg::Pc,
+            0x2000,
+            None,
+        )
+        .unwrap();
 
-        if in_filesection && line.len() > 3 {
-            result.push(line.to_owned())
+        crate::filelist::extract::execute_command(
+            path("first/Filelist.bin"),
+            path("extracted/files"),
+            true,
+        )
+        .unwrap();
+
+        // The split size is ignored in favor of the data file boundaries in the .scr
+        execute_command(
+            path("extracted/files"),
+            path("second/Filelist"),
+            'x',
+            7,
+            PlatformArg::Pc,
+            0x80000000,
+            Some(path("extracted/FileList.scr")),
+        )
+        .unwrap();
+
+        let mut first: Vec<_> = std::fs::read_dir(base.join("first"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        first.sort();
+        assert!(first.len() > 2, "Expected multiple data files");
+
+        for name in first {
+            assert_eq!(
+                std::fs::read(base.join("first").join(&name)).unwrap(),
+                std::fs::read(base.join("second").join(&name)).unwrap(),
+                "{name:?} differs"
+            );
         }
 
-        if line == "[FileList]" {
-            in_filesection = true;
-        } else if line.starts_with('[') {
-            in_filesection = false;
-        }
+        std::fs::remove_dir_all(&base).unwrap();
     }
-
-    Ok(result)
 }
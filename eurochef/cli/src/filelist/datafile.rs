@@ -0,0 +1,165 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+};
+
+use anyhow::Context;
+use eurochef_filelist::unified::UXFileLoc;
+
+/// Writes file data into data files (.000, .001, ... or .DAT), starting a new data file once `split_size` is reached
+pub struct DataFileWriter {
+    output_file: String,
+    split_size: u32,
+    alignment: u32,
+    single_data_file: bool,
+
+    file: File,
+    filelist_num: u32,
+    common_garbage_buf: Vec<u8>,
+}
+
+impl DataFileWriter {
+    /// `output_file` is the path of the filelist without extension
+    pub fn new(output_file: &str, split_size: u32, single_data_file: bool) -> anyhow::Result<Self> {
+        let fp_data = if single_data_file {
+            format!("{output_file}.DAT")
+        } else {
+            format!("{output_file}.000")
+        };
+
+        Ok(Self {
+            output_file: output_file.to_string(),
+            split_size,
+            alignment: 0x800,
+            single_data_file,
+            file: File::create(fp_data).context("Failed to create output file")?,
+            filelist_num: 0,
+            common_garbage_buf: vec![],
+        })
+    }
+
+    /// Continues writing at the end of an existing data file, `filelist_num` is `None` for a single .DAT file
+    pub fn append(
+        output_file: &str,
+        filelist_num: Option<u32>,
+        split_size: u32,
+    ) -> anyhow::Result<Self> {
+        let fp_data = match filelist_num {
+            Some(num) => format!("{output_file}.{num:03}"),
+            None => format!("{output_file}.DAT"),
+        };
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&fp_data)
+            .with_context(|| format!("Failed to open data file {fp_data}"))?;
+
+        // Make sure new data starts on a 2048 byte boundary
+        let end = file.seek(SeekFrom::End(0))?;
+        let aligned_end = (end + 0x7ff) & !0x7ff;
+        file.write_all(&vec![0u8; (aligned_end - end) as usize])?;
+
+        Ok(Self {
+            output_file: output_file.to_string(),
+            split_size,
+            alignment: 0x800,
+            single_data_file: filelist_num.is_none(),
+            file,
+            filelist_num: filelist_num.unwrap_or(0),
+            common_garbage_buf: vec![],
+        })
+    }
+
+    /// Index of the last data file
+    pub fn filelist_num(&self) -> u32 {
+        self.filelist_num
+    }
+
+    pub fn set_split_size(&mut self, split_size: u32) {
+        self.split_size = split_size;
+    }
+
+    /// Alignment of the data following the next file, 2048 bytes by default
+    pub fn set_alignment(&mut self, alignment: u32) {
+        self.alignment = alignment.max(1);
+    }
+
+    /// Continues in the given data file, creating any data files in between
+    pub fn start_data_file(&mut self, filelist_num: u32) -> anyhow::Result<()> {
+        if self.single_data_file {
+            anyhow::bail!("This filelist only has a single data file");
+        }
+
+        if filelist_num < self.filelist_num {
+            anyhow::bail!(
+                "Can't go back to data file {filelist_num}, already writing data file {}",
+                self.filelist_num
+            );
+        }
+
+        while self.filelist_num < filelist_num {
+            self.next_data_file()?;
+        }
+
+        Ok(())
+    }
+
+    fn next_data_file(&mut self) -> anyhow::Result<()> {
+        self.filelist_num += 1;
+
+        let fp_data = format!("{}.{:03}", self.output_file, self.filelist_num);
+        self.file = File::create(&fp_data)?;
+
+        Ok(())
+    }
+
+    /// Writes the data padded to the current alignment, returns the location of the data and the amount of padding
+    pub fn write_file(&mut self, filedata: &[u8]) -> anyhow::Result<(UXFileLoc, usize)> {
+        if !self.single_data_file
+            && self.file.stream_position()? as usize + filedata.len() > self.split_size as usize
+        {
+            self.next_data_file()?;
+        }
+
+        let location = UXFileLoc {
+            addr: self.file.stream_position()? as u32,
+            filelist_num: Some(self.filelist_num),
+            unk0: 0,
+        };
+
+        // swy: write the actual file contents
+        self.file.write_all(filedata)?;
+
+        // Pad next data to the alignment (2048 bytes by default)
+        let unaligned_pos = self.file.stream_position()?;
+        let alignment = self.alignment as u64;
+        let aligned_pos = (unaligned_pos + alignment - 1) / alignment * alignment;
+        let difference: usize = (aligned_pos - unaligned_pos) as usize;
+
+        // swy: this funky buffer holds the cumulative overwritten contents of everything that came before;
+        //      we need to use this as a sort of emulation layer for matching how the original XUtil memcpy()'ed not only
+        //      until the end of the current file, but also extending it to any garbage data that may lay beyond the limit, what's there?
+        //      probably the data at that offset of any previous file big enough to reach there, otherwise we'll use zeroes
+
+        //      so here we're always making the buffer big enough to cover the total space that we need, and then pasting the file to cover
+        //      from the start, until its maximum size, anything that remains keeps the previous data, because that's what we like ¯\_(ツ)_/¯
+        let filedata_len_plus_padding = filedata.len() + difference;
+
+        self.common_garbage_buf.resize(
+            self.common_garbage_buf.len().max(filedata_len_plus_padding),
+            0x00,
+        );
+        self.common_garbage_buf[0..filedata.len()].copy_from_slice(filedata);
+
+        if difference > 0 {
+            // swy: fill out the padding with the correct garbage at that offset,
+            //      this should make the diff engines' life easier. and we should
+            //      get a byte-by-byte perfect reconstruction for pristine files,
+            //      (as long as they get stored in the same order with the help of a handy .scr spec file)
+            self.file
+                .write_all(&self.common_garbage_buf[filedata.len()..filedata_len_plus_padding])?;
+        }
+
+        Ok((location, difference))
+    }
+}
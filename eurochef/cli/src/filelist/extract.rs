@@ -6,10 +6,14 @@ use std::{
 
 use anyhow::Context;
 use eurochef_edb::{
-    binrw::BinReaderExt,
+    binrw::{BinReaderExt, Endian},
     versions::{transform_windows_path, Platform},
 };
-use eurochef_filelist::UXFileList;
+use eurochef_filelist::{
+    scr::{ScrEntry, ScrFile},
+    unified::UXFileLoc,
+    UXFileList,
+};
 use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
 
 use crate::filelist::TICK_STRINGS;
@@ -75,16 +79,6 @@ pub fn execute_command(
         None // swy: the user explicitly asked not to write the .scr file at all
     };
 
-    if let Some(f) = scr_file.as_mut() {
-        writeln!(
-            f,
-            "[FileInfomation]
-
-[FileList]\n"
-        )
-        .expect("Failed to write scr file header");
-    }
-
     let file_base = &filename[..filename.len() - 3];
     let mut data_files = vec![];
     if let Some(num_filelists) = filelist.num_filelists {
@@ -113,10 +107,6 @@ pub fn execute_command(
         let filename_fixed = filename.replace('\\', "/");
         let fpath = Path::new(&filename_fixed);
 
-        if let Some(ref mut f) = scr_file {
-            writeln!(f, "{}", filename).expect("Failed to write file name to .scr");
-        };
-
         if fpath.to_string_lossy().is_empty() {
             println!(
                 "Skipping file {} with empty path (hashcode {:08x})",
@@ -125,28 +115,30 @@ pub fn execute_command(
             continue;
         }
 
-        let df = &mut data_files[info.filelist_num.unwrap_or(0) as usize];
-
-        df.seek(std::io::SeekFrom::Start(info.addr as u64))?;
-
-        let magic: u32 = df
-            .read_type(filelist.endian)
-            .expect("Failed to read file header");
-
-        let mut filesize = info.length;
+        let Some(first_location) = info.locations.first() else {
+            println!("Skipping file {filename} without any locations");
+            continue;
+        };
 
-        if magic == 0x47454F4D {
-            df.seek(std::io::SeekFrom::Current(0x10))?;
-            filesize = df
-                .read_type(filelist.endian)
-                .expect("Failed to read GeoFile size");
+        let data = read_file_data(
+            &mut data_files,
+            first_location,
+            info.length,
+            filelist.endian,
+        )?;
+
+        // Some discs store extra copies of a file across data files to cut down on seeking, make sure they're actually the same
+        for (l, location) in info.locations.iter().enumerate().skip(1) {
+            let copy = read_file_data(&mut data_files, location, info.length, filelist.endian)?;
+            if copy != data {
+                println!(
+                    "Warning: copy {l} of {filename} (data file {}, address {:#x}) doesn't match the first copy",
+                    location.filelist_num.unwrap_or(0),
+                    location.addr
+                );
+            }
         }
 
-        df.seek(std::io::SeekFrom::Start(info.addr as u64))?;
-
-        let mut data = vec![0u8; filesize as usize];
-        df.read_exact(&mut data)?;
-
         let fpath_noprefix = Path::new(&output_folder).join(&fpath.to_str().unwrap()[3..]);
         std::fs::create_dir_all(fpath_noprefix.parent().unwrap())?;
         File::create(&fpath_noprefix)
@@ -154,7 +146,66 @@ pub fn execute_command(
             .write_all(&data)?;
     }
 
+    if let Some(ref mut f) = scr_file {
+        write!(f, "{}", create_scr_file(&filelist)).expect("Failed to write .scr file");
+    }
+
     println!("Successfully extracted {} files", filelist.files.len());
 
     Ok(())
 }
+
+fn read_file_data(
+    data_files: &mut [File],
+    location: &UXFileLoc,
+    length: u32,
+    endian: Endian,
+) -> anyhow::Result<Vec<u8>> {
+    let df = &mut data_files[location.filelist_num.unwrap_or(0) as usize];
+
+    df.seek(std::io::SeekFrom::Start(location.addr as u64))?;
+
+    let magic: u32 = df.read_type(endian).expect("Failed to read file header");
+
+    let mut filesize = length;
+
+    if magic == 0x47454F4D {
+        df.seek(std::io::SeekFrom::Current(0x10))?;
+        filesize = df.read_type(endian).expect("Failed to read GeoFile size");
+    }
+
+    df.seek(std::io::SeekFrom::Start(location.addr as u64))?;
+
+    let mut data = vec![0u8; filesize as usize];
+    df.read_exact(&mut data)?;
+
+    Ok(data)
+}
+
+/// Lists all files in the order they're stored in the data files. Repeated paths are extra copies of a file
+fn create_scr_file(filelist: &UXFileList) -> ScrFile {
+    let mut locations: Vec<_> = filelist
+        .files
+        .iter()
+        .filter(|(filename, _)| !filename.is_empty())
+        .flat_map(|(filename, info)| info.locations.iter().map(move |l| (*l, filename)))
+        .collect();
+    locations.sort_by_key(|(l, _)| (l.filelist_num, l.addr));
+
+    let mut scr = ScrFile::default();
+    let mut current_filelist = 0;
+    for (l, filename) in locations {
+        let filelist_num = l.filelist_num.unwrap_or(0);
+        if filelist_num != current_filelist {
+            scr.entries.push(ScrEntry::Filelist(filelist_num));
+            current_filelist = filelist_num;
+        }
+
+        scr.entries.push(ScrEntry::File {
+            pattern: filename.clone(),
+            options: Default::default(),
+        });
+    }
+
+    scr
+}
@@ -1,4 +1,27 @@
+use std::fs::File;
+
+use anyhow::Context;
+use eurochef_edb::binrw::{BinReaderExt, Endian};
+
 const TICK_STRINGS: &str = "⠁⠂⠄⡀⢀⠠⠐⠈";
 
+pub mod convert;
 pub mod create;
+pub mod datafile;
 pub mod extract;
+pub mod patch;
+pub mod verify;
+
+/// Reads the version of a filelist header (.bin)
+fn read_filelist_version(filename: &str, endian: Endian) -> anyhow::Result<u32> {
+    let mut file = File::open(filename).context("Failed to open filelist header")?;
+    Ok(file.read_type(endian)?)
+}
+
+/// Paths of all data files belonging to a filelist
+fn data_file_paths(file_base: &str, num_filelists: Option<u16>) -> Vec<String> {
+    match num_filelists {
+        Some(num) => (0..=num).map(|i| format!("{file_base}.{i:03}")).collect(),
+        None => vec![format!("{file_base}.DAT")],
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+use anyhow::Context;
+use eurochef_filelist::{unified::UXFileInfo, UXFileList, Vfs};
+use walkdir::WalkDir;
+
+use crate::filelist::{
+    convert::repack, create::read_file_metadata, data_file_paths, datafile::DataFileWriter,
+    read_filelist_version,
+};
+
+pub fn execute_command(
+    filename: String,
+    input_folder: Option<String>,
+    split_size: u32,
+    compact: bool,
+) -> anyhow::Result<()> {
+    if input_folder.is_none() && !compact {
+        anyhow::bail!("Nothing to do, specify an input folder and/or --compact");
+    }
+
+    let mut vfs = Vfs::open(&filename)?;
+    let endian = vfs.filelist.endian;
+    let version = read_filelist_version(&filename, endian)?;
+    if !UXFileList::is_version_supported(version) {
+        anyhow::bail!("Filelist version {version} is not supported for packing right now")
+    }
+
+    let file_base = Path::new(&filename)
+        .with_extension("")
+        .to_string_lossy()
+        .to_string();
+
+    if let Some(input_folder) = input_folder {
+        let (replaced, added) = patch(&mut vfs, &input_folder, &file_base, split_size)?;
+
+        if replaced + added == 0 {
+            println!("No changed files found");
+        } else {
+            // Write the new header next to the original, so a failed write doesn't destroy the filelist
+            let temp_filename = format!("{filename}.tmp");
+            let result = File::create(&temp_filename)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut f_info| vfs.filelist.write(&mut f_info, version));
+            if let Err(e) = result {
+                let _ = std::fs::remove_file(&temp_filename);
+                return Err(e.context(format!("Failed to write {temp_filename}")));
+            }
+            std::fs::rename(&temp_filename, &filename)
+                .with_context(|| format!("Failed to move {temp_filename} to {filename}"))?;
+
+            println!("Successfully replaced {replaced} and added {added} files");
+        }
+    }
+
+    if compact {
+        compact_data_files(&filename, &file_base, version, split_size)?;
+    }
+
+    Ok(())
+}
+
+/// Appends new and changed files from `input_folder` to the data files, returns the amount of replaced and added files
+fn patch(
+    vfs: &mut Vfs,
+    input_folder: &str,
+    file_base: &str,
+    split_size: u32,
+) -> anyhow::Result<(usize, usize)> {
+    let endian = vfs.filelist.endian;
+    let single_data_file = vfs.filelist.num_filelists.is_none();
+
+    // Files are matched with the input folder the way they are extracted, without the drive letter
+    let drive_letter = vfs
+        .filelist
+        .files
+        .iter()
+        .find_map(|(p, _)| p.split_once(":\\").map(|(d, _)| d.to_string()))
+        .unwrap_or("x".to_string());
+    let mut file_indices: HashMap<String, usize> = vfs
+        .filelist
+        .files
+        .iter()
+        .enumerate()
+        .map(|(i, (p, _))| (p.to_lowercase(), i))
+        .collect();
+
+    let mut data_writer: Option<DataFileWriter> = None;
+    let (mut replaced, mut added) = (0, 0);
+    for e in WalkDir::new(input_folder).sort_by_file_name() {
+        let e = e?;
+        if !e.file_type().is_file() {
+            continue;
+        }
+
+        let fpath = pathdiff::diff_paths(e.path(), input_folder)
+            .unwrap()
+            .to_string_lossy()
+            .replace('/', "\\");
+        let vpath = format!("{drive_letter}:\\{fpath}").to_lowercase();
+
+        let mut filedata = vec![];
+        File::open(e.path())?.read_to_end(&mut filedata)?;
+
+        let existing_index = file_indices.get(&vpath).copied();
+        if let Some(index) = existing_index {
+            let mut current = vec![];
+            vfs.open_info(&vfs.filelist.files[index].1)?
+                .read_to_end(&mut current)?;
+
+            if current == filedata {
+                continue;
+            }
+        }
+
+        if data_writer.is_none() {
+            data_writer = Some(DataFileWriter::append(
+                file_base,
+                vfs.filelist.num_filelists.map(|n| n as u32),
+                split_size,
+            )?);
+        }
+        let data_writer = data_writer.as_mut().unwrap();
+
+        let index = existing_index.unwrap_or(vfs.filelist.files.len());
+        let (length, hashcode, file_version, flags) =
+            read_file_metadata(&vpath, &filedata, index as u32, endian)?;
+        let (location, _) = data_writer.write_file(&filedata)?;
+
+        if let Some(index) = existing_index {
+            println!("Replacing {vpath}");
+            let info = &mut vfs.filelist.files[index].1;
+
+            // Only EDB and SFX files carry their own hashcode, keep the existing one for anything else
+            if vpath.ends_with(".edb") || vpath.ends_with(".sfx") {
+                info.hashcode = hashcode;
+                info.version = file_version;
+                info.flags = flags;
+            }
+            info.length = length;
+            info.locations = vec![location];
+            replaced += 1;
+        } else {
+            println!("Adding {vpath}");
+            file_indices.insert(vpath.clone(), vfs.filelist.files.len());
+            vfs.filelist.files.push((
+                vpath,
+                UXFileInfo {
+                    locations: vec![location],
+                    length,
+                    hashcode,
+                    version: file_version,
+                    flags,
+                    unk0: 0,
+                },
+            ));
+            added += 1;
+        }
+
+        if !single_data_file {
+            vfs.filelist.num_filelists = Some(data_writer.filelist_num() as u16);
+        }
+    }
+
+    Ok((replaced, added))
+}
+
+/// Rewrites the data files without the space used by replaced files
+fn compact_data_files(
+    filename: &str,
+    file_base: &str,
+    version: u32,
+    split_size: u32,
+) -> anyhow::Result<()> {
+    let vfs = Vfs::open(filename)?;
+    let old_data_files = data_file_paths(file_base, vfs.filelist.num_filelists);
+    let old_size: u64 = old_data_files
+        .iter()
+        .map(|p| std::fs::metadata(p).map(|m| m.len()))
+        .sum::<std::io::Result<u64>>()?;
+
+    println!("Compacting {filename}");
+    let temp_base = format!("{file_base}_compact");
+    let (_, num_data_files) = repack(&vfs, &temp_base, version, vfs.filelist.endian, split_size)?;
+
+    let num_filelists = (version != 4).then_some(num_data_files as u16 - 1);
+    let new_data_files = data_file_paths(file_base, num_filelists);
+    let temp_data_files = data_file_paths(&temp_base, num_filelists);
+
+    // Only replace the original files once everything has been written
+    for p in &old_data_files {
+        std::fs::remove_file(p).with_context(|| format!("Failed to remove {p}"))?;
+    }
+    for (temp, new) in temp_data_files.iter().zip(&new_data_files) {
+        std::fs::rename(temp, new).with_context(|| format!("Failed to move {temp} to {new}"))?;
+    }
+    std::fs::rename(format!("{temp_base}.bin"), filename)?;
+
+    let new_size: u64 = new_data_files
+        .iter()
+        .map(|p| std::fs::metadata(p).map(|m| m.len()))
+        .sum::<std::io::Result<u64>>()?;
+    println!(
+        "Successfully compacted {filename}, reclaimed {} bytes",
+        old_size.saturating_sub(new_size)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use std::{
+    fs::File,
+    io::{BufReader, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::Context;
+use eurochef_edb::binrw::{BinReaderExt, Endian};
+use eurochef_filelist::{unified::UXFileInfo, UXFileList};
+
+use crate::filelist::data_file_paths;
+
+/// Fields of a GEOM (.edb) header that are mirrored in the filelist
+struct GeomHeader {
+    hashcode: u32,
+    version: u32,
+    file_size: u32,
+    base_file_size: u32,
+}
+
+pub fn execute_command(filename: String) -> anyhow::Result<()> {
+    println!("Verifying {filename}");
+    let mut file = File::open(&filename).context("Failed to open filelist header")?;
+    let mut reader = BufReader::new(&mut file);
+    let filelist = UXFileList::read(&mut reader)?;
+
+    let file_base = Path::new(&filename)
+        .with_extension("")
+        .to_string_lossy()
+        .to_string();
+
+    let mut problems = vec![];
+    let mut data_files = vec![];
+    for p in data_file_paths(&file_base, filelist.num_filelists) {
+        match File::open(&p).and_then(|f| Ok((f.metadata()?.len(), f))) {
+            Ok((size, f)) => data_files.push(Some((BufReader::new(f), size))),
+            Err(e) => {
+                problems.push(format!("Failed to open data file {p}: {e}"));
+                data_files.push(None);
+            }
+        }
+    }
+
+    // Data file index, start, end, file index
+    let mut ranges: Vec<(usize, u64, u64, usize)> = vec![];
+    for (i, (path, info)) in filelist.files.iter().enumerate() {
+        if info.locations.is_empty() {
+            problems.push(format!("{path} doesn't have any locations"));
+        }
+
+        for location in &info.locations {
+            let data_file_index = location.filelist_num.unwrap_or(0) as usize;
+            let Some(Some((data_file, size))) = data_files.get_mut(data_file_index) else {
+                problems.push(format!(
+                    "{path} is stored in data file {data_file_index}, which doesn't exist"
+                ));
+                continue;
+            };
+
+            let start = location.addr as u64;
+            let mut end = start + info.length as u64;
+            if end <= *size {
+                if let Some(header) = read_geom_header(data_file, start, filelist.endian)? {
+                    end = start + header.file_size as u64;
+                    problems.extend(compare_geom_header(path, info, &header));
+                }
+            }
+
+            if end > *size {
+                problems.push(format!(
+                    "{path} ({start:#x}..{end:#x}) extends past the end of data file {data_file_index} ({size:#x} bytes)"
+                ));
+            }
+
+            ranges.push((data_file_index, start, end, i));
+        }
+    }
+
+    ranges.sort();
+    let mut previous: Option<(usize, u64, usize)> = None;
+    for (data_file_index, start, end, i) in ranges {
+        if let Some((prev_data_file, prev_end, prev_i)) = previous {
+            if prev_data_file == data_file_index && start < prev_end {
+                problems.push(format!(
+                    "{} overlaps with {} in data file {data_file_index} at {start:#x}",
+                    filelist.files[i].0, filelist.files[prev_i].0
+                ));
+            }
+        }
+
+        // Keep the range that extends the furthest, so it's checked against all following ranges
+        match previous {
+            Some((prev_data_file, prev_end, _))
+                if prev_data_file == data_file_index && prev_end >= end => {}
+            _ => previous = Some((data_file_index, end, i)),
+        }
+    }
+
+    for p in &problems {
+        println!("{p}");
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("Found {} problems in {filename}", problems.len());
+    }
+
+    println!("Verified {} files, no problems found", filelist.files.len());
+
+    Ok(())
+}
+
+fn read_geom_header(
+    reader: &mut BufReader<File>,
+    start: u64,
+    endian: Endian,
+) -> anyhow::Result<Option<GeomHeader>> {
+    reader.seek(SeekFrom::Start(start))?;
+    let Ok(magic) = reader.read_type::<u32>(endian) else {
+        return Ok(None);
+    };
+
+    if magic != 0x47454F4D {
+        return Ok(None);
+    }
+
+    let hashcode = reader.read_type(endian)?;
+    let version = reader.read_type(endian)?;
+    reader.seek(SeekFrom::Start(start + 0x14))?;
+
+    Ok(Some(GeomHeader {
+        hashcode,
+        version,
+        file_size: reader.read_type(endian)?,
+        base_file_size: reader.read_type(endian)?,
+    }))
+}
+
+fn compare_geom_header(path: &str, info: &UXFileInfo, header: &GeomHeader) -> Vec<String> {
+    let mut problems = vec![];
+    if header.hashcode != info.hashcode {
+        problems.push(format!(
+            "{path} has hashcode {:08x} in the filelist, but {:08x} in its header",
+            info.hashcode, header.hashcode
+        ));
+    }
+
+    if header.version != info.version {
+        problems.push(format!(
+            "{path} has version {} in the filelist, but {} in its header",
+            info.version, header.version
+        ));
+    }
+
+    if header.base_file_size != info.length {
+        problems.push(format!(
+            "{path} has length {:#x} in the filelist, but {:#x} in its header",
+            info.length, header.base_file_size
+        ));
+    }
+
+    problems
+}
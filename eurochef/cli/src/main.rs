@@ -3,6 +3,7 @@ extern crate tracing;
 
 mod edb;
 mod filelist;
+mod sfx;
 
 use anyhow::Context;
 use clap::{Parser, Subcommand};
@@ -59,6 +60,11 @@ enum Command {
         #[command(subcommand)]
         subcommand: EdbCommand,
     },
+    /// Commands for working with sound banks
+    Sfx {
+        #[command(subcommand)]
+        subcommand: SfxCommand,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -89,7 +95,12 @@ enum EdbCommand {
         filename: String,
 
         /// Output folder for spreadsheet (default: "./spreadsheets/{filename}/")
+        /// When importing, this is the patched .edb file instead (default: "./patched/{filename}")
         output_folder: Option<String>,
+
+        /// Replace the text in text spreadsheets with an extracted CSV file, or a folder of them
+        #[arg(short, long)]
+        import: Option<String>,
     },
     /// Extract maps
     Maps {
@@ -113,20 +124,37 @@ enum EdbCommand {
         filename: String,
 
         /// Output folder for textures (default: "./textures/{filename}/")
+        /// When importing, this is the patched .edb file instead (default: "./patched/{filename}")
         output_folder: Option<String>,
 
         /// Override for platform detection
         #[arg(value_enum, short, long, ignore_case = true)]
         platform: Option<PlatformArg>,
 
-        /// Output file format to use (supported: tga, png, qoi)
+        /// Replace textures with images from this folder, named the same way as extracted textures
+        #[arg(short, long)]
+        import: Option<String>,
+
+        /// Resize imported images that don't match the original texture dimensions (volume textures are resized per slice)
+        #[arg(long)]
+        resize: bool,
+
+        /// Output file format to use (supported: tga, png, qoi, dds, ktx2)
         /// Selecting PNG will export animated textures as APNGs (unless disabled)
+        /// DDS and KTX2 files contain every mip level, which are kept when importing them.
+        /// DXT/BC compressed textures are exported to DDS and KTX2 without recompressing them
         #[arg(short, long, default_value("tga"))]
         format: String,
 
         /// Don't export APNGs when using PNG as output format
         #[arg(long)]
         no_apngs: bool,
+
+        /// How volume textures are exported to regular image formats (supported: slices, atlas)
+        /// Slices are exported as separate images, an atlas stacks all slices vertically
+        /// DDS and KTX2 always export volume textures as-is
+        #[arg(long, default_value("slices"))]
+        volume_layout: String,
     },
     /// Extract animations (!!MAJOR WIP!!)
     Animations {
@@ -140,6 +168,39 @@ enum EdbCommand {
         /// Override for platform detection
         #[arg(value_enum, short, long, ignore_case = true)]
         platform: Option<PlatformArg>,
+
+        /// Bind meshes to the skeleton by attaching every vertex to the closest bone.
+        /// This is only an approximation, the real vertex weights can't be read yet
+        #[arg(long)]
+        approximate_weights: bool,
+    },
+    /// Convert a file to another platform. Textures are re-encoded (not to GameCube/Wii yet), maps and spreadsheets can only be converted between platforms with the same byte order. Other sections are not supported yet
+    Convert {
+        /// .edb file to read
+        filename: String,
+
+        /// Output file (default: "./converted/{platform}/{filename}")
+        output: Option<String>,
+
+        /// Platform to convert to
+        #[arg(value_enum, long, ignore_case = true)]
+        to: PlatformArg,
+
+        /// Override for platform detection
+        #[arg(value_enum, short, long, ignore_case = true)]
+        platform: Option<PlatformArg>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum SfxCommand {
+    /// Extract samples from a sound bank as WAV files, named after the sound effects using them
+    Extract {
+        /// .sfx file to read
+        filename: String,
+
+        /// Output folder for samples (default: "./sounds/{filename}/")
+        output_folder: Option<String>,
     },
 }
 
@@ -170,7 +231,7 @@ enum FilelistCommand {
         #[arg(long, short = 'l', default_value_t = 'x')]
         drive_letter: char,
 
-        /// Supported versions: 5, 6, 7
+        /// Supported versions: 4, 5, 6, 7, 9, 10
         #[arg(long, short, default_value_t = 7)]
         version: u32,
 
@@ -181,10 +242,51 @@ enum FilelistCommand {
         #[arg(long, short = 'z', default_value_t = 0x80000000, value_parser = maybe_hex::<u32>)]
         split_size: u32,
 
-        /// .scr file to read options from (currently doesnt support wildcards)
+        /// .scr file listing the files to pack (wildcards supported), their order and data file layout
         #[arg(long, short)]
         scr_file: Option<String>,
     },
+    /// Convert a filelist to another version and/or platform, file contents are copied as-is
+    Convert {
+        /// .bin file to convert (don't use a .000 file)
+        filename: String,
+
+        /// Destination for the converted filelist, without filename extension (default: "./converted/v{version}/{filename}")
+        output_file: Option<String>,
+
+        /// Version to convert to (default: same as the input). Supported versions: 4, 5, 6, 7, 9, 10
+        #[arg(long, short)]
+        version: Option<u32>,
+
+        /// Platform to convert to, only affects the endianness of the filelist (default: same as the input)
+        #[arg(value_enum, short, long, ignore_case = true)]
+        platform: Option<PlatformArg>,
+
+        /// Maximum size per data file
+        #[arg(long, short = 'z', default_value_t = 0x80000000, value_parser = maybe_hex::<u32>)]
+        split_size: u32,
+    },
+    /// Add or replace files in a filelist by appending them to the data files, without repacking everything
+    Patch {
+        /// .bin file to patch (don't use a .000 file)
+        filename: String,
+
+        /// Folder with new and changed files, laid out like an extracted filelist
+        input_folder: Option<String>,
+
+        /// Maximum size per data file
+        #[arg(long, short = 'z', default_value_t = 0x80000000, value_parser = maybe_hex::<u32>)]
+        split_size: u32,
+
+        /// Rewrite the data files afterwards to reclaim the space used by replaced files
+        #[arg(long)]
+        compact: bool,
+    },
+    /// Check a filelist for files outside of their data files, overlapping files and mismatched EDB headers
+    Verify {
+        /// .bin file to verify (don't use a .000 file)
+        filename: String,
+    },
 }
 
 pub fn main() -> anyhow::Result<()> {
@@ -203,6 +305,7 @@ pub fn main() -> anyhow::Result<()> {
     match &args.cmd {
         Command::Filelist { subcommand } => handle_filelist(subcommand.clone()),
         Command::Edb { subcommand } => handle_edb(subcommand.clone()),
+        Command::Sfx { subcommand } => handle_sfx(subcommand.clone()),
     }
 }
 
@@ -230,19 +333,65 @@ fn handle_edb(cmd: EdbCommand) -> anyhow::Result<()> {
         EdbCommand::Spreadsheets {
             filename,
             output_folder,
-        } => edb::spreadsheets::execute_command(filename, output_folder),
+            import,
+        } => match import {
+            Some(input) => {
+                edb::spreadsheets::execute_import_command(filename, input, output_folder)
+            }
+            None => edb::spreadsheets::execute_command(filename, output_folder),
+        },
         EdbCommand::Textures {
             filename,
             platform,
             output_folder,
+            import,
+            resize,
             format,
             no_apngs,
-        } => edb::textures::execute_command(filename, platform, output_folder, format, no_apngs),
+            volume_layout,
+        } => match import {
+            Some(input_folder) => edb::textures::execute_import_command(
+                filename,
+                platform,
+                input_folder,
+                output_folder,
+                resize,
+            ),
+            None => edb::textures::execute_command(
+                filename,
+                platform,
+                output_folder,
+                format,
+                no_apngs,
+                volume_layout,
+            ),
+        },
         EdbCommand::Animations {
             filename,
             platform,
             output_folder,
-        } => edb::animations::execute_command(filename, platform, output_folder),
+            approximate_weights,
+        } => edb::animations::execute_command(
+            filename,
+            platform,
+            output_folder,
+            approximate_weights,
+        ),
+        EdbCommand::Convert {
+            filename,
+            output,
+            to,
+            platform,
+        } => edb::convert::execute_command(filename, platform, to, output),
+    }
+}
+
+fn handle_sfx(cmd: SfxCommand) -> anyhow::Result<()> {
+    match cmd {
+        SfxCommand::Extract {
+            filename,
+            output_folder,
+        } => sfx::extract::execute_command(filename, output_folder),
     }
 }
 
@@ -272,5 +421,25 @@ fn handle_filelist(cmd: FilelistCommand) -> anyhow::Result<()> {
             scr_file,
         )
         .context("Failed to create filelist"),
+        FilelistCommand::Convert {
+            filename,
+            output_file,
+            version,
+            platform,
+            split_size,
+        } => {
+            filelist::convert::execute_command(filename, output_file, version, platform, split_size)
+                .context("Failed to convert filelist")
+        }
+        FilelistCommand::Patch {
+            filename,
+            input_folder,
+            split_size,
+            compact,
+        } => filelist::patch::execute_command(filename, input_folder, split_size, compact)
+            .context("Failed to patch filelist"),
+        FilelistCommand::Verify { filename } => {
+            filelist::verify::execute_command(filename).context("Failed to verify filelist")
+        }
     }
 }
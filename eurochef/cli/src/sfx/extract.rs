@@ -0,0 +1,70 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use eurochef_filelist::vfs;
+use eurochef_sfx::{wav::write_wav, SoundBank};
+use eurochef_shared::{filesystem::path::DissectedFilelistPath, maps::format_hashcode};
+
+pub fn execute_command(filename: String, output_folder: Option<String>) -> anyhow::Result<()> {
+    let output_folder = output_folder.unwrap_or(format!(
+        "./sounds/{}/",
+        Path::new(&filename).file_name().unwrap().to_string_lossy()
+    ));
+    let output_folder = Path::new(&output_folder);
+    std::fs::create_dir_all(output_folder)?;
+
+    let dissected_path = DissectedFilelistPath::dissect(&filename);
+
+    let hashcodes = match &dissected_path {
+        Some(p) => eurochef_shared::filesystem::load_hashcodes(p, true),
+        None => {
+            warn!("Given path is not a valid EngineX-compatible path, sounds won't be named");
+            Default::default()
+        }
+    };
+
+    let mut reader = BufReader::new(vfs::open_path(&filename)?);
+    let bank = SoundBank::read(&mut reader)?;
+
+    info!(
+        "Extracting {} samples ({} sound effects) from {} bank {:x}",
+        bank.samples.len(),
+        bank.sound_effects.len(),
+        bank.platform,
+        bank.header.hashcode
+    );
+
+    let mut used_names = HashSet::new();
+    for (i, hashcode) in bank.sample_hashcodes().into_iter().enumerate() {
+        let sample = match bank.decode_sample(i) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to decode sample {i}: {e}");
+                continue;
+            }
+        };
+
+        // Samples shared by or unused by sound effects fall back to their index
+        let name = match hashcode.map(|h| format_hashcode(&hashcodes, h)) {
+            Some(name) if used_names.insert(name.clone()) => name,
+            Some(name) => format!("{name}_{i}"),
+            None => format!("sample_{i}"),
+        };
+
+        let mut output = BufWriter::new(File::create(output_folder.join(format!("{name}.wav")))?);
+        write_wav(
+            &mut output,
+            &sample.data,
+            sample.sample_rate,
+            sample.channels,
+        )?;
+    }
+
+    info!("Successfully extracted sounds!");
+
+    Ok(())
+}
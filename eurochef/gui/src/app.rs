@@ -11,9 +11,10 @@ use egui::{mutex::RwLock, Color32, FontData, FontDefinitions, NumExt};
 use eurochef_edb::{
     binrw::{BinReaderExt, Endian},
     edb::EdbFile,
-    versions::Platform,
+    versions::{transform_windows_path, Platform},
     Hashcode, HashcodeUtils,
 };
+use eurochef_filelist::{vfs, Vfs};
 use eurochef_shared::filesystem::path::DissectedFilelistPath;
 use eurochef_shared::{
     hashcodes::parse_hashcodes, script::UXGeoScript, spreadsheets::UXGeoSpreadsheet,
@@ -155,7 +156,8 @@ impl EurochefApp {
         &mut self,
         path: P,
     ) -> anyhow::Result<()> {
-        let platform = Platform::from_path(&path);
+        let mut reader = BufReader::new(vfs::open_path(&path)?);
+        let platform = Platform::from_reader(&mut reader, &path);
 
         if let Some(dissected_path) = DissectedFilelistPath::dissect(&path) {
             self.game = dissected_path.game.clone();
@@ -172,39 +174,70 @@ impl EurochefApp {
             );
             self.path_cache.clear();
 
-            for entry in glob::glob(&format!(
-                "{}/*.edb",
-                dissected_path.dir_absolute().to_string_lossy()
-            ))? {
-                match entry {
-                    Ok(path) => {
-                        let file = File::open(&path)?;
-                        let mut reader = BufReader::new(file);
-                        let endian = if reader.read_ne::<u8>()? == 0x47 {
-                            Endian::Big
-                        } else {
-                            Endian::Little
-                        };
-                        reader.seek(std::io::SeekFrom::Start(4))?;
-                        let hashcode: Hashcode = reader.read_type(endian)?;
-                        self.path_cache
-                            .insert(hashcode, path.to_string_lossy().to_string());
+            if let Some((filelist_path, _)) = Vfs::split_path(&path) {
+                let vfs = Vfs::open(&filelist_path)?;
+                let dir = dissected_path
+                    .dir_relative()
+                    .to_string_lossy()
+                    .replace('/', "\\")
+                    .to_lowercase();
+
+                for file in vfs.files() {
+                    let file_lower = file.to_lowercase();
+                    if !file_lower.contains(&dir) || !file_lower.ends_with(".edb") {
+                        continue;
+                    }
+
+                    let mut reader = BufReader::new(vfs.open_file(file)?);
+                    let hashcode = Self::read_edb_hashcode(&mut reader)?;
+                    self.path_cache.insert(
+                        hashcode,
+                        filelist_path
+                            .join(transform_windows_path(file))
+                            .to_string_lossy()
+                            .to_string(),
+                    );
+                }
+            } else {
+                for entry in glob::glob(&format!(
+                    "{}/*.edb",
+                    dissected_path.dir_absolute().to_string_lossy()
+                ))? {
+                    match entry {
+                        Ok(path) => {
+                            let file = File::open(&path)?;
+                            let mut reader = BufReader::new(file);
+                            let hashcode = Self::read_edb_hashcode(&mut reader)?;
+                            self.path_cache
+                                .insert(hashcode, path.to_string_lossy().to_string());
+                        }
+                        Err(e) => println!("{:?}", e),
                     }
-                    Err(e) => println!("{:?}", e),
                 }
             }
 
             info!("Indexed {} EDBs", self.path_cache.len());
         }
 
-        let mut f = File::open(path)?;
         let mut data = vec![];
-        f.read_to_end(&mut data)?;
+        reader.rewind()?;
+        reader.read_to_end(&mut data)?;
         self.pending_file = Some((data, platform));
 
         Ok(())
     }
 
+    fn read_edb_hashcode<R: Read + Seek>(reader: &mut R) -> anyhow::Result<Hashcode> {
+        let endian = if reader.read_ne::<u8>()? == 0x47 {
+            Endian::Big
+        } else {
+            Endian::Little
+        };
+        reader.seek(std::io::SeekFrom::Start(4))?;
+
+        Ok(reader.read_type(endian)?)
+    }
+
     pub fn load_into_render_store(
         &mut self,
         references: &[Hashcode],
@@ -216,8 +249,7 @@ impl EurochefApp {
             match file_map.entry(file_ref) {
                 hash_map::Entry::Occupied(e) => e.into_mut(),
                 hash_map::Entry::Vacant(a) => {
-                    let file = File::open(path)?;
-                    let reader = BufReader::new(file);
+                    let reader = BufReader::new(vfs::open_path(path)?);
 
                     a.insert(EdbFile::new(Box::new(reader), platform)?)
                 }
@@ -19,6 +19,8 @@ pub struct TextureList {
 
     enlarged_texture: Option<(usize, u32)>,
     enlarged_zoom: f32,
+    /// Slice of the enlarged texture to show, for volume textures
+    enlarged_slice: u32,
 
     fallback_texture: egui::TextureHandle,
 }
@@ -37,6 +39,7 @@ impl TextureList {
 
             enlarged_texture: None,
             enlarged_zoom: Self::ENLARGED_ZOOM_DEFAULT,
+            enlarged_slice: 0,
 
             fallback_texture: ctx.load_texture(
                 "fallback",
@@ -59,8 +62,12 @@ impl TextureList {
                     .map(|f| {
                         ctx.load_texture(
                             format!("{:08x}", it.hashcode),
+                            // Volume textures are uploaded as a vertical atlas of all slices
                             egui::ColorImage::from_rgba_unmultiplied(
-                                [t.width as usize, t.height as usize],
+                                [
+                                    t.width as usize,
+                                    t.height as usize * t.depth.max(1) as usize,
+                                ],
                                 f,
                             ),
                             egui::TextureOptions::default(),
@@ -118,7 +125,7 @@ impl TextureList {
 
                                 let diagnostics = t.diagnostics.to_strings();
 
-                                let response = egui::Image::new(current, egui::vec2(128., 128.) * self.zoom).sense(egui::Sense::click()).ui(ui)
+                                let response = egui::Image::new(current, egui::vec2(128., 128.) * self.zoom).uv(slice_uv(0, t.depth)).sense(egui::Sense::click()).ui(ui)
                                 .on_hover_ui(|ui| {
                                     ui.label(format!(
                                         "Hashcode: {:08x}\nFormat (internal): 0x{:x}\nDimensions: {}x{}{}\nScroll: {} {}\nFlags: 0x{:x}\nGameflags: 0x{:x}\nIndex: {i}\n",
@@ -242,7 +249,18 @@ impl TextureList {
 
                         self.enlarged_zoom *= ctx.input(|i| i.zoom_delta());
 
-                        egui::Image::new(current, current.size_vec2() * self.enlarged_zoom).ui(ui);
+                        if t.depth > 1 {
+                            egui::Slider::new(&mut self.enlarged_slice, 0..=t.depth as u32 - 1)
+                                .text("Slice")
+                                .ui(ui);
+                        }
+
+                        egui::Image::new(
+                            current,
+                            egui::vec2(t.width as f32, t.height as f32) * self.enlarged_zoom,
+                        )
+                        .uv(slice_uv(self.enlarged_slice, t.depth))
+                        .ui(ui);
 
                         // TODO(cohae): Animation checkbox, when unticked, show frame slider
                     });
@@ -252,10 +270,20 @@ impl TextureList {
         if !window_open {
             self.enlarged_texture = None;
             self.enlarged_zoom = Self::ENLARGED_ZOOM_DEFAULT; // swy: reset the zoom level each time we close a preview
+            self.enlarged_slice = 0;
         }
     }
 }
 
+/// UV rect of a single slice in a volume texture atlas
+fn slice_uv(slice: u32, depth: u16) -> egui::Rect {
+    let depth = depth.max(1) as f32;
+    egui::Rect::from_min_max(
+        egui::pos2(0., slice as f32 / depth),
+        egui::pos2(1., (slice + 1) as f32 / depth),
+    )
+}
+
 pub fn cutoff_string(string: String, max_len: usize) -> String {
     if string.len() > max_len {
         let new_string = String::from_utf8_lossy(&string.as_bytes()[..max_len]).to_string();
@@ -7,6 +7,7 @@ pub mod maps;
 pub mod platform;
 pub mod script;
 pub mod spreadsheets;
+pub mod texture_containers;
 pub mod textures;
 
 pub struct IdentifiableResult<T: Clone> {
@@ -2,7 +2,7 @@ use anyhow::ensure;
 use enumn::N;
 use image::RgbaImage;
 
-use super::TextureDecoder;
+use super::{BlockFormat, TextureDecoder, TextureEncoder};
 
 pub struct GxTextureDecoder;
 
@@ -14,8 +14,15 @@ impl TextureDecoder for GxTextureDecoder {
         depth: u32,
         format: u8,
     ) -> anyhow::Result<usize> {
-        let bits = (width as usize * height as usize * depth as usize)
-            * InternalFormat::from_exformat(format)?.bpp();
+        // Data is always stored in whole blocks
+        let fmt = InternalFormat::from_exformat(format)?;
+        let (blockw, blockh) = fmt.block_size();
+        let (width, height) = (
+            (width as usize + blockw - 1) / blockw * blockw,
+            (height as usize + blockh - 1) / blockh * blockh,
+        );
+
+        let bits = (width * height * depth as usize) * fmt.bpp();
 
         Ok((bits + 7) / 8)
     }
@@ -31,16 +38,44 @@ impl TextureDecoder for GxTextureDecoder {
         format: u8,
         _version: u32,
     ) -> anyhow::Result<()> {
+        anyhow::ensure!(depth <= 1, "Volume textures are not supported on GX");
+
         // * The data for GC/Wii textures contains an extra header with the internal GX format
         let input_header = &input[0..64];
         let gxformat = input_header[27];
 
-        let input = &input[64..];
-
         let fmt = InternalFormat::n(gxformat)
             .ok_or(anyhow::anyhow!("Invalid texture format 0x{gxformat:x}"))?;
 
         anyhow::ensure!(fmt == InternalFormat::from_exformat(format)?);
+
+        // Small mip levels are padded to a whole block, decode them at the padded size and crop the result
+        let (blockw, blockh) = fmt.block_size();
+        let (padded_width, padded_height) = (
+            (width + blockw as u32 - 1) / blockw as u32 * blockw as u32,
+            (height + blockh as u32 - 1) / blockh as u32 * blockh as u32,
+        );
+        if depth == 1 && (padded_width, padded_height) != (width, height) {
+            let mut padded = RgbaImage::new(padded_width, padded_height);
+            self.decode(
+                input,
+                _clut,
+                &mut padded,
+                padded_width,
+                padded_height,
+                depth,
+                format,
+                _version,
+            )?;
+
+            anyhow::ensure!(output.len() == (width as usize * height as usize) * 4);
+            output.copy_from_slice(
+                &image::imageops::crop_imm(&padded, 0, 0, width, height).to_image(),
+            );
+            return Ok(());
+        }
+
+        let input = &input[64..];
         anyhow::ensure!(input.len() >= self.get_data_size(width, height, depth, format)?);
         anyhow::ensure!(output.len() == (width as usize * height as usize * depth as usize) * 4);
 
@@ -228,9 +263,170 @@ impl TextureDecoder for GxTextureDecoder {
 
         Ok(())
     }
+
+    fn extract_blocks(
+        &self,
+        input: &[u8],
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+        if InternalFormat::from_exformat(format)? != InternalFormat::Cmpr {
+            return Ok(None);
+        }
+
+        anyhow::ensure!(depth <= 1, "Volume textures are not supported on GX");
+        anyhow::ensure!(input.len() >= 64 + self.get_data_size(width, height, depth, format)?);
+        let input = &input[64..];
+
+        // CMPR stores 8x8 tiles of 4 blocks each, smaller textures are padded to a whole tile
+        let (blocks_x, blocks_y) = ((width as usize + 3) / 4, (height as usize + 3) / 4);
+        let tiles_x = (width as usize + 7) / 8;
+        let mut blocks = Vec::with_capacity(blocks_x * blocks_y * 8);
+        for by in 0..blocks_y {
+            for bx in 0..blocks_x {
+                let tile = (by / 2) * tiles_x + bx / 2;
+                let offset = tile * 32 + ((by % 2) * 2 + bx % 2) * 8;
+                blocks.extend(swap_dxt_block(&input[offset..offset + 8]));
+            }
+        }
+
+        Ok(Some((BlockFormat::Bc1, blocks)))
+    }
 }
 
-#[derive(Debug, N, PartialEq)]
+pub struct GxTextureEncoder;
+
+impl TextureEncoder for GxTextureEncoder {
+    /// The 64-byte GX header is only filled in with the internal format,
+    /// the rest of it should be copied from the original texture when replacing one
+    fn encode(
+        &self,
+        input: &RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        _version: u32,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let fmt = InternalFormat::from_exformat(format)?;
+
+        anyhow::ensure!(depth <= 1, "Volume textures are not supported on GX");
+        anyhow::ensure!(input.len() == (width as usize * height as usize * depth as usize) * 4);
+
+        let mut output = vec![0u8; 64];
+        output[27] = fmt as u8;
+
+        let intensity = |p: &image::Rgba<u8>| ((p[0] as u32 + p[1] as u32 + p[2] as u32) / 3) as u8;
+
+        match fmt {
+            InternalFormat::RGBA8 => {
+                for y in (0..height).step_by(4) {
+                    for x in (0..width).step_by(4) {
+                        let mut src1 = [0u8; 32];
+                        let mut src2 = [0u8; 32];
+                        for iy in 0..4 {
+                            for ix in 0..4 {
+                                let (bx, by) = (x + ix as u32, y + iy as u32);
+                                if bx >= width || by >= height {
+                                    continue;
+                                }
+
+                                let p = input[(bx, by)];
+                                src1[iy * 8 + ix * 2] = p[3];
+                                src1[iy * 8 + ix * 2 + 1] = p[0];
+                                src2[iy * 8 + ix * 2] = p[1];
+                                src2[iy * 8 + ix * 2 + 1] = p[2];
+                            }
+                        }
+
+                        output.extend_from_slice(&src1);
+                        output.extend_from_slice(&src2);
+                    }
+                }
+            }
+            InternalFormat::Cmpr => {
+                // CMPR stores 8x8 tiles of 4 blocks each,
+                // smaller textures are padded to a whole tile by repeating the edge pixels
+                let (padded_width, padded_height) = ((width + 7) & !7, (height + 7) & !7);
+                let padded = RgbaImage::from_fn(padded_width, padded_height, |x, y| {
+                    input[(x.min(width - 1), y.min(height - 1))]
+                });
+
+                let (w, h) = (padded_width as usize, padded_height as usize);
+                let mut blocks = vec![0u8; squish::Format::Bc1.compressed_size(w, h)];
+                squish::Format::Bc1.compress(&padded, w, h, Default::default(), &mut blocks);
+
+                let blocks_x = w / 4;
+                for y in (0..h / 4).step_by(2) {
+                    for x in (0..blocks_x).step_by(2) {
+                        for (bx, by) in [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
+                            let offset = (by * blocks_x + bx) * 8;
+                            output.extend(swap_dxt_block(&blocks[offset..offset + 8]));
+                        }
+                    }
+                }
+            }
+            _ => {
+                // Pixels are stored in tiles, one after another
+                let (blockw, blockh) = fmt.block_size();
+                let mut pixels = vec![];
+                for y in (0..height).step_by(blockh) {
+                    for x in (0..width).step_by(blockw) {
+                        for by in 0..blockh as u32 {
+                            for bx in 0..blockw as u32 {
+                                pixels.push(
+                                    input
+                                        .get_pixel_checked(x + bx, y + by)
+                                        .copied()
+                                        .unwrap_or(image::Rgba([0, 0, 0, 0])),
+                                );
+                            }
+                        }
+                    }
+                }
+
+                match fmt {
+                    InternalFormat::I4 => output.extend(
+                        pixels
+                            .chunks(2)
+                            .map(|p| (intensity(&p[0]) & 0xf0) | (intensity(&p[1]) >> 4)),
+                    ),
+                    InternalFormat::I8 => output.extend(pixels.iter().map(intensity)),
+                    InternalFormat::IA4 => {
+                        output.extend(pixels.iter().map(|p| (p[3] & 0xf0) | (intensity(p) >> 4)))
+                    }
+                    InternalFormat::IA8 => {
+                        output.extend(pixels.iter().flat_map(|p| [p[3], intensity(p)]))
+                    }
+                    InternalFormat::RGB5A3 => output.extend(pixels.iter().flat_map(|p| {
+                        if p[3] == 0xff {
+                            (0x8000
+                                | ((p[0] as u16 >> 3) << 10)
+                                | ((p[1] as u16 >> 3) << 5)
+                                | (p[2] as u16 >> 3))
+                                .to_be_bytes()
+                        } else {
+                            (((p[3] as u16 >> 5) << 12)
+                                | ((p[0] as u16 >> 4) << 8)
+                                | ((p[1] as u16 >> 4) << 4)
+                                | (p[2] as u16 >> 4))
+                                .to_be_bytes()
+                        }
+                    })),
+                    _ => {
+                        anyhow::bail!("Unsupported format {:?}", fmt);
+                    }
+                }
+            }
+        }
+
+        Ok((output, None))
+    }
+}
+
+#[derive(Debug, N, PartialEq, Clone, Copy)]
 #[repr(u8)]
 enum InternalFormat {
     // TODO: We're just using the internal GX formats for this array, that might change once we've discovered all exformat conversions
@@ -345,9 +541,9 @@ fn decode_dxt_block(dst: &mut [u8], src: &[u8], pitch: u32) -> anyhow::Result<()
         for x in 0..4 {
             let offset = (y * pitch as usize + x) * 4;
 
-            // ensure!(offset + 4 < dst.len());
+            // ensure!(offset + 4 <= dst.len());
 
-            if offset + 4 < dst.len() {
+            if offset + 4 <= dst.len() {
                 dst[offset..offset + 4]
                     .copy_from_slice(&colours[((val >> 6) & 3) as usize].to_le_bytes());
                 val <<= 2;
@@ -358,6 +554,21 @@ fn decode_dxt_block(dst: &mut [u8], src: &[u8], pitch: u32) -> anyhow::Result<()
     Ok(())
 }
 
+/// Converts a BC1 block to the GX layout (big endian colors, first pixel in the high bits), or the other way around
+fn swap_dxt_block(src: &[u8]) -> [u8; 8] {
+    let mut dst = [0u8; 8];
+    dst[0] = src[1];
+    dst[1] = src[0];
+    dst[2] = src[3];
+    dst[3] = src[2];
+    for y in 0..4 {
+        let v = src[4 + y];
+        dst[4 + y] = ((v & 0x03) << 6) | ((v & 0x0c) << 2) | ((v & 0x30) >> 2) | ((v & 0xc0) >> 6);
+    }
+
+    dst
+}
+
 fn convert_3_to_8(x: u8) -> u8 {
     (x << 5) | (x << 2) | (x >> 1)
 }
@@ -1,9 +1,12 @@
 pub mod gx;
 pub mod pc;
 pub mod ps2;
+pub mod ps3;
+pub mod wiiu;
 pub mod xbox;
 pub mod xenon;
 
+use anyhow::Context;
 use eurochef_edb::versions::Platform;
 use image::RgbaImage;
 
@@ -22,7 +25,8 @@ pub trait TextureDecoder {
         unimplemented!()
     }
 
-    /// Output buffer must be width*height*depth*4 bytes long (RGBA)
+    /// Output buffer must be width*height*depth*4 bytes long (RGBA).
+    /// Volume textures are decoded as a vertical atlas of all slices (width x height*depth)
     fn decode(
         &self,
         input: &[u8],
@@ -34,6 +38,65 @@ pub trait TextureDecoder {
         format: u8,
         version: u32,
     ) -> anyhow::Result<()>;
+
+    /// Returns the data as standard 4x4 blocks (in linear order), for formats that map directly to a block format.
+    /// Returns `None` for formats that have to be decoded instead
+    fn extract_blocks(
+        &self,
+        _input: &[u8],
+        _width: u32,
+        _height: u32,
+        _depth: u32,
+        _format: u8,
+    ) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+        Ok(None)
+    }
+}
+
+/// Standard block compressed formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// DXT1
+    Bc1,
+    /// DXT2/DXT3
+    Bc2,
+    /// DXT4/DXT5
+    Bc3,
+    Bc4,
+    Bc5,
+}
+
+impl BlockFormat {
+    pub fn block_size(&self) -> usize {
+        match self {
+            Self::Bc1 | Self::Bc4 => 8,
+            Self::Bc2 | Self::Bc3 | Self::Bc5 => 16,
+        }
+    }
+
+    pub fn data_size(&self, width: u32, height: u32, depth: u32) -> usize {
+        let blocks = ((width as usize + 3) / 4) * ((height as usize + 3) / 4) * depth as usize;
+        blocks * self.block_size()
+    }
+}
+
+pub trait TextureEncoder {
+    /// Encodes RGBA pixels into the given format.
+    /// Returns the texture data and the CLUT for paletted formats that store it separately
+    fn encode(
+        &self,
+        input: &RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        version: u32,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)>;
+
+    /// Whether the format stores palette indices. Every encoded level gets its own palette
+    fn is_paletted(&self, _format: u8) -> bool {
+        false
+    }
 }
 
 pub fn create_for_platform(platform: Platform) -> Box<dyn TextureDecoder> {
@@ -43,6 +106,391 @@ pub fn create_for_platform(platform: Platform) -> Box<dyn TextureDecoder> {
         Platform::GameCube | Platform::Wii => Box::new(gx::GxTextureDecoder),
         Platform::Xbox => Box::new(xbox::XboxTextureDecoder),
         Platform::Xbox360 => Box::new(xenon::XenonTextureDecoder),
+        Platform::WiiU => Box::new(wiiu::WiiUTextureDecoder),
+        Platform::Ps3 => Box::new(ps3::Ps3TextureDecoder),
         p => panic!("Unsupported platform for texture decoding: {p:?}"),
     }
 }
+
+pub fn create_encoder_for_platform(platform: Platform) -> anyhow::Result<Box<dyn TextureEncoder>> {
+    Ok(match platform {
+        Platform::Pc => Box::new(pc::PcTextureEncoder),
+        Platform::Ps2 => Box::new(ps2::Ps2TextureEncoder),
+        Platform::GameCube | Platform::Wii => Box::new(gx::GxTextureEncoder),
+        Platform::Xbox => Box::new(xbox::XboxTextureEncoder),
+        Platform::Xbox360 => Box::new(xenon::XenonTextureEncoder),
+        p => anyhow::bail!("Encoding textures for {p} is not supported yet"),
+    })
+}
+
+/// Copies block data that is already stored in linear order
+pub(crate) fn copy_blocks(
+    input: &[u8],
+    block_format: BlockFormat,
+    width: u32,
+    height: u32,
+    depth: u32,
+) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+    let size = block_format.data_size(width, height, depth);
+    let blocks = input
+        .get(..size)
+        .with_context(|| format!("Expected {size} bytes of block data, got {}", input.len()))?;
+
+    Ok(Some((block_format, blocks.to_vec())))
+}
+
+/// Decodes a volume texture whose slices are stored one after another, one slice at a time
+pub(crate) fn decode_slices(
+    input: &[u8],
+    output: &mut RgbaImage,
+    width: u32,
+    height: u32,
+    depth: u32,
+    slice_size: usize,
+    mut decode_slice: impl FnMut(&[u8], &mut RgbaImage) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(output.len() == (width as usize * height as usize * depth as usize) * 4);
+
+    let slice_len = (width as usize * height as usize) * 4;
+    let mut slice = RgbaImage::new(width, height);
+    for z in 0..depth as usize {
+        let slice_data = input
+            .get(z * slice_size..(z + 1) * slice_size)
+            .with_context(|| format!("Slice {z} is outside of the texture data"))?;
+
+        decode_slice(slice_data, &mut slice)
+            .with_context(|| format!("Failed to decode slice {z}"))?;
+        output.as_mut()[z * slice_len..(z + 1) * slice_len].copy_from_slice(&slice);
+    }
+
+    Ok(())
+}
+
+/// Encodes a volume texture (a vertical atlas of all slices) one slice at a time, storing the slices one after another
+pub(crate) fn encode_slices(
+    input: &RgbaImage,
+    width: u32,
+    height: u32,
+    depth: u32,
+    mut encode_slice: impl FnMut(&RgbaImage) -> anyhow::Result<Vec<u8>>,
+) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(input.len() == (width as usize * height as usize * depth as usize) * 4);
+
+    let mut output = vec![];
+    for z in 0..depth {
+        let slice = image::imageops::crop_imm(input, 0, z * height, width, height).to_image();
+        output.extend(encode_slice(&slice).with_context(|| format!("Failed to encode slice {z}"))?);
+    }
+
+    Ok(output)
+}
+
+/// Reduces the colors of an image to at most `max_colors` using median cut.
+/// Returns the palette and a palette index for every pixel
+fn quantize(input: &RgbaImage, max_colors: usize) -> (Vec<[u8; 4]>, Vec<u8>) {
+    let mut colors: Vec<[u8; 4]> = input.pixels().map(|p| p.0).collect();
+    colors.sort_unstable();
+    colors.dedup();
+
+    let palette: Vec<[u8; 4]> = if colors.len() <= max_colors {
+        colors
+    } else {
+        let mut boxes = vec![colors];
+        while boxes.len() < max_colors {
+            // Split the box with the largest range along that channel
+            let Some((index, channel, _)) = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .flat_map(|(i, b)| {
+                    (0..4).map(move |c| {
+                        let (min, max) = b
+                            .iter()
+                            .fold((255, 0), |(min, max), p| (p[c].min(min), p[c].max(max)));
+                        (i, c, max - min)
+                    })
+                })
+                .max_by_key(|&(_, _, range)| range)
+            else {
+                break;
+            };
+
+            let mut b = boxes.swap_remove(index);
+            b.sort_unstable_by_key(|p| p[channel]);
+            let upper = b.split_off(b.len() / 2);
+            boxes.push(b);
+            boxes.push(upper);
+        }
+
+        boxes
+            .iter()
+            .map(|b| {
+                let mut sum = [0usize; 4];
+                for p in b {
+                    for c in 0..4 {
+                        sum[c] += p[c] as usize;
+                    }
+                }
+
+                sum.map(|v| (v / b.len()) as u8)
+            })
+            .collect()
+    };
+
+    let mut cache = std::collections::HashMap::new();
+    let indices = input
+        .pixels()
+        .map(|p| {
+            *cache.entry(p.0).or_insert_with(|| {
+                palette
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, c)| {
+                        (0..4)
+                            .map(|i| (c[i] as i32 - p[i] as i32).pow(2))
+                            .sum::<i32>()
+                    })
+                    .map(|(i, _)| i as u8)
+                    .unwrap_or(0)
+            })
+        })
+        .collect();
+
+    (palette, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let (x, y) = ((x % 16) as u8, (y % 16) as u8);
+            [x * 16, y * 16, (x + y) * 8, 0xfe - x * 2].into()
+        })
+    }
+
+    fn roundtrip(platform: Platform, format: u8, width: u32, height: u32) {
+        let image = test_image(width, height);
+        let (data, clut) = create_encoder_for_platform(platform)
+            .unwrap()
+            .encode(&image, width, height, 1, format, 259)
+            .unwrap();
+
+        let mut output = RgbaImage::new(width, height);
+        create_for_platform(platform)
+            .decode(
+                &data,
+                clut.as_deref(),
+                &mut output,
+                width,
+                height,
+                1,
+                format,
+                259,
+            )
+            .unwrap();
+
+        assert!(
+            image == output,
+            "{platform} format {format} doesn't round-trip"
+        );
+    }
+
+    fn gradient(width: u32, height: u32, alpha: bool) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let a = if alpha { 255 - (x * 4) as u8 } else { 255 };
+            [(x * 8) as u8, (y * 8) as u8, 128, a].into()
+        })
+    }
+
+    fn greyscale(width: u32, height: u32, alpha: bool) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let v = ((x + y) * 4) as u8;
+            let a = if alpha { 255 - (y * 4) as u8 } else { 255 };
+            [v, v, v, a].into()
+        })
+    }
+
+    /// Encodes and decodes the image, returning the largest difference of any channel
+    fn roundtrip_error(
+        platform: Platform,
+        format: u8,
+        image: &RgbaImage,
+        height: u32,
+        depth: u32,
+    ) -> u8 {
+        let width = image.width();
+        let (data, clut) = create_encoder_for_platform(platform)
+            .unwrap()
+            .encode(image, width, height, depth, format, 259)
+            .unwrap();
+
+        let mut output = RgbaImage::new(width, height * depth);
+        create_for_platform(platform)
+            .decode(
+                &data,
+                clut.as_deref(),
+                &mut output,
+                width,
+                height,
+                depth,
+                format,
+                259,
+            )
+            .unwrap();
+
+        image
+            .as_raw()
+            .iter()
+            .zip(output.as_raw())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn roundtrip_lossy(platform: Platform, format: u8, image: &RgbaImage, tolerance: u8) {
+        let error = roundtrip_error(platform, format, image, image.height(), 1);
+        assert!(
+            error <= tolerance,
+            "{platform} format {format} is off by {error} (tolerance {tolerance})"
+        );
+    }
+
+    #[test]
+    fn roundtrip_lossless() {
+        roundtrip(Platform::Pc, 6, 32, 16);
+        roundtrip(Platform::Xbox, 6, 32, 16);
+        roundtrip(Platform::Xbox, 9, 32, 16);
+        roundtrip(Platform::Xbox360, 9, 32, 16);
+        roundtrip(Platform::GameCube, 1, 32, 16);
+        roundtrip(Platform::Ps2, 5, 32, 16);
+    }
+
+    #[test]
+    fn roundtrip_small_mips() {
+        // Mip levels smaller than a single block/tile
+        roundtrip(Platform::Pc, 6, 2, 1);
+        roundtrip(Platform::GameCube, 1, 2, 2);
+        roundtrip(Platform::GameCube, 1, 1, 1);
+    }
+
+    #[test]
+    fn decode_volume() {
+        // Slices encoded one after another should decode to a vertical atlas
+        let (width, height, depth) = (8, 4, 3);
+        let atlas = test_image(width, height * depth);
+        for (platform, format) in [(Platform::Pc, 6), (Platform::Xbox360, 9)] {
+            let encoder = create_encoder_for_platform(platform).unwrap();
+            let mut data = vec![];
+            for z in 0..depth {
+                let slice = image::imageops::crop_imm(&atlas, 0, z * height, width, height);
+                let (slice_data, _) = encoder
+                    .encode(&slice.to_image(), width, height, 1, format, 259)
+                    .unwrap();
+                data.extend(slice_data);
+            }
+
+            let mut output = RgbaImage::new(width, height * depth);
+            create_for_platform(platform)
+                .decode(&data, None, &mut output, width, height, depth, format, 259)
+                .unwrap();
+
+            assert!(
+                atlas == output,
+                "{platform} format {format} volume doesn't decode"
+            );
+        }
+    }
+
+    #[test]
+    fn extract_blocks() {
+        // Encoded DXT data should come back as the blocks it was compressed to
+        let (width, height) = (16, 8);
+        let image = test_image(width, height);
+        let (w, h) = (width as usize, height as usize);
+        let mut expected = vec![0u8; squish::Format::Bc1.compressed_size(w, h)];
+        squish::Format::Bc1.compress(&image, w, h, Default::default(), &mut expected);
+
+        for (platform, format) in [
+            (Platform::Pc, 2),
+            (Platform::Xbox360, 0),
+            (Platform::GameCube, 0),
+        ] {
+            let (data, _) = create_encoder_for_platform(platform)
+                .unwrap()
+                .encode(&image, width, height, 1, format, 259)
+                .unwrap();
+
+            let blocks = create_for_platform(platform)
+                .extract_blocks(&data, width, height, 1, format)
+                .unwrap();
+            assert!(
+                blocks == Some((BlockFormat::Bc1, expected.clone())),
+                "{platform} format {format} blocks don't match"
+            );
+        }
+    }
+
+    #[test]
+    fn roundtrip_dxt() {
+        roundtrip_lossy(Platform::Pc, 2, &gradient(32, 32, false), 24);
+        roundtrip_lossy(Platform::Pc, 7, &gradient(32, 32, true), 24);
+        roundtrip_lossy(Platform::Pc, 9, &gradient(32, 32, true), 24);
+        roundtrip_lossy(Platform::GameCube, 0, &gradient(32, 32, false), 24);
+    }
+
+    #[test]
+    fn roundtrip_cmpr_padded() {
+        // CMPR is padded to whole 8x8 tiles
+        roundtrip_lossy(Platform::GameCube, 0, &gradient(12, 10, false), 24);
+        roundtrip_lossy(Platform::GameCube, 0, &gradient(4, 4, false), 24);
+    }
+
+    #[test]
+    fn roundtrip_gx_lossy() {
+        // 3-bit alpha for translucent pixels
+        roundtrip_lossy(Platform::GameCube, 3, &gradient(32, 32, true), 36);
+        roundtrip_lossy(Platform::GameCube, 4, &greyscale(32, 32, false), 16);
+        roundtrip_lossy(Platform::GameCube, 8, &greyscale(32, 32, true), 0);
+    }
+
+    #[test]
+    fn roundtrip_ps2_p16() {
+        // 16 colors fit the palette exactly, only the alpha loses its lowest bit
+        let image = RgbaImage::from_fn(32, 32, |x, y| {
+            let i = ((x / 8 + y / 8 * 4) * 16) as u8;
+            [i, 255 - i, i / 2, 255 - i / 4].into()
+        });
+        roundtrip_lossy(Platform::Ps2, 1, &image, 1);
+    }
+
+    #[test]
+    fn encode_volume() {
+        // Volumes are encoded from a vertical atlas of all slices
+        let (width, height, depth) = (8, 4, 4);
+        let image = gradient(width, height * depth, true);
+        for (platform, format, tolerance) in [
+            (Platform::Pc, 6, 0),
+            (Platform::Pc, 9, 24),
+            (Platform::Xbox, 6, 0),
+            (Platform::Xbox, 7, 0),
+            (Platform::Xbox, 9, 0),
+            (Platform::Xbox, 12, 24),
+            (Platform::Xbox360, 9, 0),
+            (Platform::Xbox360, 5, 24),
+        ] {
+            let error = roundtrip_error(platform, format, &image, height, depth);
+            assert!(
+                error <= tolerance,
+                "{platform} format {format} volume is off by {error} (tolerance {tolerance})"
+            );
+        }
+    }
+
+    #[test]
+    fn roundtrip_paletted() {
+        roundtrip(Platform::Xbox, 7, 32, 32);
+        roundtrip(Platform::Ps2, 3, 32, 32);
+    }
+}
@@ -2,7 +2,9 @@ use anyhow::Context;
 use enumn::N;
 use image::RgbaImage;
 
-use super::TextureDecoder;
+use super::{
+    copy_blocks, decode_slices, encode_slices, BlockFormat, TextureDecoder, TextureEncoder,
+};
 
 pub struct PcTextureDecoder;
 
@@ -14,10 +16,15 @@ impl TextureDecoder for PcTextureDecoder {
         depth: u32,
         format: u8,
     ) -> anyhow::Result<usize> {
-        let bits = (width as usize * height as usize * depth as usize)
-            * InternalFormat::n(format)
-                .context(format!("Invalid format 0x{format:x}"))?
-                .bpp();
+        let fmt = InternalFormat::n(format).context(format!("Invalid format 0x{format:x}"))?;
+        // Compressed formats always store whole 4x4 blocks
+        let (width, height) = if fmt.is_compressed() {
+            ((width + 3) & !3, (height + 3) & !3)
+        } else {
+            (width, height)
+        };
+
+        let bits = (width as usize * height as usize * depth as usize) * fmt.bpp();
 
         Ok((bits + 7) / 8)
     }
@@ -38,6 +45,22 @@ impl TextureDecoder for PcTextureDecoder {
 
         anyhow::ensure!(output.len() == (width as usize * height as usize * depth as usize) * 4);
 
+        if depth > 1 {
+            // Volume slices are stored one after another
+            let slice_size = self.get_data_size(width, height, 1, format)?;
+            return decode_slices(
+                input,
+                output,
+                width,
+                height,
+                depth,
+                slice_size,
+                |input, output| {
+                    self.decode(input, _clut, output, width, height, 1, format, _version)
+                },
+            );
+        }
+
         match fmt {
             InternalFormat::Dxt1
             | InternalFormat::Dxt1Alpha
@@ -96,6 +119,102 @@ impl TextureDecoder for PcTextureDecoder {
 
         Ok(())
     }
+
+    fn extract_blocks(
+        &self,
+        input: &[u8],
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+        let fmt = InternalFormat::n(format).context(format!("Invalid format 0x{format:x}"))?;
+        match fmt.block_format() {
+            Some(block_format) => copy_blocks(input, block_format, width, height, depth),
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct PcTextureEncoder;
+
+impl TextureEncoder for PcTextureEncoder {
+    fn encode(
+        &self,
+        input: &RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        _version: u32,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let fmt = InternalFormat::n(format)
+            .ok_or(anyhow::anyhow!("Invalid texture format 0x{format:x}"))?;
+
+        anyhow::ensure!(input.len() == (width as usize * height as usize * depth as usize) * 4);
+
+        if depth > 1 {
+            // Volume slices are stored one after another
+            let output = encode_slices(input, width, height, depth, |slice| {
+                Ok(self.encode(slice, width, height, 1, format, _version)?.0)
+            })?;
+            return Ok((output, None));
+        }
+
+        let output = match fmt {
+            InternalFormat::Dxt1
+            | InternalFormat::Dxt1Alpha
+            | InternalFormat::Dxt2
+            | InternalFormat::Dxt3
+            | InternalFormat::Dxt4
+            | InternalFormat::Dxt5 => {
+                let bcn = match fmt {
+                    InternalFormat::Dxt1 | InternalFormat::Dxt1Alpha => squish::Format::Bc1,
+                    InternalFormat::Dxt2 => squish::Format::Bc2,
+                    InternalFormat::Dxt3 => squish::Format::Bc2,
+                    InternalFormat::Dxt4 => squish::Format::Bc3,
+                    InternalFormat::Dxt5 => squish::Format::Bc3,
+                    _ => panic!("Invalid DXT format"),
+                };
+
+                let mut output = vec![0u8; bcn.compressed_size(width as usize, height as usize)];
+                bcn.compress(
+                    input,
+                    width as usize,
+                    height as usize,
+                    Default::default(),
+                    &mut output,
+                );
+                output
+            }
+            InternalFormat::ARGB8 => input
+                .pixels()
+                .flat_map(|p| [p[2], p[1], p[0], p[3]])
+                .collect(),
+            InternalFormat::RGB565 => input
+                .pixels()
+                .flat_map(|p| {
+                    (((p[0] as u16 >> 3) << 11) | ((p[1] as u16 >> 2) << 5) | (p[2] as u16 >> 3))
+                        .to_le_bytes()
+                })
+                .collect(),
+            InternalFormat::ARGB1555 => input
+                .pixels()
+                .flat_map(|p| {
+                    (((p[3] as u16 >> 7) << 15)
+                        | ((p[0] as u16 >> 3) << 10)
+                        | ((p[1] as u16 >> 3) << 5)
+                        | (p[2] as u16 >> 3))
+                        .to_le_bytes()
+                })
+                .collect(),
+            _ => {
+                anyhow::bail!("Unsupported format {:?}", fmt);
+            }
+        };
+
+        Ok((output, None))
+    }
 }
 
 #[derive(Debug, N)]
@@ -127,4 +246,20 @@ impl InternalFormat {
             Self::Dxt5 => 8,
         }
     }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            Self::Dxt1 | Self::Dxt1Alpha | Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5
+        )
+    }
+
+    pub fn block_format(&self) -> Option<BlockFormat> {
+        match self {
+            Self::Dxt1 | Self::Dxt1Alpha => Some(BlockFormat::Bc1),
+            Self::Dxt2 | Self::Dxt3 => Some(BlockFormat::Bc2),
+            Self::Dxt4 | Self::Dxt5 => Some(BlockFormat::Bc3),
+            _ => None,
+        }
+    }
 }
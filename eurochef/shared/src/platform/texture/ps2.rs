@@ -2,7 +2,7 @@ use anyhow::Context;
 use enumn::N;
 use image::{Rgba, RgbaImage};
 
-use super::TextureDecoder;
+use super::{quantize, TextureDecoder, TextureEncoder};
 
 pub struct Ps2TextureDecoder;
 
@@ -40,6 +40,8 @@ impl TextureDecoder for Ps2TextureDecoder {
         format: u8,
         version: u32,
     ) -> anyhow::Result<()> {
+        anyhow::ensure!(depth <= 1, "Volume textures are not supported on PS2");
+
         let fmt = InternalFormat::n(format)
             .ok_or(anyhow::anyhow!("Invalid texture format 0x{format:x}"))?;
 
@@ -95,6 +97,73 @@ impl TextureDecoder for Ps2TextureDecoder {
     }
 }
 
+pub struct Ps2TextureEncoder;
+
+impl TextureEncoder for Ps2TextureEncoder {
+    fn encode(
+        &self,
+        input: &RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        version: u32,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let fmt = InternalFormat::n(format)
+            .ok_or(anyhow::anyhow!("Invalid texture format 0x{format:x}"))?;
+
+        anyhow::ensure!(input.len() == (width as usize * height as usize * depth as usize) * 4);
+
+        // PS2 alpha goes from 0 to 0x80
+        let to_ps2_color = |c: &[u8; 4]| [c[0], c[1], c[2], c[3] >> 1];
+
+        match fmt {
+            InternalFormat::P256x32 => {
+                let (palette, indices) = quantize(input, 256);
+                let mut clut = [[0u8; 4]; 256];
+                for (i, c) in palette.iter().enumerate() {
+                    clut[i] = to_ps2_color(c);
+                }
+
+                // Swapping the middle rows of every 32 colors is its own inverse
+                let clut_linear = clut;
+                for i in 0..8 {
+                    let offset = i * 32;
+                    clut[offset + 8..offset + 16]
+                        .copy_from_slice(&clut_linear[offset + 16..offset + 24]);
+                    clut[offset + 16..offset + 24]
+                        .copy_from_slice(&clut_linear[offset + 8..offset + 16])
+                }
+
+                Ok((
+                    swizzle8_from_32(&indices, width, height, version),
+                    Some(bytemuck::cast_slice(&clut).to_vec()),
+                ))
+            }
+            InternalFormat::P16x32 => {
+                let (palette, indices) = quantize(input, 16);
+                let mut clut = [[0u8; 4]; 16];
+                for (i, c) in palette.iter().enumerate() {
+                    clut[i] = to_ps2_color(c);
+                }
+
+                Ok((
+                    swizzle4_from_32(&indices, width, height, version),
+                    Some(bytemuck::cast_slice(&clut).to_vec()),
+                ))
+            }
+            InternalFormat::_32BIT => Ok((input.to_vec(), None)),
+            _ => {
+                anyhow::bail!("Unsupported format {:?}", fmt);
+            }
+        }
+    }
+
+    fn is_paletted(&self, format: u8) -> bool {
+        InternalFormat::n(format).map_or(false, |f| f.clut_size() != 0)
+    }
+}
+
 #[derive(Debug, N, PartialEq)]
 #[repr(u8)]
 enum InternalFormat {
@@ -223,3 +292,76 @@ fn swizzle8_to_32(input: &[u8], width: u32, height: u32, version: u32) -> Vec<u8
 
     output
 }
+
+/// Inverse of [`swizzle4_to_32`], packs the indices into nibbles
+fn swizzle4_from_32(input: &[u8], width: u32, height: u32, version: u32) -> Vec<u8> {
+    const INTERLACE_MATRIX: [u8; 8] = [0x00, 0x10, 0x02, 0x12, 0x11, 0x01, 0x13, 0x03];
+
+    const MATRIX: [i32; 4] = [0, 1, -1, 0];
+    const TILE_MATRIX: [i32; 2] = [4, -4];
+
+    let mut pixels = input.to_vec();
+    if version > 177 {
+        for y in 0..height {
+            for x in 0..width {
+                let odd_row = (y & 1) != 0;
+
+                let num1 = (y / 4) & 1;
+                let num2 = (x / 4) & 1;
+                let num3 = y % 4;
+
+                let mut num4 = (x / 4) % 4;
+
+                if odd_row {
+                    num4 += 4;
+                }
+
+                let num5 = (x * 4) % 16;
+                let num6 = (x / 16) * 32;
+
+                let num7 = if odd_row { (y - 1) * width } else { y * width };
+
+                let xx = x as i32 + num1 as i32 * TILE_MATRIX[num2 as usize];
+                let yy = y as i32 + MATRIX[num3 as usize];
+
+                let i = INTERLACE_MATRIX[num4 as usize] as usize + (num5 + num6 + num7) as usize;
+                let j = yy as usize * width as usize + xx as usize;
+
+                if i < pixels.len() && j < input.len() {
+                    pixels[i] = input[j];
+                }
+            }
+        }
+    }
+
+    pixels
+        .chunks(2)
+        .map(|p| (p[0] & 0xf) | (p.get(1).copied().unwrap_or(0) << 4))
+        .collect()
+}
+
+/// Inverse of [`swizzle8_to_32`]
+fn swizzle8_from_32(input: &[u8], width: u32, height: u32, version: u32) -> Vec<u8> {
+    if version <= 177 {
+        return input.to_vec();
+    }
+    let mut output = vec![0u8; input.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let block_location = (y & (!0xF)) * width + (x & (!0xF)) * 2;
+            let swap_selector = (((y + 2) >> 2) & 0x1) * 4;
+            let pos_y = (((y & (!3)) >> 1) + (y & 1)) & 0x7;
+            let column_location = pos_y * width * 2 + ((x + swap_selector) & 0x7) * 4;
+
+            let byte_num = ((y >> 1) & 1) + ((x >> 2) & 2); // 0, 1, 2, 3
+
+            let offset = (block_location + column_location + byte_num) as usize;
+            if offset < output.len() {
+                output[offset] = input[(y * width + x) as usize];
+            }
+        }
+    }
+
+    output
+}
@@ -0,0 +1,319 @@
+use std::sync::Once;
+
+use anyhow::Context;
+use enumn::N;
+use image::RgbaImage;
+use tracing::warn;
+
+use super::{copy_blocks, BlockFormat, TextureDecoder};
+
+/// Texture decoder for the RSX.
+///
+/// Formats are assumed to be native `CELL_GCM_TEXTURE_*` values, including the linear (`LN`) and unnormalized (`UN`) flags.
+/// Uncompressed textures are swizzled unless the linear flag is set, compressed textures are always linear.
+/// None of this has been checked against game files yet, so a warning is logged the first time a texture is decoded
+pub struct Ps3TextureDecoder;
+
+static UNVERIFIED_WARNING: Once = Once::new();
+
+const FLAG_LINEAR: u8 = 0x20;
+const FLAG_UNNORMALIZED: u8 = 0x40;
+
+impl TextureDecoder for Ps3TextureDecoder {
+    fn get_data_size(
+        &self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<usize> {
+        let fmt = InternalFormat::from_format(format)?;
+        let (width, height) = if fmt.is_compressed() {
+            ((width as usize + 3) & !3, (height as usize + 3) & !3)
+        } else {
+            (width as usize, height as usize)
+        };
+
+        Ok((width * height * depth as usize * fmt.bpp() + 7) / 8)
+    }
+
+    fn decode(
+        &self,
+        input: &[u8],
+        _clut: Option<&[u8]>,
+        output: &mut RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        _version: u32,
+    ) -> anyhow::Result<()> {
+        let fmt = InternalFormat::from_format(format)?;
+        UNVERIFIED_WARNING.call_once(|| {
+            warn!("PS3 texture formats haven't been verified against game files, textures may decode incorrectly")
+        });
+
+        anyhow::ensure!(output.len() == (width as usize * height as usize * depth as usize) * 4);
+        let data_size = self.get_data_size(width, height, depth, format)?;
+        anyhow::ensure!(
+            input.len() >= data_size,
+            "Expected at least {data_size} bytes of texture data, got {}",
+            input.len()
+        );
+
+        let (width, height, depth) = (width as usize, height as usize, depth as usize);
+        let mut buffer = vec![0u8; width * height * depth * 4];
+        match fmt {
+            InternalFormat::Dxt1 | InternalFormat::Dxt23 | InternalFormat::Dxt45 => {
+                let bcn = match fmt {
+                    InternalFormat::Dxt1 => squish::Format::Bc1,
+                    InternalFormat::Dxt23 => squish::Format::Bc2,
+                    _ => squish::Format::Bc3,
+                };
+
+                // Volume slices are stored one after another
+                let slice_size = bcn.compressed_size(width, height);
+                for (z, slice) in buffer.chunks_exact_mut(width * height * 4).enumerate() {
+                    bcn.decompress(&input[z * slice_size..], width, height, slice);
+                }
+            }
+            _ => {
+                let bytes_per_pixel = fmt.bpp() / 8;
+                let swizzled = format & FLAG_LINEAR == 0;
+                if swizzled {
+                    // Swizzled textures are padded to power of two dimensions
+                    let swizzled_size = width.next_power_of_two()
+                        * height.next_power_of_two()
+                        * depth.next_power_of_two()
+                        * bytes_per_pixel;
+                    anyhow::ensure!(
+                        input.len() >= swizzled_size,
+                        "Expected at least {swizzled_size} bytes of swizzled texture data, got {}",
+                        input.len()
+                    );
+                }
+
+                for z in 0..depth {
+                    for y in 0..height {
+                        for x in 0..width {
+                            let index = if swizzled {
+                                swizzled_offset(x, y, z, width, height, depth)
+                            } else {
+                                (z * height + y) * width + x
+                            };
+
+                            let p = &input[index * bytes_per_pixel..(index + 1) * bytes_per_pixel];
+                            let o = ((z * height + y) * width + x) * 4;
+                            buffer[o..o + 4].copy_from_slice(&fmt.decode_pixel(p));
+                        }
+                    }
+                }
+            }
+        }
+
+        // TODO(cohae): This line shouldnt have to exist
+        output.copy_from_slice(&buffer);
+
+        Ok(())
+    }
+
+    fn extract_blocks(
+        &self,
+        input: &[u8],
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+        let fmt = InternalFormat::from_format(format)?;
+        match fmt.block_format() {
+            Some(block_format) => copy_blocks(input, block_format, width, height, depth),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Offset of a pixel in a swizzled (Morton order) texture. The bits of every axis are interleaved, until an axis runs out of bits
+fn swizzled_offset(
+    x: usize,
+    y: usize,
+    z: usize,
+    width: usize,
+    height: usize,
+    depth: usize,
+) -> usize {
+    let mut log2 = [
+        width.next_power_of_two().trailing_zeros(),
+        height.next_power_of_two().trailing_zeros(),
+        depth.next_power_of_two().trailing_zeros(),
+    ];
+    let mut coords = [x, y, z];
+    let (mut offset, mut shift) = (0, 0);
+    while log2.iter().any(|&l| l > 0) {
+        for (c, l) in coords.iter_mut().zip(log2.iter_mut()) {
+            if *l > 0 {
+                offset |= (*c & 1) << shift;
+                *c >>= 1;
+                shift += 1;
+                *l -= 1;
+            }
+        }
+    }
+
+    offset
+}
+
+fn expand5(v: u16) -> u8 {
+    ((v << 3) | (v >> 2)) as u8
+}
+
+fn expand4(v: u16) -> u8 {
+    ((v << 4) | v) as u8
+}
+
+#[derive(Debug, N, Clone, Copy)]
+#[repr(u8)]
+enum InternalFormat {
+    B8 = 0x81,
+    A1R5G5B5 = 0x82,
+    A4R4G4B4 = 0x83,
+    R5G6B5 = 0x84,
+    A8R8G8B8 = 0x85,
+    Dxt1 = 0x86,
+    Dxt23 = 0x87,
+    Dxt45 = 0x88,
+}
+
+impl InternalFormat {
+    fn from_format(format: u8) -> anyhow::Result<Self> {
+        Self::n(format & !(FLAG_LINEAR | FLAG_UNNORMALIZED))
+            .context(format!("Unknown CELL_GCM texture format 0x{format:x}"))
+    }
+
+    pub fn bpp(&self) -> usize {
+        match self {
+            Self::B8 => 8,
+            Self::A1R5G5B5 | Self::A4R4G4B4 | Self::R5G6B5 => 16,
+            Self::A8R8G8B8 => 32,
+            Self::Dxt1 => 4,
+            Self::Dxt23 | Self::Dxt45 => 8,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::Dxt1 | Self::Dxt23 | Self::Dxt45)
+    }
+
+    pub fn block_format(&self) -> Option<BlockFormat> {
+        match self {
+            Self::Dxt1 => Some(BlockFormat::Bc1),
+            Self::Dxt23 => Some(BlockFormat::Bc2),
+            Self::Dxt45 => Some(BlockFormat::Bc3),
+            _ => None,
+        }
+    }
+
+    /// Decodes a single (big endian) uncompressed pixel to RGBA
+    fn decode_pixel(&self, p: &[u8]) -> [u8; 4] {
+        match self {
+            Self::B8 => [p[0], p[0], p[0], 255],
+            Self::A8R8G8B8 => [p[1], p[2], p[3], p[0]],
+            Self::A1R5G5B5 => {
+                let v = u16::from_be_bytes([p[0], p[1]]);
+                [
+                    expand5((v >> 10) & 0x1f),
+                    expand5((v >> 5) & 0x1f),
+                    expand5(v & 0x1f),
+                    if v & 0x8000 != 0 { 255 } else { 0 },
+                ]
+            }
+            Self::A4R4G4B4 => {
+                let v = u16::from_be_bytes([p[0], p[1]]);
+                [
+                    expand4((v >> 8) & 0xf),
+                    expand4((v >> 4) & 0xf),
+                    expand4(v & 0xf),
+                    expand4(v >> 12),
+                ]
+            }
+            Self::R5G6B5 => {
+                let v = u16::from_be_bytes([p[0], p[1]]);
+                let g = (v >> 5) & 0x3f;
+                [
+                    expand5(v >> 11),
+                    ((g << 2) | (g >> 4)) as u8,
+                    expand5(v & 0x1f),
+                    255,
+                ]
+            }
+            Self::Dxt1 | Self::Dxt23 | Self::Dxt45 => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(input: &[u8], width: u32, height: u32, format: u8) -> anyhow::Result<RgbaImage> {
+        let mut output = RgbaImage::new(width, height);
+        Ps3TextureDecoder.decode(input, None, &mut output, width, height, 1, format, 0)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn swizzled_b8() {
+        // Morton order, with the axis that runs out of bits first dropping out (x0 y0 x1 x2)
+        let input: Vec<u8> = (0..16).collect();
+        let output = decode(&input, 8, 2, InternalFormat::B8 as u8).unwrap();
+        let expected = [[0, 1, 4, 5, 8, 9, 12, 13], [2, 3, 6, 7, 10, 11, 14, 15]];
+        for (y, row) in expected.iter().enumerate() {
+            for (x, &v) in row.iter().enumerate() {
+                assert_eq!(output.get_pixel(x as u32, y as u32).0, [v, v, v, 255]);
+            }
+        }
+
+        // Linear textures are stored row by row
+        let output = decode(&input, 8, 2, InternalFormat::B8 as u8 | FLAG_LINEAR).unwrap();
+        assert_eq!(output.get_pixel(2, 1).0, [10, 10, 10, 255]);
+    }
+
+    #[test]
+    fn pixel_formats() {
+        // ARGB, big endian
+        let output = decode(
+            &[0x80, 0x11, 0x22, 0x33],
+            1,
+            1,
+            InternalFormat::A8R8G8B8 as u8 | FLAG_LINEAR,
+        )
+        .unwrap();
+        assert_eq!(output.get_pixel(0, 0).0, [0x11, 0x22, 0x33, 0x80]);
+
+        let output = decode(
+            &0xfc00u16.to_be_bytes(),
+            1,
+            1,
+            InternalFormat::A1R5G5B5 as u8 | FLAG_LINEAR,
+        )
+        .unwrap();
+        assert_eq!(output.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+        let output = decode(
+            &0x07e0u16.to_be_bytes(),
+            1,
+            1,
+            InternalFormat::R5G6B5 as u8 | FLAG_LINEAR,
+        )
+        .unwrap();
+        assert_eq!(output.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn swizzled_npot_bounds() {
+        // A 3x3 swizzled texture is addressed as 4x4, which doesn't fit in the unpadded size
+        let input = vec![0u8; 9];
+        assert!(decode(&input, 3, 3, InternalFormat::B8 as u8).is_err());
+        assert!(decode(&[0u8; 16], 3, 3, InternalFormat::B8 as u8).is_ok());
+    }
+}
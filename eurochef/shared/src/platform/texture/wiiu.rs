@@ -0,0 +1,474 @@
+use std::sync::Once;
+
+use anyhow::Context;
+use enumn::N;
+use image::RgbaImage;
+use tracing::warn;
+
+use super::{BlockFormat, TextureDecoder};
+
+/// Texture decoder for GX2 surfaces.
+///
+/// Formats are assumed to be native `GX2SurfaceFormat` values (without the sRGB/signed bits). Surfaces are
+/// expected to use the default tile mode (2D thin), with a swizzle of 0.
+/// Neither assumption has been checked against game files yet, so a warning is logged the first time a texture is decoded
+pub struct WiiUTextureDecoder;
+
+static UNVERIFIED_WARNING: Once = Once::new();
+
+impl TextureDecoder for WiiUTextureDecoder {
+    fn get_data_size(
+        &self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<usize> {
+        let fmt = InternalFormat::from_format(format)?;
+        let surface = SurfaceLayout::new(fmt, width as usize, height as usize);
+
+        Ok(surface.slice_size() * depth as usize)
+    }
+
+    fn decode(
+        &self,
+        input: &[u8],
+        _clut: Option<&[u8]>,
+        output: &mut RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        _version: u32,
+    ) -> anyhow::Result<()> {
+        let fmt = InternalFormat::from_format(format)?;
+        UNVERIFIED_WARNING.call_once(|| {
+            warn!("Wii U texture formats haven't been verified against game files, textures may decode incorrectly")
+        });
+
+        anyhow::ensure!(output.len() == (width as usize * height as usize * depth as usize) * 4);
+
+        // Volume slices are assumed to use thin tiling as well, with every slice rotating the banks and pipes
+        let surface = SurfaceLayout::new(fmt, width as usize, height as usize);
+        let data_size = surface.slice_size() * depth as usize;
+        anyhow::ensure!(
+            input.len() >= data_size,
+            "Expected at least {data_size} bytes of texture data, got {}",
+            input.len()
+        );
+
+        let (width, height) = (width as usize, height as usize);
+        let output: &mut [u8] = output;
+        for z in 0..depth as usize {
+            let untiled = surface.untile(input, z);
+            let buffer = &mut output[z * width * height * 4..(z + 1) * width * height * 4];
+            match fmt {
+                InternalFormat::Bc1 => {
+                    squish::Format::Bc1.decompress(&untiled, width, height, buffer)
+                }
+                InternalFormat::Bc2 => {
+                    squish::Format::Bc2.decompress(&untiled, width, height, buffer)
+                }
+                InternalFormat::Bc3 => {
+                    squish::Format::Bc3.decompress(&untiled, width, height, buffer)
+                }
+                InternalFormat::Bc4 | InternalFormat::Bc5 => {
+                    let block_size = fmt.element_bytes();
+                    let blocks_x = (width + 3) / 4;
+                    for (i, block) in untiled.chunks_exact(block_size).enumerate() {
+                        let red = decode_bc4_block(&block[..8]);
+                        let green = if block_size == 16 {
+                            Some(decode_bc4_block(&block[8..]))
+                        } else {
+                            None
+                        };
+
+                        let (bx, by) = ((i % blocks_x) * 4, (i / blocks_x) * 4);
+                        for p in 0..16 {
+                            let (x, y) = (bx + p % 4, by + p / 4);
+                            if x >= width || y >= height {
+                                continue;
+                            }
+
+                            let o = (y * width + x) * 4;
+                            buffer[o..o + 4].copy_from_slice(&match green {
+                                Some(green) => [red[p], green[p], 0, 255],
+                                None => [red[p], red[p], red[p], 255],
+                            });
+                        }
+                    }
+                }
+                _ => {
+                    let bytes_per_pixel = fmt.element_bytes();
+                    for (i, p) in untiled.chunks_exact(bytes_per_pixel).enumerate() {
+                        buffer[i * 4..i * 4 + 4].copy_from_slice(&fmt.decode_pixel(p));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn extract_blocks(
+        &self,
+        input: &[u8],
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+        let fmt = InternalFormat::from_format(format)?;
+        let Some(block_format) = fmt.block_format() else {
+            return Ok(None);
+        };
+
+        let surface = SurfaceLayout::new(fmt, width as usize, height as usize);
+        anyhow::ensure!(input.len() >= surface.slice_size() * depth as usize);
+
+        let blocks = (0..depth as usize)
+            .flat_map(|z| surface.untile(input, z))
+            .collect();
+        Ok(Some((block_format, blocks)))
+    }
+}
+
+/// Decodes a single BC4 block (the same layout as a BC3 alpha block) to 16 values
+fn decode_bc4_block(block: &[u8]) -> [u8; 16] {
+    let (e0, e1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [e0, e1, 0, 0, 0, 0, 0, 0];
+    if e0 > e1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as u32) * e0 + i as u32 * e1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as u32) * e0 + i as u32 * e1) / 5;
+        }
+        palette[7] = 255;
+    }
+
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+
+    let mut out = [0u8; 16];
+    for (i, v) in out.iter_mut().enumerate() {
+        *v = palette[((indices >> (i * 3)) & 7) as usize] as u8;
+    }
+
+    out
+}
+
+const PIPE_INTERLEAVE_BYTES: usize = 256;
+const NUM_PIPES: usize = 2;
+const NUM_BANKS: usize = 4;
+const MICRO_TILE_SIZE: usize = 8;
+const MACRO_TILE_WIDTH: usize = 8 * NUM_BANKS;
+const MACRO_TILE_HEIGHT: usize = 8 * NUM_PIPES;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TileMode {
+    /// `GX2_TILE_MODE_1D_TILED_THIN1`
+    Micro,
+    /// `GX2_TILE_MODE_2D_TILED_THIN1`
+    Macro,
+}
+
+/// Layout of a single slice of a GX2 surface, in elements (pixels or 4x4 blocks)
+struct SurfaceLayout {
+    tile_mode: TileMode,
+    /// Bits per element
+    bpp: usize,
+    width: usize,
+    height: usize,
+    pitch: usize,
+    aligned_height: usize,
+}
+
+impl SurfaceLayout {
+    fn new(fmt: InternalFormat, width: usize, height: usize) -> Self {
+        let bpp = fmt.element_bytes() * 8;
+        let (width, height) = if fmt.is_compressed() {
+            ((width + 3) / 4, (height + 3) / 4)
+        } else {
+            (width, height)
+        };
+
+        // Surfaces that are smaller than a macro tile get degraded to micro tiling
+        let macro_pitch_align =
+            MACRO_TILE_WIDTH.max(MACRO_TILE_WIDTH * (PIPE_INTERLEAVE_BYTES / 8) / bpp);
+        let (tile_mode, pitch_align, height_align) =
+            if width < macro_pitch_align || height < MACRO_TILE_HEIGHT {
+                (
+                    TileMode::Micro,
+                    MICRO_TILE_SIZE.max(PIPE_INTERLEAVE_BYTES / bpp),
+                    MICRO_TILE_SIZE,
+                )
+            } else {
+                (TileMode::Macro, macro_pitch_align, MACRO_TILE_HEIGHT)
+            };
+
+        Self {
+            tile_mode,
+            bpp,
+            width,
+            height,
+            pitch: align(width, pitch_align),
+            aligned_height: align(height, height_align),
+        }
+    }
+
+    fn slice_size(&self) -> usize {
+        self.pitch * self.aligned_height * self.bpp / 8
+    }
+
+    /// Copies the elements of a slice to a linear buffer without any padding
+    fn untile(&self, input: &[u8], slice: usize) -> Vec<u8> {
+        let element_bytes = self.bpp / 8;
+        let mut output = vec![0u8; self.width * self.height * element_bytes];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let src = self.element_address(x, y, slice);
+                let dst = (y * self.width + x) * element_bytes;
+                output[dst..dst + element_bytes].copy_from_slice(&input[src..src + element_bytes]);
+            }
+        }
+
+        output
+    }
+
+    /// Byte address of an element, as calculated by AddrLib for thin tile modes
+    fn element_address(&self, x: usize, y: usize, slice: usize) -> usize {
+        let micro_tile_bytes = self.bpp * MICRO_TILE_SIZE * MICRO_TILE_SIZE / 8;
+        let element_offset = self.bpp * pixel_index(x, y, self.bpp) / 8;
+
+        match self.tile_mode {
+            TileMode::Micro => {
+                let micro_tiles_per_row = self.pitch / MICRO_TILE_SIZE;
+                let micro_tile_offset = micro_tile_bytes
+                    * (x / MICRO_TILE_SIZE + (y / MICRO_TILE_SIZE) * micro_tiles_per_row);
+
+                slice * self.slice_size() + micro_tile_offset + element_offset
+            }
+            TileMode::Macro => {
+                let pipe = ((y >> 3) ^ (x >> 3)) & 1;
+                let bank_bit0 = ((y / (16 * NUM_PIPES)) ^ (x >> 3)) & 1;
+                let bank_bit1 = ((y / (8 * NUM_PIPES)) ^ (x >> 4)) & 1;
+                let bank = bank_bit0 | (bank_bit1 << 1);
+
+                // Slices are rotated across banks and pipes
+                let rotation = NUM_PIPES * ((NUM_BANKS >> 1) - 1);
+                let bank_pipe =
+                    ((pipe + NUM_PIPES * bank) ^ (slice * rotation)) % (NUM_PIPES * NUM_BANKS);
+                let (pipe, bank) = (bank_pipe % NUM_PIPES, bank_pipe / NUM_PIPES);
+
+                let macro_tile_bytes = self.bpp * MACRO_TILE_WIDTH * MACRO_TILE_HEIGHT / 8;
+                let macro_tiles_per_row = self.pitch / MACRO_TILE_WIDTH;
+                let macro_tile_offset = macro_tile_bytes
+                    * (x / MACRO_TILE_WIDTH + macro_tiles_per_row * (y / MACRO_TILE_HEIGHT));
+                let slice_offset = slice * self.slice_size();
+
+                // Pipe and bank bits are inserted right above the pipe interleave
+                let total_offset = element_offset + ((macro_tile_offset + slice_offset) >> 3);
+                let offset_high = (total_offset & !(PIPE_INTERLEAVE_BYTES - 1)) << 3;
+                let offset_low = total_offset & (PIPE_INTERLEAVE_BYTES - 1);
+
+                (bank << 9) | (pipe << 8) | offset_low | offset_high
+            }
+        }
+    }
+}
+
+/// Index of an element within an 8x8 micro tile
+fn pixel_index(x: usize, y: usize, bpp: usize) -> usize {
+    let (x0, x1, x2) = (x & 1, (x >> 1) & 1, (x >> 2) & 1);
+    let (y0, y1, y2) = (y & 1, (y >> 1) & 1, (y >> 2) & 1);
+
+    let bits = match bpp {
+        8 => [x0, x1, x2, y1, y0, y2],
+        16 => [x0, x1, x2, y0, y1, y2],
+        64 => [x0, y0, x1, x2, y1, y2],
+        128 => [y0, x0, x1, x2, y1, y2],
+        _ => [x0, x1, y0, x2, y1, y2],
+    };
+
+    bits.iter()
+        .enumerate()
+        .fold(0, |acc, (i, b)| acc | (b << i))
+}
+
+fn align(v: usize, alignment: usize) -> usize {
+    (v + alignment - 1) / alignment * alignment
+}
+
+fn expand5(v: u16) -> u8 {
+    ((v << 3) | (v >> 2)) as u8
+}
+
+fn expand4(v: u16) -> u8 {
+    ((v << 4) | v) as u8
+}
+
+#[derive(Debug, N, Clone, Copy)]
+#[repr(u8)]
+enum InternalFormat {
+    R5G6B5 = 0x08,
+    R5G5B5A1 = 0x0a,
+    R4G4B4A4 = 0x0b,
+    R8G8B8A8 = 0x1a,
+    Bc1 = 0x31,
+    Bc2 = 0x32,
+    Bc3 = 0x33,
+    Bc4 = 0x34,
+    Bc5 = 0x35,
+}
+
+impl InternalFormat {
+    fn from_format(format: u8) -> anyhow::Result<Self> {
+        Self::n(format).context(format!("Unknown GX2 surface format 0x{format:x}"))
+    }
+
+    /// Size of a single pixel, or a 4x4 block for compressed formats
+    pub fn element_bytes(&self) -> usize {
+        match self {
+            Self::R5G6B5 | Self::R5G5B5A1 | Self::R4G4B4A4 => 2,
+            Self::R8G8B8A8 => 4,
+            Self::Bc1 | Self::Bc4 => 8,
+            Self::Bc2 | Self::Bc3 | Self::Bc5 => 16,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            Self::Bc1 | Self::Bc2 | Self::Bc3 | Self::Bc4 | Self::Bc5
+        )
+    }
+
+    pub fn block_format(&self) -> Option<BlockFormat> {
+        match self {
+            Self::Bc1 => Some(BlockFormat::Bc1),
+            Self::Bc2 => Some(BlockFormat::Bc2),
+            Self::Bc3 => Some(BlockFormat::Bc3),
+            Self::Bc4 => Some(BlockFormat::Bc4),
+            Self::Bc5 => Some(BlockFormat::Bc5),
+            _ => None,
+        }
+    }
+
+    /// Decodes a single (little endian) uncompressed pixel to RGBA
+    fn decode_pixel(&self, p: &[u8]) -> [u8; 4] {
+        match self {
+            Self::R8G8B8A8 => [p[0], p[1], p[2], p[3]],
+            Self::R5G6B5 => {
+                let v = u16::from_le_bytes([p[0], p[1]]);
+                let g = (v >> 5) & 0x3f;
+                [
+                    expand5(v >> 11),
+                    ((g << 2) | (g >> 4)) as u8,
+                    expand5(v & 0x1f),
+                    255,
+                ]
+            }
+            Self::R5G5B5A1 => {
+                let v = u16::from_le_bytes([p[0], p[1]]);
+                [
+                    expand5(v & 0x1f),
+                    expand5((v >> 5) & 0x1f),
+                    expand5((v >> 10) & 0x1f),
+                    if v & 0x8000 != 0 { 255 } else { 0 },
+                ]
+            }
+            Self::R4G4B4A4 => {
+                let v = u16::from_le_bytes([p[0], p[1]]);
+                [
+                    expand4(v & 0xf),
+                    expand4((v >> 4) & 0xf),
+                    expand4((v >> 8) & 0xf),
+                    expand4(v >> 12),
+                ]
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untile_roundtrip() {
+        // Every element of a tiled surface should map to a unique address within the surface
+        for (fmt, width, height) in [
+            (InternalFormat::R8G8B8A8, 64, 32),
+            (InternalFormat::R8G8B8A8, 16, 16),
+            (InternalFormat::Bc1, 256, 128),
+            (InternalFormat::Bc3, 128, 64),
+        ] {
+            let surface = SurfaceLayout::new(fmt, width, height);
+            let element_bytes = surface.bpp / 8;
+            let mut seen = vec![false; surface.slice_size() / element_bytes];
+            for y in 0..surface.height {
+                for x in 0..surface.width {
+                    let address = surface.element_address(x, y, 0);
+                    assert_eq!(address % element_bytes, 0);
+                    assert!(!std::mem::replace(&mut seen[address / element_bytes], true));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn micro_tiled_rgba8() {
+        // 16x8 surface, two 8x8 micro tiles. 32 bpp elements are ordered x0 x1 y0 x2 y1 y2 within a tile
+        let surface = SurfaceLayout::new(InternalFormat::R8G8B8A8, 16, 8);
+        assert_eq!(surface.tile_mode, TileMode::Micro);
+        for (x, y, address) in [
+            (1, 0, 4),
+            (2, 0, 8),
+            (0, 1, 16),
+            (4, 0, 32),
+            (0, 2, 64),
+            (0, 4, 128),
+            (8, 0, 256),
+        ] {
+            assert_eq!(surface.element_address(x, y, 0), address, "({x}, {y})");
+        }
+
+        let input: Vec<u8> = (0..surface.slice_size() / 4)
+            .flat_map(|i| [i as u8, 0, 0, 255])
+            .collect();
+        let mut output = RgbaImage::new(16, 8);
+        WiiUTextureDecoder
+            .decode(&input, None, &mut output, 16, 8, 1, 0x1a, 0)
+            .unwrap();
+        assert_eq!(output.get_pixel(0, 1).0, [4, 0, 0, 255]);
+        assert_eq!(output.get_pixel(9, 0).0, [65, 0, 0, 255]);
+    }
+
+    #[test]
+    fn micro_tiled_bc1() {
+        // 64 bpp elements are ordered x0 y0 x1 x2 y1 y2, so the second 2x2 group of blocks starts at element 4
+        let input: Vec<u8> = (0..512u32 / 8).flat_map(|i| [i as u8; 8]).collect();
+        let (format, blocks) = WiiUTextureDecoder
+            .extract_blocks(&input, 16, 4, 1, 0x31)
+            .unwrap()
+            .unwrap();
+        assert_eq!(format, BlockFormat::Bc1);
+        let order: Vec<u8> = blocks.chunks_exact(8).map(|b| b[0]).collect();
+        assert_eq!(order, [0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn macro_tiled_pipe_bank() {
+        // The second micro tile of a macro tile goes to pipe 1, bank 1
+        let surface = SurfaceLayout::new(InternalFormat::R8G8B8A8, 32, 16);
+        assert_eq!(surface.tile_mode, TileMode::Macro);
+        assert_eq!(surface.element_address(0, 0, 0), 0);
+        assert_eq!(surface.element_address(1, 0, 0), 4);
+        assert_eq!(surface.element_address(8, 0, 0), (1 << 9) | (1 << 8));
+        assert_eq!(surface.element_address(0, 8, 0), 1 << 8);
+    }
+}
@@ -2,7 +2,10 @@ use anyhow::Context;
 use enumn::N;
 use image::RgbaImage;
 
-use super::TextureDecoder;
+use super::{
+    copy_blocks, decode_slices, encode_slices, quantize, BlockFormat, TextureDecoder,
+    TextureEncoder,
+};
 
 pub struct XboxTextureDecoder;
 
@@ -14,10 +17,15 @@ impl TextureDecoder for XboxTextureDecoder {
         depth: u32,
         format: u8,
     ) -> anyhow::Result<usize> {
-        let bits = (width as usize * height as usize * depth as usize)
-            * InternalFormat::n(format)
-                .context(format!("Invalid format 0x{format:x}"))?
-                .bpp();
+        let fmt = InternalFormat::n(format).context(format!("Invalid format 0x{format:x}"))?;
+        // Compressed formats always store whole 4x4 blocks
+        let (width, height) = if fmt.is_compressed() {
+            ((width + 3) & !3, (height + 3) & !3)
+        } else {
+            (width, height)
+        };
+
+        let bits = (width as usize * height as usize * depth as usize) * fmt.bpp();
 
         Ok((bits + 7) / 8)
     }
@@ -38,6 +46,22 @@ impl TextureDecoder for XboxTextureDecoder {
 
         anyhow::ensure!(output.len() == (width as usize * height as usize * depth as usize) * 4);
 
+        if depth > 1 && !fmt.is_swizzled() {
+            // Volume slices are stored one after another, swizzled volumes are handled below
+            let slice_size = self.get_data_size(width, height, 1, format)?;
+            return decode_slices(
+                input,
+                output,
+                width,
+                height,
+                depth,
+                slice_size,
+                |input, output| {
+                    self.decode(input, _clut, output, width, height, 1, format, _version)
+                },
+            );
+        }
+
         let mut buffer = vec![0u8; output.len()];
         match fmt {
             InternalFormat::Dxt1
@@ -105,7 +129,7 @@ impl TextureDecoder for XboxTextureDecoder {
             }
             InternalFormat::P8 => {
                 let clut: &[[u8; 4]] = bytemuck::cast_slice(&input[input.len() - 1024..]);
-                for y in 0..height {
+                for y in 0..height * depth {
                     for x in 0..width {
                         let byte = input[(y * width + x) as usize];
                         let pixel = clut[byte as usize];
@@ -120,13 +144,20 @@ impl TextureDecoder for XboxTextureDecoder {
 
         // TODO: Using an intermediate buffer is inefficient, we should just swizzle when decoding.
         if fmt.is_swizzled() {
-            for y in 0..height {
-                for x in 0..width {
-                    let load_offset = deswizzle(x, y, width, height) as usize;
+            for z in 0..depth {
+                for y in 0..height {
+                    for x in 0..width {
+                        let load_offset = if depth > 1 {
+                            deswizzle_3d(x, y, z, width, height, depth)
+                        } else {
+                            deswizzle(x, y, width, height)
+                        } as usize;
 
-                    let pixel = &buffer[load_offset * 4..load_offset * 4 + 4];
+                        let pixel = &buffer[load_offset * 4..load_offset * 4 + 4];
 
-                    output[(x, y)] = [pixel[0], pixel[1], pixel[2], pixel[3]].into();
+                        output[(x, z * height + y)] =
+                            [pixel[0], pixel[1], pixel[2], pixel[3]].into();
+                    }
                 }
             }
         } else {
@@ -135,6 +166,139 @@ impl TextureDecoder for XboxTextureDecoder {
 
         Ok(())
     }
+
+    fn extract_blocks(
+        &self,
+        input: &[u8],
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+        let fmt = InternalFormat::n(format).context(format!("Invalid format 0x{format:x}"))?;
+        match fmt.block_format() {
+            Some(block_format) => copy_blocks(input, block_format, width, height, depth),
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct XboxTextureEncoder;
+
+impl TextureEncoder for XboxTextureEncoder {
+    fn encode(
+        &self,
+        input: &RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        _version: u32,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let fmt = InternalFormat::n(format)
+            .ok_or(anyhow::anyhow!("Invalid texture format 0x{format:x}"))?;
+
+        anyhow::ensure!(input.len() == (width as usize * height as usize * depth as usize) * 4);
+
+        if depth > 1 && !fmt.is_swizzled() {
+            // Volume slices are stored one after another, swizzled volumes are handled below
+            let output = encode_slices(input, width, height, depth, |slice| {
+                Ok(self.encode(slice, width, height, 1, format, _version)?.0)
+            })?;
+            return Ok((output, None));
+        }
+
+        let swizzled;
+        let input = if fmt.is_swizzled() {
+            let mut buffer = RgbaImage::new(width, height * depth);
+            for z in 0..depth {
+                for y in 0..height {
+                    for x in 0..width {
+                        let store_offset = if depth > 1 {
+                            deswizzle_3d(x, y, z, width, height, depth)
+                        } else {
+                            deswizzle(x, y, width, height)
+                        };
+
+                        buffer[(store_offset % width, store_offset / width)] =
+                            input[(x, z * height + y)];
+                    }
+                }
+            }
+
+            swizzled = buffer;
+            &swizzled
+        } else {
+            input
+        };
+
+        let output = match fmt {
+            InternalFormat::Dxt1
+            | InternalFormat::Dxt1Alpha
+            | InternalFormat::Dxt2
+            | InternalFormat::Dxt3
+            | InternalFormat::Dxt4
+            | InternalFormat::Dxt5 => {
+                let bcn = match fmt {
+                    InternalFormat::Dxt1 | InternalFormat::Dxt1Alpha => squish::Format::Bc1,
+                    InternalFormat::Dxt2 => squish::Format::Bc2,
+                    InternalFormat::Dxt3 => squish::Format::Bc2,
+                    InternalFormat::Dxt4 => squish::Format::Bc3,
+                    InternalFormat::Dxt5 => squish::Format::Bc3,
+                    _ => panic!("Invalid DXT format"),
+                };
+
+                let mut output = vec![0u8; bcn.compressed_size(width as usize, height as usize)];
+                bcn.compress(
+                    input,
+                    width as usize,
+                    height as usize,
+                    Default::default(),
+                    &mut output,
+                );
+                output
+            }
+            InternalFormat::ARGB8 | InternalFormat::ARGB8Linear => input
+                .pixels()
+                .flat_map(|p| [p[2], p[1], p[0], p[3]])
+                .collect(),
+            InternalFormat::ARGB4 => input
+                .pixels()
+                .flat_map(|p| [(p[1] & 0xf0) | (p[2] >> 4), (p[3] & 0xf0) | (p[0] >> 4)])
+                .collect(),
+            InternalFormat::RGB565 => input
+                .pixels()
+                .flat_map(|p| {
+                    (((p[2] as u16 >> 3) << 11) | ((p[1] as u16 >> 2) << 5) | (p[0] as u16 >> 3))
+                        .to_le_bytes()
+                })
+                .collect(),
+            InternalFormat::ARGB1555 | InternalFormat::XRGB1555 => input
+                .pixels()
+                .flat_map(|p| {
+                    (((p[3] as u16 >> 7) << 15)
+                        | ((p[0] as u16 >> 3) << 10)
+                        | ((p[1] as u16 >> 3) << 5)
+                        | (p[2] as u16 >> 3))
+                        .to_le_bytes()
+                })
+                .collect(),
+            InternalFormat::P8 => {
+                // The palette is stored right after the indices
+                let (palette, mut output) = quantize(input, 256);
+                let mut clut = [[0u8; 4]; 256];
+                clut[..palette.len()].copy_from_slice(&palette);
+                output.extend_from_slice(bytemuck::cast_slice(&clut));
+                output
+            }
+        };
+
+        Ok((output, None))
+    }
+
+    fn is_paletted(&self, format: u8) -> bool {
+        matches!(InternalFormat::n(format), Some(InternalFormat::P8))
+    }
 }
 
 // Implementation based on https://registry.khronos.org/DataFormat/specs/1.3/dataformat.1.3.inline.html#_more_complex_2_d_texel_addressing
@@ -155,6 +319,25 @@ fn deswizzle(x: u32, y: u32, width: u32, height: u32) -> u32 {
     offset
 }
 
+/// Volume textures interleave the bits of all three coordinates, until a dimension runs out of bits
+fn deswizzle_3d(x: u32, y: u32, z: u32, width: u32, height: u32, depth: u32) -> u32 {
+    let mut offset = 0;
+    let mut shift = 0;
+
+    let mut mask = 1;
+    while mask < width || mask < height || mask < depth {
+        for (v, size) in [(x, width), (y, height), (z, depth)] {
+            if mask < size {
+                offset |= u32::from(v & mask != 0) << shift;
+                shift += 1;
+            }
+        }
+        mask <<= 1;
+    }
+
+    offset
+}
+
 #[derive(Debug, N)]
 #[repr(u8)]
 enum InternalFormat {
@@ -189,6 +372,22 @@ impl InternalFormat {
         }
     }
 
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            Self::Dxt1 | Self::Dxt1Alpha | Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5
+        )
+    }
+
+    pub fn block_format(&self) -> Option<BlockFormat> {
+        match self {
+            Self::Dxt1 | Self::Dxt1Alpha => Some(BlockFormat::Bc1),
+            Self::Dxt2 | Self::Dxt3 => Some(BlockFormat::Bc2),
+            Self::Dxt4 | Self::Dxt5 => Some(BlockFormat::Bc3),
+            _ => None,
+        }
+    }
+
     pub fn is_swizzled(&self) -> bool {
         match self {
             Self::RGB565 | Self::ARGB4 | Self::ARGB8 | Self::P8 | Self::ARGB1555 => true,
@@ -2,7 +2,9 @@ use anyhow::Context;
 use enumn::N;
 use image::RgbaImage;
 
-use super::TextureDecoder;
+use super::{
+    copy_blocks, decode_slices, encode_slices, BlockFormat, TextureDecoder, TextureEncoder,
+};
 
 pub struct XenonTextureDecoder;
 
@@ -14,10 +16,15 @@ impl TextureDecoder for XenonTextureDecoder {
         depth: u32,
         format: u8,
     ) -> anyhow::Result<usize> {
-        let bits = (width as usize * height as usize * depth as usize)
-            * InternalFormat::n(format)
-                .context(format!("Invalid format 0x{format:x}"))?
-                .bpp();
+        let fmt = InternalFormat::n(format).context(format!("Invalid format 0x{format:x}"))?;
+        // Compressed formats always store whole 4x4 blocks
+        let (width, height) = if fmt.is_compressed() {
+            ((width + 3) & !3, (height + 3) & !3)
+        } else {
+            (width, height)
+        };
+
+        let bits = (width as usize * height as usize * depth as usize) * fmt.bpp();
 
         Ok((bits + 7) / 8)
     }
@@ -38,6 +45,22 @@ impl TextureDecoder for XenonTextureDecoder {
 
         anyhow::ensure!(output.len() == (width as usize * height as usize * depth as usize) * 4);
 
+        if depth > 1 {
+            // Volume slices are stored one after another
+            let slice_size = self.get_data_size(width, height, 1, format)?;
+            return decode_slices(
+                input,
+                output,
+                width,
+                height,
+                depth,
+                slice_size,
+                |input, output| {
+                    self.decode(input, _clut, output, width, height, 1, format, _version)
+                },
+            );
+        }
+
         let mut buffer = vec![0u8; output.len()];
         match fmt {
             InternalFormat::Dxt1
@@ -96,6 +119,98 @@ impl TextureDecoder for XenonTextureDecoder {
 
         Ok(())
     }
+
+    fn extract_blocks(
+        &self,
+        input: &[u8],
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+    ) -> anyhow::Result<Option<(BlockFormat, Vec<u8>)>> {
+        let fmt = InternalFormat::n(format).context(format!("Invalid format 0x{format:x}"))?;
+        let Some(block_format) = fmt.block_format() else {
+            return Ok(None);
+        };
+
+        let mut blocks = copy_blocks(input, block_format, width, height, depth)?;
+        if let Some((_, data)) = &mut blocks {
+            swap_endianness16(data);
+        }
+
+        Ok(blocks)
+    }
+}
+
+pub struct XenonTextureEncoder;
+
+impl TextureEncoder for XenonTextureEncoder {
+    fn encode(
+        &self,
+        input: &RgbaImage,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: u8,
+        _version: u32,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let fmt = InternalFormat::n(format)
+            .ok_or(anyhow::anyhow!("Invalid texture format 0x{format:x}"))?;
+
+        anyhow::ensure!(input.len() == (width as usize * height as usize * depth as usize) * 4);
+
+        if depth > 1 {
+            // Volume slices are stored one after another
+            let output = encode_slices(input, width, height, depth, |slice| {
+                Ok(self.encode(slice, width, height, 1, format, _version)?.0)
+            })?;
+            return Ok((output, None));
+        }
+
+        let output = match fmt {
+            InternalFormat::Dxt1
+            | InternalFormat::Dxt2
+            | InternalFormat::Dxt3
+            | InternalFormat::Dxt4
+            | InternalFormat::Dxt5 => {
+                let bcn = match fmt {
+                    InternalFormat::Dxt1 | InternalFormat::Dxt2 => squish::Format::Bc1,
+                    InternalFormat::Dxt3 => squish::Format::Bc2,
+                    InternalFormat::Dxt5 | InternalFormat::Dxt4 => squish::Format::Bc3,
+                    _ => panic!("Invalid DXT format"),
+                };
+
+                // Blocks are stored linearly, just like the decoder expects them
+                let mut output = vec![0u8; bcn.compressed_size(width as usize, height as usize)];
+                bcn.compress(
+                    input,
+                    width as usize,
+                    height as usize,
+                    Default::default(),
+                    &mut output,
+                );
+                swap_endianness16(&mut output);
+                output
+            }
+            InternalFormat::ARGB4 => input
+                .pixels()
+                .flat_map(|p| [(p[3] & 0xf0) | (p[0] >> 4), (p[1] & 0xf0) | (p[2] >> 4)])
+                .collect(),
+            InternalFormat::RGB565 => input
+                .pixels()
+                .flat_map(|p| {
+                    (((p[0] as u16 >> 3) << 11) | ((p[1] as u16 >> 2) << 5) | (p[2] as u16 >> 3))
+                        .to_be_bytes()
+                })
+                .collect(),
+            InternalFormat::ARGB8 => input
+                .pixels()
+                .flat_map(|p| [p[3], p[0], p[1], p[2]])
+                .collect(),
+        };
+
+        Ok((output, None))
+    }
 }
 
 fn swap_endianness16(buffer: &mut [u8]) {
@@ -127,4 +242,20 @@ impl InternalFormat {
             Self::Dxt3 | Self::Dxt4 | Self::Dxt5 => 8,
         }
     }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            Self::Dxt1 | Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5
+        )
+    }
+
+    pub fn block_format(&self) -> Option<BlockFormat> {
+        match self {
+            Self::Dxt1 | Self::Dxt2 => Some(BlockFormat::Bc1),
+            Self::Dxt3 => Some(BlockFormat::Bc2),
+            Self::Dxt4 | Self::Dxt5 => Some(BlockFormat::Bc3),
+            _ => None,
+        }
+    }
 }
@@ -1,13 +1,13 @@
 use anyhow::Context;
 use nohash_hasher::IntMap;
 use serde::Deserialize;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::{BufRead, Seek, SeekFrom, Write};
 
 use crate::maps::DefinitionDataType;
 use eurochef_edb::common::EXRelPtr;
 use eurochef_edb::{
-    binrw::BinReaderExt,
-    edb::EdbFile,
+    binrw::{BinReaderExt, NullWideString},
+    edb::{EdbFile, EdbPatcher},
     text::{EXGeoSpreadSheet, EXGeoTextItem},
     Hashcode,
 };
@@ -39,6 +39,8 @@ pub struct UXGeoTextItem {
     pub text: String,
     pub sound_hashcode: u32,
     // pub userdata: EXRelPtr,
+    /// Absolute location of the EXGeoTextItem
+    pub address: u32,
 }
 
 impl UXGeoSpreadsheet {
@@ -69,6 +71,7 @@ impl UXGeoSpreadsheet {
                         edb.seek(SeekFrom::Current(4)).unwrap(); // Skip commonobject
                         let text_count = edb.read_type::<u32>(edb.endian).unwrap();
                         for _i in 0..text_count {
+                            let address = edb.stream_position()? as u32;
                             let item = edb
                                 .read_type::<EXGeoTextItem>(edb.endian)
                                 .context("Failed to read textitem")?;
@@ -76,6 +79,7 @@ impl UXGeoSpreadsheet {
                                 hashcode: item.hashcode,
                                 text: item.string.to_string(),
                                 sound_hashcode: item.sound_hashcode,
+                                address,
                             });
                         }
 
@@ -131,7 +135,7 @@ impl UXGeoSpreadsheet {
             .find(|s| s.hashcode == section_hashcode)
             .ok_or(anyhow::anyhow!("Failed to find section"))?;
 
-        writeln!(writer, "section,hashcode,sound_hashcode,text")?;
+        writeln!(writer, "{TEXT_CSV_HEADER}")?;
         for item in &section.entries {
             writeln!(
                 writer,
@@ -147,6 +151,173 @@ impl UXGeoSpreadsheet {
     }
 }
 
+/// Column header of the CSV files written by [`UXGeoSpreadsheet::export_text_to_csv`]
+pub const TEXT_CSV_HEADER: &str = "section,hashcode,sound_hashcode,text";
+
+/// A single row of a CSV file written by [`UXGeoSpreadsheet::export_text_to_csv`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextCsvRow {
+    pub section: u32,
+    pub hashcode: u32,
+    pub sound_hashcode: u32,
+    pub text: String,
+}
+
+impl UXGeoSpreadsheet {
+    /// Reads the rows of a CSV file written by [`Self::export_text_to_csv`], skipping the header and comments
+    pub fn read_text_csv<R: BufRead>(reader: R) -> anyhow::Result<Vec<TextCsvRow>> {
+        let mut rows = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') || line == TEXT_CSV_HEADER {
+                continue;
+            }
+
+            rows.push(parse_text_csv_line(line).with_context(|| format!("Line {}", i + 1))?);
+        }
+
+        Ok(rows)
+    }
+
+    /// Checks if the first line after the leading comments is the text CSV column header
+    pub fn is_text_csv<R: BufRead>(reader: R) -> anyhow::Result<bool> {
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            return Ok(line == TEXT_CSV_HEADER);
+        }
+
+        Ok(false)
+    }
+
+    /// Applies the text and sound hashcodes from `rows` to matching entries.
+    /// Returns the indices of the sections that have changed
+    pub fn import_text(&mut self, rows: &[TextCsvRow]) -> anyhow::Result<Vec<usize>> {
+        let spreadsheet = match self {
+            UXGeoSpreadsheet::Text(v) => v,
+            _ => anyhow::bail!("Spreadsheet is not a text spreadsheet"),
+        };
+
+        let mut changed_sections = vec![];
+        for row in rows {
+            let Some((section_index, section)) = spreadsheet
+                .iter_mut()
+                .enumerate()
+                .find(|(_, s)| s.hashcode == row.section)
+            else {
+                continue;
+            };
+
+            let Some(item) = section
+                .entries
+                .iter_mut()
+                .find(|e| e.hashcode == row.hashcode)
+            else {
+                warn!(
+                    "Text item {:08x} doesn't exist in section {:08x}, skipping",
+                    row.hashcode, row.section
+                );
+                continue;
+            };
+
+            if item.text != row.text || item.sound_hashcode != row.sound_hashcode {
+                item.text = row.text.clone();
+                item.sound_hashcode = row.sound_hashcode;
+                if !changed_sections.contains(&section_index) {
+                    changed_sections.push(section_index);
+                }
+            }
+        }
+
+        Ok(changed_sections)
+    }
+}
+
+impl UXGeoTextSection {
+    /// Writes the text items of this section, rebuilding its string pool.
+    ///
+    /// The pool is written in place if the original strings were stored back-to-back and the new strings fit,
+    /// otherwise it is appended to the end of the file.
+    pub fn write(&self, edb: &mut EdbFile, writer: &mut EdbPatcher) -> anyhow::Result<()> {
+        let mut items = vec![];
+        let mut old_ranges = vec![];
+        for e in &self.entries {
+            edb.seek(SeekFrom::Start(e.address as u64))?;
+            let mut item = edb
+                .read_type::<EXGeoTextItem>(edb.endian)
+                .context("Failed to read textitem")?;
+
+            let start = item.string.offset_absolute();
+            old_ranges.push((start, start + (item.string.data_mut().len() as u64 + 1) * 2));
+            items.push(item);
+        }
+
+        old_ranges.sort();
+        old_ranges.dedup();
+        // Strings are only padded to keep them 4-byte aligned
+        let contiguous = old_ranges.windows(2).all(|w| w[1].0 <= w[0].1 + 3);
+        let old_start = old_ranges.first().map(|r| r.0).unwrap_or_default();
+        let old_end = old_ranges.iter().map(|r| r.1).max().unwrap_or_default();
+
+        let strings: Vec<NullWideString> = self
+            .entries
+            .iter()
+            .map(|e| NullWideString::from(e.text.as_str()))
+            .collect();
+        let pool_size: u64 = strings.iter().map(|s| (s.len() as u64 + 1) * 2).sum();
+
+        let mut address = if contiguous && pool_size <= old_end - old_start {
+            writer.write_at(old_start, &vec![0u8; (old_end - old_start) as usize], ())?;
+            old_start
+        } else {
+            writer.append_bytes(&vec![0u8; pool_size as usize]) as u64
+        };
+
+        for ((e, mut item), string) in self.entries.iter().zip(items).zip(strings) {
+            let size = (string.len() as u64 + 1) * 2;
+            item.string = EXRelPtr::new_in_place(address, size, string);
+            item.sound_hashcode = e.sound_hashcode;
+            writer.write_at(e.address as u64, &item, ())?;
+            address += size;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_text_csv_line(line: &str) -> anyhow::Result<TextCsvRow> {
+    let mut fields = line.splitn(4, ',');
+    let mut next_hashcode = |name: &str| -> anyhow::Result<u32> {
+        let field = fields
+            .next()
+            .with_context(|| format!("Missing {name} column"))?
+            .trim();
+        u32::from_str_radix(field, 16).with_context(|| format!("Invalid {name} '{field}'"))
+    };
+
+    let section = next_hashcode("section")?;
+    let hashcode = next_hashcode("hashcode")?;
+    let sound_hashcode = next_hashcode("sound_hashcode")?;
+
+    let text = fields.next().context("Missing text column")?;
+    let text = match text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => text.to_string(),
+    };
+
+    Ok(TextCsvRow {
+        section,
+        hashcode,
+        sound_hashcode,
+        text: text.replace("\\n", "\n"),
+    })
+}
+
 pub type SpreadsheetDefinitions = IntMap<Hashcode, SpreadsheetFileDefinition>;
 
 /// Represents all spreadsheets in a file
@@ -171,3 +342,91 @@ pub struct DataSheetColumn {
     #[serde(alias = "type", default)]
     pub dtype: DefinitionDataType,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use eurochef_edb::{binrw::BinWriterExt, versions::Platform};
+
+    use super::*;
+
+    /// Builds a minimal big endian (v259) EDB with a single text section containing "Hi" and "Yo"
+    fn build_edb() -> Vec<u8> {
+        let mut cur = Cursor::new(vec![0u8; 0x1c0]);
+        let mut w = |offset: u64, values: &[u32]| {
+            cur.seek(SeekFrom::Start(offset)).unwrap();
+            for v in values {
+                cur.write_be(v).unwrap();
+            }
+        };
+
+        w(0x0, &[0x47454F4D, 0x80000001, 259, 0, 0, 0x1c0, 0x1c0]);
+        // Refpointer list and spreadsheet list (1 entry each)
+        w(0x48, &[0x00010001, 0x100 - 0x4c]);
+        w(0x98, &[0x00010001, 0x120 - 0x9c]);
+        w(0x100, &[0, 0, 0x140, 0]);
+        w(0x120, &[0x1b000001, 0, 0x1a0, 0, 1]);
+        // Text items
+        w(0x140, &[0, 2]);
+        w(0x148, &[0x1c000001, 0x168 - 0x14c, 0, u32::MAX]);
+        w(0x158, &[0x1c000002, 0x170 - 0x15c, 0, u32::MAX]);
+        // String pool, padded to 4 bytes
+        w(0x168, &[0x00480069, 0x00000000, 0x0059006f, 0x00000000]);
+        // Spreadsheet with a single section
+        w(0x1a0, &[1, 0x1d000001, 0]);
+
+        cur.into_inner()
+    }
+
+    fn import(data: Vec<u8>, csv: &str) -> Vec<u8> {
+        let mut edb = EdbFile::new(Box::new(Cursor::new(data)), Platform::Pc).unwrap();
+        let mut spreadsheets = UXGeoSpreadsheet::read_all(&mut edb).unwrap();
+        let rows = UXGeoSpreadsheet::read_text_csv(csv.as_bytes()).unwrap();
+        assert_eq!(spreadsheets[0].1.import_text(&rows).unwrap(), vec![0]);
+
+        let UXGeoSpreadsheet::Text(sections) = &spreadsheets[0].1 else {
+            panic!("Not a text spreadsheet");
+        };
+
+        let mut writer = EdbPatcher::new(&mut edb).unwrap();
+        sections[0].write(&mut edb, &mut writer).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn read_text(data: Vec<u8>) -> Vec<String> {
+        let mut edb = EdbFile::new(Box::new(Cursor::new(data)), Platform::Pc).unwrap();
+        match &UXGeoSpreadsheet::read_all(&mut edb).unwrap()[0].1 {
+            UXGeoSpreadsheet::Text(s) => s[0].entries.iter().map(|e| e.text.clone()).collect(),
+            _ => panic!("Not a text spreadsheet"),
+        }
+    }
+
+    #[test]
+    fn import_text() {
+        let data = build_edb();
+
+        // Doesn't fit in the original pool, so it gets appended
+        let grown = import(
+            data.clone(),
+            "section,hashcode,sound_hashcode,text\n1d000001,1c000001,ffffffff,\"Hello, \"\"world\"\"\\n\"",
+        );
+        assert!(grown.len() > data.len());
+        assert_eq!(read_text(grown), vec!["Hello, \"world\"\n", "Yo"]);
+
+        // Fits, so the pool is rewritten in place
+        let shrunk = import(data.clone(), "1d000001,1c000002,ffffffff,\"A\"");
+        assert_eq!(shrunk.len(), data.len());
+        assert_eq!(read_text(shrunk), vec!["Hi", "A"]);
+    }
+
+    #[test]
+    fn detect_text_csv() {
+        let text = "# Section 1d000001\nsection,hashcode,sound_hashcode,text\n1d000001,1c000001,ffffffff,\"Hi\"\n";
+        assert!(UXGeoSpreadsheet::is_text_csv(text.as_bytes()).unwrap());
+
+        let data = "name,damage,speed\n1,2,3\n";
+        assert!(!UXGeoSpreadsheet::is_text_csv(data.as_bytes()).unwrap());
+        assert!(!UXGeoSpreadsheet::is_text_csv("data\n00ff\n".as_bytes()).unwrap());
+    }
+}
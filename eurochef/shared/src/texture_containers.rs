@@ -0,0 +1,538 @@
+//! DDS and KTX2 containers for decoded textures, keeping every mip level.
+//!
+//! Levels are either uncompressed RGBA8 or standard BC1-BC5 blocks. Volume textures are passed around as a vertical
+//! atlas of all slices (or all slices of blocks one after another), which matches the layout of a volume level in both containers
+
+use std::io::Write;
+
+use anyhow::Context;
+use image::RgbaImage;
+
+use crate::platform::texture::BlockFormat;
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDSD_DEPTH: u32 = 0x800000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const RGBA8_MASKS: [u32; 4] = [0x000000ff, 0x0000ff00, 0x00ff0000, 0xff000000];
+
+const KTX2_IDENTIFIER: &[u8; 12] = b"\xabKTX 20\xbb\r\n\x1a\n";
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+
+/// Pixel format of the levels passed to the writers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8,
+    Block(BlockFormat),
+}
+
+impl PixelFormat {
+    fn data_size(&self, width: u32, height: u32, depth: u32) -> usize {
+        match self {
+            Self::Rgba8 => (width * height * depth * 4) as usize,
+            Self::Block(b) => b.data_size(width, height, depth),
+        }
+    }
+
+    /// KTX2 level alignment, the least common multiple of the texel block size and 4
+    fn level_alignment(&self) -> usize {
+        match self {
+            Self::Rgba8 => 4,
+            Self::Block(b) => b.block_size(),
+        }
+    }
+
+    fn from_fourcc(fourcc: &[u8]) -> Option<Self> {
+        let block_format = match fourcc {
+            b"DXT1" => BlockFormat::Bc1,
+            b"DXT2" | b"DXT3" => BlockFormat::Bc2,
+            b"DXT4" | b"DXT5" => BlockFormat::Bc3,
+            b"ATI1" | b"BC4U" => BlockFormat::Bc4,
+            b"ATI2" | b"BC5U" => BlockFormat::Bc5,
+            _ => return None,
+        };
+
+        Some(Self::Block(block_format))
+    }
+
+    /// UNORM VkFormat, BC1 is stored with alpha
+    fn vk_format(&self) -> u32 {
+        match self {
+            Self::Rgba8 => VK_FORMAT_R8G8B8A8_UNORM,
+            Self::Block(BlockFormat::Bc1) => 133,
+            Self::Block(BlockFormat::Bc2) => 135,
+            Self::Block(BlockFormat::Bc3) => 137,
+            Self::Block(BlockFormat::Bc4) => 139,
+            Self::Block(BlockFormat::Bc5) => 141,
+        }
+    }
+
+    fn from_vk_format(format: u32) -> Option<Self> {
+        Some(match format {
+            VK_FORMAT_R8G8B8A8_UNORM | VK_FORMAT_R8G8B8A8_SRGB => Self::Rgba8,
+            // BC1 RGB/RGBA, UNORM/SRGB
+            131..=134 => Self::Block(BlockFormat::Bc1),
+            135 | 136 => Self::Block(BlockFormat::Bc2),
+            137 | 138 => Self::Block(BlockFormat::Bc3),
+            139 => Self::Block(BlockFormat::Bc4),
+            141 => Self::Block(BlockFormat::Bc5),
+            _ => return None,
+        })
+    }
+
+    /// Decodes a single level to RGBA
+    fn decode(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        depth: u32,
+    ) -> anyhow::Result<RgbaImage> {
+        let rgba = match self {
+            Self::Rgba8 => data.to_vec(),
+            Self::Block(b) => {
+                let format = match b {
+                    BlockFormat::Bc1 => squish::Format::Bc1,
+                    BlockFormat::Bc2 => squish::Format::Bc2,
+                    BlockFormat::Bc3 => squish::Format::Bc3,
+                    BlockFormat::Bc4 | BlockFormat::Bc5 => {
+                        anyhow::bail!("Decoding {b:?} textures is not supported")
+                    }
+                };
+
+                let (width, height) = (width as usize, height as usize);
+                let slice_size = b.data_size(width as u32, height as u32, 1);
+                let mut rgba = vec![0u8; width * height * depth as usize * 4];
+                for (z, slice) in rgba.chunks_exact_mut(width * height * 4).enumerate() {
+                    format.decompress(&data[z * slice_size..], width, height, slice);
+                }
+
+                rgba
+            }
+        };
+
+        Ok(RgbaImage::from_vec(width, height * depth, rgba).unwrap())
+    }
+}
+
+/// Writes a texture with all of its mip levels (starting at the top level) to a DDS file
+pub fn write_dds<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: PixelFormat,
+    levels: &[&[u8]],
+) -> anyhow::Result<()> {
+    check_levels(width, height, depth, format, levels)?;
+
+    let (pitch_flag, pitch) = match format {
+        PixelFormat::Rgba8 => (DDSD_PITCH, width * 4),
+        PixelFormat::Block(_) => (DDSD_LINEARSIZE, levels[0].len() as u32),
+    };
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | pitch_flag | DDSD_PIXELFORMAT;
+    let mut caps = DDSCAPS_TEXTURE;
+    if levels.len() > 1 {
+        flags |= DDSD_MIPMAPCOUNT;
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+
+    let mut caps2 = 0;
+    if depth > 1 {
+        flags |= DDSD_DEPTH;
+        caps |= DDSCAPS_COMPLEX;
+        caps2 |= DDSCAPS2_VOLUME;
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(DDS_MAGIC);
+    for v in [124, flags, height, width, pitch, depth, levels.len() as u32] {
+        header.extend_from_slice(&v.to_le_bytes());
+    }
+    header.extend_from_slice(&[0u8; 11 * 4]);
+
+    // Pixel format
+    match format {
+        PixelFormat::Rgba8 => {
+            for v in [32, DDPF_RGB | DDPF_ALPHAPIXELS, 0, 32] {
+                header.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in RGBA8_MASKS {
+                header.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        PixelFormat::Block(b) => {
+            for v in [32, DDPF_FOURCC] {
+                header.extend_from_slice(&v.to_le_bytes());
+            }
+            header.extend_from_slice(dds_fourcc(b));
+            header.extend_from_slice(&[0u8; 5 * 4]);
+        }
+    }
+
+    for v in [caps, caps2, 0, 0, 0] {
+        header.extend_from_slice(&v.to_le_bytes());
+    }
+
+    writer.write_all(&header)?;
+    for l in levels {
+        writer.write_all(l)?;
+    }
+
+    Ok(())
+}
+
+/// Reads every mip level from an RGBA8 or BC1-BC3 DDS file, decoded to RGBA
+pub fn read_dds(data: &[u8]) -> anyhow::Result<Vec<RgbaImage>> {
+    anyhow::ensure!(data.get(0..4) == Some(DDS_MAGIC), "Not a DDS file");
+    anyhow::ensure!(read_u32(data, 4)? == 124, "Invalid DDS header size");
+
+    let flags = read_u32(data, 8)?;
+    let height = read_u32(data, 12)?;
+    let width = read_u32(data, 16)?;
+    let depth = if flags & DDSD_DEPTH != 0 {
+        read_u32(data, 24)?.max(1)
+    } else {
+        1
+    };
+    let level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(data, 28)?.max(1)
+    } else {
+        1
+    };
+
+    let pixel_flags = read_u32(data, 80)?;
+    let masks = [
+        read_u32(data, 92)?,
+        read_u32(data, 96)?,
+        read_u32(data, 100)?,
+        read_u32(data, 104)?,
+    ];
+    let format = if pixel_flags & DDPF_FOURCC != 0 {
+        PixelFormat::from_fourcc(&data[84..88]).with_context(|| {
+            format!(
+                "Unsupported DDS FourCC {}",
+                String::from_utf8_lossy(&data[84..88])
+            )
+        })?
+    } else {
+        anyhow::ensure!(
+            pixel_flags & DDPF_RGB != 0 && read_u32(data, 88)? == 32 && masks == RGBA8_MASKS,
+            "Only RGBA8 and block compressed DDS files are supported"
+        );
+        PixelFormat::Rgba8
+    };
+
+    let mut offset = 128;
+    let mut levels = vec![];
+    for level in 0..level_count {
+        let (level_width, level_height, level_depth) =
+            level_dimensions(width, height, depth, level);
+        let size = format.data_size(level_width, level_height, level_depth);
+        let level_data = data
+            .get(offset..offset + size)
+            .with_context(|| format!("Mip level {level} is outside of the file"))?;
+        offset += size;
+
+        levels.push(format.decode(level_data, level_width, level_height, level_depth)?);
+    }
+
+    Ok(levels)
+}
+
+/// Writes a texture with all of its mip levels (starting at the top level) to a KTX2 file
+pub fn write_ktx2<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: PixelFormat,
+    levels: &[&[u8]],
+) -> anyhow::Result<()> {
+    check_levels(width, height, depth, format, levels)?;
+
+    let dfd = match format {
+        PixelFormat::Rgba8 => rgba8_dfd(),
+        PixelFormat::Block(b) => block_dfd(b),
+    };
+    let level_index_size = levels.len() * 24;
+    let dfd_offset = 80 + level_index_size;
+
+    // Level data is stored from the smallest to the largest level
+    let mut level_index = vec![(0u64, 0u64); levels.len()];
+    let alignment = format.level_alignment();
+    let mut offset = dfd_offset + dfd.len();
+    for (i, l) in levels.iter().enumerate().rev() {
+        offset = align(offset, alignment);
+        level_index[i] = (offset as u64, l.len() as u64);
+        offset += l.len();
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(KTX2_IDENTIFIER);
+    for v in [
+        format.vk_format(),
+        1, // typeSize
+        width,
+        height,
+        if depth > 1 { depth } else { 0 }, // pixelDepth
+        0,                                 // layerCount
+        1,                                 // faceCount
+        levels.len() as u32,
+        0, // supercompressionScheme
+        dfd_offset as u32,
+        dfd.len() as u32,
+        0, // kvdByteOffset
+        0, // kvdByteLength
+    ] {
+        header.extend_from_slice(&v.to_le_bytes());
+    }
+    header.extend_from_slice(&[0u8; 16]); // sgdByteOffset, sgdByteLength
+
+    for (offset, length) in level_index {
+        for v in [offset, length, length] {
+            header.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    header.extend(dfd);
+
+    let mut data = header;
+    for l in levels.iter().rev() {
+        data.resize(align(data.len(), alignment), 0);
+        data.extend_from_slice(l);
+    }
+
+    writer.write_all(&data)?;
+
+    Ok(())
+}
+
+fn align(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+/// Reads every mip level from an RGBA8 or BC1-BC3 KTX2 file, decoded to RGBA
+pub fn read_ktx2(data: &[u8]) -> anyhow::Result<Vec<RgbaImage>> {
+    anyhow::ensure!(data.get(0..12) == Some(KTX2_IDENTIFIER), "Not a KTX2 file");
+
+    let vk_format = read_u32(data, 12)?;
+    let format = PixelFormat::from_vk_format(vk_format).with_context(|| {
+        format!(
+            "Only RGBA8 and block compressed KTX2 files are supported (got VkFormat {vk_format})"
+        )
+    })?;
+    anyhow::ensure!(
+        read_u32(data, 44)? == 0,
+        "Supercompressed KTX2 files are not supported"
+    );
+
+    let width = read_u32(data, 20)?;
+    let height = read_u32(data, 24)?.max(1);
+    let depth = read_u32(data, 28)?.max(1);
+    let level_count = read_u32(data, 40)?.max(1);
+
+    let mut levels = vec![];
+    for level in 0..level_count {
+        let index = 80 + level as usize * 24;
+        let offset = read_u32(data, index)? as usize;
+        let (level_width, level_height, level_depth) =
+            level_dimensions(width, height, depth, level);
+        let size = format.data_size(level_width, level_height, level_depth);
+        let level_data = data
+            .get(offset..offset + size)
+            .with_context(|| format!("Mip level {level} is outside of the file"))?;
+
+        levels.push(format.decode(level_data, level_width, level_height, level_depth)?);
+    }
+
+    Ok(levels)
+}
+
+fn check_levels(
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: PixelFormat,
+    levels: &[&[u8]],
+) -> anyhow::Result<()> {
+    anyhow::ensure!(!levels.is_empty(), "Texture has no data");
+    for (level, l) in levels.iter().enumerate() {
+        let (level_width, level_height, level_depth) =
+            level_dimensions(width, height, depth, level as u32);
+        anyhow::ensure!(
+            l.len() == format.data_size(level_width, level_height, level_depth),
+            "Mip level {level} has the wrong size (expected {level_width}x{level_height}x{level_depth})"
+        );
+    }
+
+    Ok(())
+}
+
+fn level_dimensions(width: u32, height: u32, depth: u32, level: u32) -> (u32, u32, u32) {
+    (
+        (width >> level).max(1),
+        (height >> level).max(1),
+        (depth >> level).max(1),
+    )
+}
+
+/// Basic data format descriptor for R8G8B8A8_UNORM
+fn rgba8_dfd() -> Vec<u8> {
+    let mut dfd = vec![];
+    dfd.extend_from_slice(&92u32.to_le_bytes()); // dfdTotalSize
+    dfd.extend_from_slice(&0u32.to_le_bytes()); // vendorId, descriptorType
+    dfd.extend_from_slice(&2u16.to_le_bytes()); // versionNumber
+    dfd.extend_from_slice(&88u16.to_le_bytes()); // descriptorBlockSize
+
+    // RGBSDA color model, BT.709 primaries, linear transfer function, straight alpha
+    dfd.extend_from_slice(&[1, 1, 1, 0]);
+    dfd.extend_from_slice(&[0, 0, 0, 0]); // texelBlockDimension
+    dfd.extend_from_slice(&[4, 0, 0, 0, 0, 0, 0, 0]); // bytesPlane
+
+    for (i, channel) in [0u8, 1, 2, 15].into_iter().enumerate() {
+        dfd.extend_from_slice(&(i as u16 * 8).to_le_bytes()); // bitOffset
+        dfd.extend_from_slice(&[7, channel]); // bitLength - 1, channelType
+        dfd.extend_from_slice(&[0, 0, 0, 0]); // samplePosition
+        dfd.extend_from_slice(&0u32.to_le_bytes()); // sampleLower
+        dfd.extend_from_slice(&255u32.to_le_bytes()); // sampleUpper
+    }
+
+    dfd
+}
+
+fn dds_fourcc(block_format: BlockFormat) -> &'static [u8; 4] {
+    match block_format {
+        BlockFormat::Bc1 => b"DXT1",
+        BlockFormat::Bc2 => b"DXT3",
+        BlockFormat::Bc3 => b"DXT5",
+        BlockFormat::Bc4 => b"ATI1",
+        BlockFormat::Bc5 => b"ATI2",
+    }
+}
+
+/// Basic data format descriptor for a BCn format
+fn block_dfd(block_format: BlockFormat) -> Vec<u8> {
+    // Channel type and bit range of every sample
+    let (color_model, samples): (u8, &[(u8, u16)]) = match block_format {
+        BlockFormat::Bc1 => (128, &[(0, 0), (15, 0)]),
+        BlockFormat::Bc2 => (129, &[(15, 0), (0, 64)]),
+        BlockFormat::Bc3 => (130, &[(15, 0), (0, 64)]),
+        BlockFormat::Bc4 => (131, &[(0, 0)]),
+        BlockFormat::Bc5 => (132, &[(0, 0), (1, 64)]),
+    };
+
+    let block_size = 24 + samples.len() * 16;
+    let mut dfd = vec![];
+    dfd.extend_from_slice(&(block_size as u32 + 4).to_le_bytes()); // dfdTotalSize
+    dfd.extend_from_slice(&0u32.to_le_bytes()); // vendorId, descriptorType
+    dfd.extend_from_slice(&2u16.to_le_bytes()); // versionNumber
+    dfd.extend_from_slice(&(block_size as u16).to_le_bytes()); // descriptorBlockSize
+
+    // BT.709 primaries, linear transfer function, straight alpha
+    dfd.extend_from_slice(&[color_model, 1, 1, 0]);
+    dfd.extend_from_slice(&[3, 3, 0, 0]); // texelBlockDimension
+    dfd.extend_from_slice(&[block_format.block_size() as u8, 0, 0, 0, 0, 0, 0, 0]); // bytesPlane
+
+    // Every sample covers 64 bits, BC1 stores alpha in the same bits as the color
+    for &(channel, offset) in samples {
+        dfd.extend_from_slice(&offset.to_le_bytes()); // bitOffset
+        dfd.extend_from_slice(&[63, channel]); // bitLength - 1, channelType
+        dfd.extend_from_slice(&[0, 0, 0, 0]); // samplePosition
+        dfd.extend_from_slice(&0u32.to_le_bytes()); // sampleLower
+        dfd.extend_from_slice(&u32::MAX.to_le_bytes()); // sampleUpper
+    }
+
+    dfd
+}
+
+fn read_u32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    Ok(u32::from_le_bytes(
+        data.get(offset..offset + 4)
+            .context("Unexpected end of file")?
+            .try_into()
+            .unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_mips() {
+        // A regular texture and a volume texture
+        for (width, height, depth) in [(8, 4, 1), (4, 4, 4)] {
+            let levels: Vec<Vec<u8>> = (0..3)
+                .map(|level| {
+                    let (w, h, d) = level_dimensions(width, height, depth, level);
+                    (0..w * h * d * 4)
+                        .map(|i| (i as u8).wrapping_mul(level as u8 + 1))
+                        .collect()
+                })
+                .collect();
+            let levels: Vec<&[u8]> = levels.iter().map(|l| l.as_slice()).collect();
+
+            let mut dds = vec![];
+            write_dds(&mut dds, width, height, depth, PixelFormat::Rgba8, &levels).unwrap();
+            let mut ktx2 = vec![];
+            write_ktx2(&mut ktx2, width, height, depth, PixelFormat::Rgba8, &levels).unwrap();
+
+            for decoded in [read_dds(&dds).unwrap(), read_ktx2(&ktx2).unwrap()] {
+                assert_eq!(decoded.len(), levels.len());
+                for (level, (d, l)) in decoded.iter().zip(&levels).enumerate() {
+                    assert_eq!(d.as_raw().as_slice(), *l, "level {level}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_blocks() {
+        // BC1 blocks of a solid color, for a 8x8 texture with 4 levels
+        let color = [0xff, 0x00, 0x00, 0xff];
+        let block = [0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0];
+        let levels: Vec<Vec<u8>> = [4, 1, 1, 1].iter().map(|&n| block.repeat(n)).collect();
+        let levels: Vec<&[u8]> = levels.iter().map(|l| l.as_slice()).collect();
+        let format = PixelFormat::Block(BlockFormat::Bc1);
+
+        let mut dds = vec![];
+        write_dds(&mut dds, 8, 8, 1, format, &levels).unwrap();
+        let mut ktx2 = vec![];
+        write_ktx2(&mut ktx2, 8, 8, 1, format, &levels).unwrap();
+
+        // Levels must be aligned to the 8 byte BC1 block size
+        for i in 0..levels.len() {
+            let offset = 80 + i * 24;
+            let level_offset = u64::from_le_bytes(ktx2[offset..offset + 8].try_into().unwrap());
+            assert_eq!(level_offset % 8, 0, "level {i}");
+        }
+
+        // BC1 levels must be aligned to the 8 byte block size
+        for i in 0..levels.len() {
+            let offset = 80 + i * 24;
+            let level_offset = u64::from_le_bytes(ktx2[offset..offset + 8].try_into().unwrap());
+            assert_eq!(level_offset % 8, 0, "level {i}");
+        }
+
+        for decoded in [read_dds(&dds).unwrap(), read_ktx2(&ktx2).unwrap()] {
+            assert_eq!(decoded.len(), levels.len());
+            for (level, d) in decoded.iter().enumerate() {
+                assert_eq!(d.dimensions(), ((8 >> level).max(1), (8 >> level).max(1)));
+                assert!(d.pixels().all(|p| p.0 == color), "level {level}");
+            }
+        }
+    }
+}
@@ -2,11 +2,22 @@ use std::io::{Read, Seek};
 
 use anyhow::Context;
 use bitflags::bitflags;
-use eurochef_edb::{binrw::BinReaderExt, edb::EdbFile, texture::EXGeoTexture, Hashcode};
-use image::RgbaImage;
+use eurochef_edb::{
+    binrw::BinReaderExt,
+    common::EXRelPtr,
+    edb::{EdbFile, EdbPatcher},
+    texture::EXGeoTexture,
+    versions::Platform,
+    Hashcode,
+};
+use image::{
+    imageops::{self, FilterType},
+    RgbaImage,
+};
+use tracing::debug;
 
 use crate::{
-    platform::texture::{self, TextureDecoder},
+    platform::texture::{self, BlockFormat, TextureDecoder, TextureEncoder},
     IdentifiableResult,
 };
 
@@ -29,9 +40,24 @@ pub struct UXGeoTexture {
     pub framerate: u8,
     pub frame_count: u8,
 
-    /// Decoded RGBA frame data
+    /// Number of mip levels below the top level, as specified by the texture
+    pub mip_count: u8,
+
+    /// Decoded RGBA frame data (top level only).
+    /// Volume textures are stored as a vertical atlas of all slices
     pub frames: Vec<Vec<u8>>,
 
+    /// Decoded RGBA data for each mip level below the top level, per frame.
+    /// Levels that could not be read or decoded are left out, so this might be shorter than `mip_count`
+    pub mips: Vec<Vec<Vec<u8>>>,
+
+    /// Block format of the original texture data, if it maps directly to one
+    pub block_format: Option<BlockFormat>,
+
+    /// Original compressed blocks for every level (starting at the top level), per frame.
+    /// Empty if `block_format` is `None`
+    pub blocks: Vec<Vec<Vec<u8>>>,
+
     pub color: [u8; 4],
 
     pub external_texture: Option<(Hashcode, Hashcode)>,
@@ -97,7 +123,8 @@ impl UXGeoTexture {
             .context("Failed to read texture")?;
 
         if let Some(external_file) = tex.external_file {
-            let external_texture = tex.frame_offsets[0].offset_relative() as u32;
+            let external_texture =
+                tex.frame_offsets[0].offset_relative().unwrap_or_default() as u32;
             edb.add_reference(external_file, external_texture);
             return Ok(UXGeoTexture {
                 width: tex.width,
@@ -109,23 +136,34 @@ impl UXGeoTexture {
                 framerate: tex.frame_rate,
                 frame_count: 0,
                 scroll: [tex.scroll_u, tex.scroll_v],
+                mip_count: tex.mip_count,
                 frames: vec![],
+                mips: vec![],
+                block_format: None,
+                blocks: vec![],
                 color: tex.color,
                 diagnostics: Default::default(),
                 external_texture: Some((external_file, external_texture)),
             });
         }
 
-        let calculated_size = texture_decoder
-            .get_data_size(
-                tex.width as u32,
-                tex.height as u32,
-                tex.depth as u32,
-                tex.format,
-            )
+        // Mipmaps are stored right after the top level
+        let level_sizes = (0..=tex.mip_count as u32)
+            .map(|level| {
+                let (width, height, depth) =
+                    mip_dimensions(tex.width, tex.height, tex.depth, level);
+                texture_decoder.get_data_size(width, height, depth, tex.format)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
             .context("Failed to get data size")?;
+        let calculated_size = level_sizes[0];
 
-        let data_size = tex.data_size.map(|v| v as usize).unwrap_or(calculated_size);
+        // GX data starts with a header that is shared by all levels
+        let header_size = if edb.platform.is_gx() { 64 } else { 0 };
+        let data_size = tex
+            .data_size
+            .map(|v| v as usize)
+            .unwrap_or(header_size + level_sizes.iter().sum::<usize>());
 
         if data_size == 0 {
             return Err(anyhow::anyhow!(
@@ -135,8 +173,10 @@ impl UXGeoTexture {
             ));
         }
 
-        let mut data = vec![0u8; data_size];
-        let mut output = RgbaImage::new(tex.width as u32, tex.height as u32);
+        let mut data = Vec::with_capacity(data_size);
+        // Volume textures are decoded as a vertical atlas of all slices
+        let depth = tex.depth.max(1) as u32;
+        let mut output = RgbaImage::new(tex.width as u32, tex.height as u32 * depth);
         let mut texture = UXGeoTexture {
             width: tex.width,
             height: tex.height,
@@ -147,7 +187,11 @@ impl UXGeoTexture {
             framerate: tex.frame_rate,
             frame_count: tex.frame_count,
             scroll: [tex.scroll_u, tex.scroll_v],
+            mip_count: tex.mip_count,
             frames: Vec::with_capacity(tex.frame_count as usize),
+            mips: Vec::with_capacity(tex.frame_count as usize),
+            block_format: None,
+            blocks: vec![],
             color: tex.color,
             diagnostics: Default::default(),
             external_texture: None,
@@ -164,8 +208,18 @@ impl UXGeoTexture {
 
         for (i, frame_offset) in tex.frame_offsets.iter().enumerate() {
             edb.seek(std::io::SeekFrom::Start(frame_offset.offset_absolute()))?;
-            edb.read_exact(&mut data)
+            data.clear();
+            edb.by_ref()
+                .take(data_size as u64)
+                .read_to_end(&mut data)
                 .context(format!("Failed to read frame {i}"))?;
+            if data.len() < header_size + calculated_size {
+                return Err(anyhow::anyhow!(
+                    "Failed to read frame {i} (expected {} bytes, got {})",
+                    header_size + calculated_size,
+                    data.len()
+                ));
+            }
 
             if edb.header.version == 156 && clut.is_empty() {
                 let clut_size = texture_decoder.get_clut_size(tex.format)?;
@@ -181,27 +235,149 @@ impl UXGeoTexture {
                     &mut output,
                     tex.width as u32,
                     tex.height as u32,
-                    tex.depth as u32,
+                    depth,
                     tex.format,
                     edb.header.version,
                 )
                 .context("Failed to decode texture")?;
 
-            if output.len() != (tex.width as usize * tex.height as usize) * 4 {
+            if output.len() != (tex.width as usize * tex.height as usize * depth as usize) * 4 {
                 return Err(anyhow::anyhow!(
                     "Texture has mismatching data length (expected {}, got {})",
-                    (tex.width as usize * tex.height as usize) * 4,
+                    (tex.width as usize * tex.height as usize * depth as usize) * 4,
                     output.len()
                 ));
             }
 
             texture.frames.push(output.clone().into_vec());
+            texture.mips.push(Self::decode_mips(
+                &data,
+                &clut,
+                &level_sizes,
+                header_size,
+                &tex,
+                texture_decoder,
+                edb.header.version,
+            ));
+
+            // Keep the compressed blocks around as long as every frame has them
+            if i == 0 || texture.block_format.is_some() {
+                match Self::extract_blocks(&data, &level_sizes, header_size, &tex, texture_decoder)
+                {
+                    Some((block_format, blocks))
+                        if i == 0 || texture.block_format == Some(block_format) =>
+                    {
+                        texture.block_format = Some(block_format);
+                        texture.blocks.push(blocks);
+                    }
+                    _ => {
+                        texture.block_format = None;
+                        texture.blocks.clear();
+                    }
+                }
+            }
         }
 
         texture.calculate_diagnostics();
         Ok(texture)
     }
 
+    /// Decodes every mip level below the top level, stopping at the first level that can't be decoded
+    fn decode_mips(
+        data: &[u8],
+        clut: &[u8],
+        level_sizes: &[usize],
+        header_size: usize,
+        tex: &EXGeoTexture,
+        texture_decoder: &dyn TextureDecoder,
+        version: u32,
+    ) -> Vec<Vec<u8>> {
+        let mut mips = vec![];
+        for level in 1..level_sizes.len() {
+            let Some(level_data) = level_data(data, level_sizes, header_size, level) else {
+                debug!("Mip level {level} is outside of the texture data");
+                break;
+            };
+
+            let (width, height, depth) =
+                mip_dimensions(tex.width, tex.height, tex.depth, level as u32);
+            let mut output = RgbaImage::new(width, height * depth);
+            if let Err(e) = texture_decoder.decode(
+                &level_data,
+                if !clut.is_empty() { Some(clut) } else { None },
+                &mut output,
+                width,
+                height,
+                depth,
+                tex.format,
+                version,
+            ) {
+                debug!("Failed to decode mip level {level}: {e}");
+                break;
+            }
+
+            mips.push(output.into_vec());
+        }
+
+        mips
+    }
+
+    /// Extracts the compressed blocks of every level, stopping at the first level that can't be extracted.
+    /// Returns `None` if the format doesn't map to a block format
+    fn extract_blocks(
+        data: &[u8],
+        level_sizes: &[usize],
+        header_size: usize,
+        tex: &EXGeoTexture,
+        texture_decoder: &dyn TextureDecoder,
+    ) -> Option<(BlockFormat, Vec<Vec<u8>>)> {
+        let mut block_format = None;
+        let mut levels = vec![];
+        for level in 0..level_sizes.len() {
+            let Some(level_data) = level_data(data, level_sizes, header_size, level) else {
+                break;
+            };
+
+            let (width, height, depth) =
+                mip_dimensions(tex.width, tex.height, tex.depth, level as u32);
+            match texture_decoder.extract_blocks(&level_data, width, height, depth, tex.format) {
+                Ok(Some((format, blocks))) => {
+                    block_format = Some(format);
+                    levels.push(blocks);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("Failed to extract blocks for mip level {level}: {e}");
+                    break;
+                }
+            }
+        }
+
+        block_format.map(|f| (f, levels))
+    }
+
+    /// Width, height and depth of a mip level, level 0 being the top level
+    pub fn mip_dimensions(&self, level: u32) -> (u32, u32, u32) {
+        mip_dimensions(self.width, self.height, self.depth, level)
+    }
+
+    /// Returns the top level followed by every decoded mip level for a frame
+    pub fn mip_chain(&self, frame: usize) -> Vec<&[u8]> {
+        let mut chain = vec![];
+        if let Some(f) = self.frames.get(frame) {
+            chain.push(f.as_slice());
+            chain.extend(
+                self.mips
+                    .get(frame)
+                    .into_iter()
+                    .flatten()
+                    .map(|m| m.as_slice()),
+            );
+        }
+
+        chain
+    }
+
     pub fn is_valid(&self) -> bool {
         self.flags != u32::MAX && self.game_flags != u16::MAX
     }
@@ -233,6 +409,190 @@ impl UXGeoTexture {
     }
 }
 
+/// Encodes new frames for a texture and appends them to the file, updating the frame offsets, data size and CLUT offset of `tex`.
+///
+/// Every frame contains the top level followed by any available mip levels, volume textures are given as a vertical atlas of all slices.
+/// Mip levels that are missing or don't have the right dimensions are generated from the top level.
+/// On GX, the 64-byte header of every frame is copied from `base` when given, `tex` must still point at the original frames then.
+/// New frames are always appended, the original frame data (and CLUT) is left in the file as unused space
+pub fn write_frames(
+    tex: &mut EXGeoTexture,
+    frames: &[Vec<RgbaImage>],
+    encoder: &dyn TextureEncoder,
+    writer: &mut EdbPatcher,
+    mut base: Option<&mut EdbFile>,
+) -> anyhow::Result<()> {
+    let version = writer.header.version;
+    anyhow::ensure!(
+        tex.external_file.is_none(),
+        "Texture is stored in another file"
+    );
+    anyhow::ensure!(
+        frames.len() == tex.frame_offsets.len(),
+        "Expected {} frames, got {}",
+        tex.frame_offsets.len(),
+        frames.len()
+    );
+
+    // All frames share a single CLUT, except for version 156 where it follows every frame
+    if tex.clut_offset.is_some() && frames.len() > 1 {
+        anyhow::bail!("Paletted textures with multiple frames are not supported yet");
+    }
+
+    // Every level would be quantized to its own palette, while the texture only has one
+    if encoder.is_paletted(tex.format) && tex.mip_count > 0 {
+        anyhow::bail!("Paletted textures with mip levels are not supported yet");
+    }
+
+    let mut frame_offsets = vec![];
+    for (i, levels) in frames.iter().enumerate() {
+        let (mut data, clut) = encode_frame(tex, levels, encoder, writer.platform, version)
+            .with_context(|| format!("Failed to encode frame {i}"))?;
+
+        if writer.platform.is_gx() {
+            if let Some(base) = base.as_deref_mut() {
+                // Keep the original GX header, the encoder only fills in the format
+                base.seek(std::io::SeekFrom::Start(
+                    tex.frame_offsets[i].offset_absolute(),
+                ))?;
+                base.read_exact(&mut data[..64])?;
+            }
+        }
+
+        if tex.data_size.is_some() {
+            tex.data_size = Some(data.len() as u32);
+        }
+
+        if let Some(clut) = clut {
+            if version == 156 {
+                data.extend(clut);
+            } else {
+                tex.clut_offset = Some(EXRelPtr::new_absolute(writer.append_bytes(&clut) as u64));
+            }
+        }
+
+        frame_offsets.push(EXRelPtr::new_absolute(writer.append_bytes(&data) as u64));
+    }
+
+    tex.frame_offsets = frame_offsets;
+
+    Ok(())
+}
+
+/// Encodes the top level of a frame followed by its mip levels.
+/// Returns the level data (behind a single GX header) and the CLUT of the top level
+fn encode_frame(
+    tex: &EXGeoTexture,
+    levels: &[RgbaImage],
+    encoder: &dyn TextureEncoder,
+    platform: Platform,
+    version: u32,
+) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+    let top = levels.first().context("Frame has no data")?;
+    let (width, height, depth) = mip_dimensions(tex.width, tex.height, tex.depth, 0);
+    anyhow::ensure!(
+        top.dimensions() == (width, height * depth),
+        "Frame is {}x{}, expected {width}x{}",
+        top.width(),
+        top.height(),
+        height * depth
+    );
+
+    let (mut data, clut) = encoder.encode(top, width, height, depth, tex.format, version)?;
+
+    // Mipmaps are stored right after the top level
+    for level in 1..=tex.mip_count as u32 {
+        let (mip_width, mip_height, mip_depth) =
+            mip_dimensions(tex.width, tex.height, tex.depth, level);
+        let generated;
+        let mip = match levels.get(level as usize) {
+            Some(m) if m.dimensions() == (mip_width, mip_height * mip_depth) => m,
+            _ => {
+                generated = resize_volume(
+                    top,
+                    depth,
+                    mip_width,
+                    mip_height,
+                    mip_depth,
+                    FilterType::Triangle,
+                );
+                &generated
+            }
+        };
+
+        let (mip_data, _) =
+            encoder.encode(mip, mip_width, mip_height, mip_depth, tex.format, version)?;
+        if platform.is_gx() {
+            data.extend_from_slice(&mip_data[64..]);
+        } else {
+            data.extend(mip_data);
+        }
+    }
+
+    Ok((data, clut))
+}
+
+/// Resizes a vertical atlas of `depth` slices to `width`x`height`x`new_depth`.
+/// Every slice is resized separately, slices that end up in the same output slice are averaged
+pub fn resize_volume(
+    image: &RgbaImage,
+    depth: u32,
+    width: u32,
+    height: u32,
+    new_depth: u32,
+    filter: FilterType,
+) -> RgbaImage {
+    let depth = depth.max(1);
+    let slice_height = image.height() / depth;
+    let slice_len = (width * height * 4) as usize;
+
+    let mut output = RgbaImage::new(width, height * new_depth);
+    for z in 0..new_depth {
+        let start = z * depth / new_depth;
+        let end = ((z + 1) * depth / new_depth).max(start + 1);
+
+        let mut sum = vec![0u32; slice_len];
+        for s in start..end {
+            let slice = imageops::crop_imm(image, 0, s * slice_height, image.width(), slice_height);
+            let resized = imageops::resize(&slice.to_image(), width, height, filter);
+            for (a, &b) in sum.iter_mut().zip(resized.as_raw()) {
+                *a += b as u32;
+            }
+        }
+
+        let count = end - start;
+        for (o, a) in output.as_mut()[z as usize * slice_len..(z as usize + 1) * slice_len]
+            .iter_mut()
+            .zip(sum)
+        {
+            *o = (a / count) as u8;
+        }
+    }
+
+    output
+}
+
+fn mip_dimensions(width: u16, height: u16, depth: u16, level: u32) -> (u32, u32, u32) {
+    (
+        (width as u32 >> level).max(1),
+        (height as u32 >> level).max(1),
+        (depth as u32 >> level).max(1),
+    )
+}
+
+/// Returns the data for a single level. The GX header is put in front of every level, as the GX decoder expects it
+fn level_data(
+    data: &[u8],
+    level_sizes: &[usize],
+    header_size: usize,
+    level: usize,
+) -> Option<Vec<u8>> {
+    let offset = header_size + level_sizes[..level].iter().sum::<usize>();
+    let level_data = data.get(offset..offset + level_sizes[level])?;
+
+    Some([&data[..header_size], level_data].concat())
+}
+
 bitflags! {
     #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
     pub struct UXTextureDiagnostics: u32 {
@@ -259,3 +619,138 @@ impl UXTextureDiagnostics {
         strings
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, SeekFrom};
+
+    use eurochef_edb::binrw::BinWriterExt;
+
+    use super::*;
+
+    /// Builds a big endian (v259) EDB with a single 4x4 texture.
+    /// PS2 textures have a CLUT, which is stored right after the texture data
+    fn build_edb(
+        platform: Platform,
+        depth: u16,
+        mip_count: u8,
+        format: u8,
+        data_size: usize,
+    ) -> Vec<u8> {
+        let size = 0x140 + data_size as u32;
+        let mut cur = Cursor::new(vec![0u8; size as usize]);
+        let mut w = |offset: u64, values: &[u32]| {
+            cur.seek(SeekFrom::Start(offset)).unwrap();
+            for v in values {
+                cur.write_be(v).unwrap();
+            }
+        };
+
+        // Magic, hashcode, version, flags, time, file size, base file size
+        w(0x0, &[0x47454F4D, 0x80000001, 259, 0, 0, size, size]);
+        // Texture list (1 entry, hash size 1)
+        w(0xb8, &[0x00010001, 0xd0 - 0xbc]);
+        w(0xd0, &[0x06000000, 0, 0x100, 0, (4 << 16) | 4, 0, 0]);
+        // Texture with a single frame, no external file and no extra data
+        w(
+            0x100,
+            &[
+                (4 << 16) | 4,
+                (depth as u32) << 16,
+                0,
+                0x01010000,
+                ((mip_count as u32) << 8) | format as u32,
+                0,
+                0,
+                u32::MAX,
+                0,
+                0,
+            ],
+        );
+        if platform == Platform::Ps2 {
+            w(0x128, &[0, 0x140 + 4 * 4 / 2 - 0x12c, 0x140 - 0x130]);
+        } else {
+            w(0x128, &[0x140 - 0x128]);
+        }
+
+        cur.into_inner()
+    }
+
+    fn open(data: Vec<u8>, platform: Platform) -> EdbFile {
+        EdbFile::new(Box::new(Cursor::new(data)), platform).unwrap()
+    }
+
+    fn write(edb: &mut EdbFile, frame: RgbaImage) -> anyhow::Result<Vec<u8>> {
+        let platform = edb.platform;
+        let mut writer = EdbPatcher::new(edb)?;
+        edb.seek(SeekFrom::Start(0x100))?;
+        let mut tex = edb.read_type_args::<EXGeoTexture>(edb.endian, (259, platform))?;
+
+        let encoder = texture::create_encoder_for_platform(platform)?;
+        write_frames(
+            &mut tex,
+            &[vec![frame]],
+            encoder.as_ref(),
+            &mut writer,
+            None,
+        )?;
+        writer.write_at(0x100, &tex, (259, platform))?;
+
+        Ok(writer.finish()?)
+    }
+
+    #[test]
+    fn write_volume_frames() {
+        // ARGB8 volume, 4x4x2 with a 2x2x1 mip level
+        let data = build_edb(Platform::Pc, 2, 1, 6, 4 * 4 * 2 * 4 + 2 * 2 * 4);
+        let frame = RgbaImage::from_fn(4, 8, |x, y| [x as u8 * 60, y as u8 * 30, 0, 255].into());
+        let mut edb = open(
+            write(&mut open(data, Platform::Pc), frame.clone()).unwrap(),
+            Platform::Pc,
+        );
+
+        let decoder = texture::create_for_platform(Platform::Pc);
+        let texture = UXGeoTexture::read(0x100, &mut edb, decoder.as_ref(), 0).unwrap();
+        assert_eq!(texture.frames, vec![frame.into_vec()]);
+        assert_eq!(texture.mips[0].len(), 1);
+        assert_eq!(texture.mips[0][0].len(), 2 * 2 * 4);
+    }
+
+    #[test]
+    fn resize_volume_slices() {
+        // Slices are resized separately, and averaged when the depth is halved
+        let image = RgbaImage::from_fn(2, 8, |_, y| {
+            let v = [0, 100, 200, 250][y as usize / 2];
+            [v, v, v, 255].into()
+        });
+
+        let resized = resize_volume(&image, 4, 4, 4, 4, FilterType::Nearest);
+        assert_eq!(resized.dimensions(), (4, 16));
+        assert_eq!(resized.get_pixel(0, 4).0, [100, 100, 100, 255]);
+
+        let halved = resize_volume(&image, 4, 1, 1, 2, FilterType::Nearest);
+        assert_eq!(halved.dimensions(), (1, 2));
+        assert_eq!(halved.get_pixel(0, 0).0, [50, 50, 50, 255]);
+        assert_eq!(halved.get_pixel(0, 1).0, [225, 225, 225, 255]);
+    }
+
+    #[test]
+    fn wrong_dimensions() {
+        let mut edb = open(
+            build_edb(Platform::Pc, 2, 0, 6, 4 * 4 * 2 * 4),
+            Platform::Pc,
+        );
+        assert!(write(&mut edb, RgbaImage::new(4, 4)).is_err());
+    }
+
+    #[test]
+    fn paletted_mips() {
+        // PS2 P16, the 4-bit indices are followed by the CLUT
+        let data = build_edb(Platform::Ps2, 1, 0, 1, 4 * 4 / 2 + 16 * 4);
+        assert!(write(&mut open(data, Platform::Ps2), RgbaImage::new(4, 4)).is_ok());
+
+        let data = build_edb(Platform::Ps2, 1, 1, 1, 4 * 4 / 2 + 16 * 4);
+        let e = write(&mut open(data, Platform::Ps2), RgbaImage::new(4, 4)).unwrap_err();
+        assert!(e.to_string().contains("mip levels"));
+    }
+}